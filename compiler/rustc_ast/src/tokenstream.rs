@@ -296,6 +296,17 @@ pub struct AttrsTarget {
 #[derive(Clone, Debug, Default, Encodable, Decodable)]
 pub struct TokenStream(pub(crate) Lrc<Vec<TokenTree>>);
 
+/// Where the tokens of a `TokenStream` came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenStreamProvenance {
+    /// The tokens were lexed from source code, or made by a macro.
+    Verbatim,
+    /// The tokens were made by pretty-printing an AST node whose own tokens weren't available,
+    /// and lexing the result. They can differ from the source code, e.g. in parentheses, in the
+    /// form of literals, and in their spans.
+    PrettyPrinted,
+}
+
 /// Indicates whether a token can join with the following token to form a
 /// compound token. Used for conversions to `proc_macro::Spacing`. Also used to
 /// guide pretty-printing, which is where the `JointHidden` value (which isn't
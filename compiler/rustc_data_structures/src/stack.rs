@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 // This is the amount of bytes that need to be left on the stack before increasing the size.
 // It must be at least as large as the stack required by any code that does not call
 // `ensure_sufficient_stack`.
@@ -5,7 +7,15 @@
 
 // Only the first stack that is pushed, grows exponentially (2^n * STACK_PER_RECURSION) from then
 // on. This flag has performance relevant characteristics. Don't set it too high.
-const STACK_PER_RECURSION: usize = 1024 * 1024; // 1MB
+// It can be changed with `-Zstack-segment-size`, see `set_stack_per_recursion`.
+static STACK_PER_RECURSION: AtomicUsize = AtomicUsize::new(1024 * 1024); // 1MB
+
+/// Sets the size of the stacks that `ensure_sufficient_stack` allocates when it runs out of
+/// stack, e.g. to grow it in larger steps for deeply nested generated code. Sizes below twice the
+/// red zone are rounded up to that, as smaller stacks would have to grow again right away.
+pub fn set_stack_per_recursion(bytes: usize) {
+    STACK_PER_RECURSION.store(bytes.max(2 * RED_ZONE), Ordering::Relaxed);
+}
 
 /// Grows the stack on demand to prevent stack overflow. Call this in strategic locations
 /// to "break up" recursive calls. E.g. almost any call to `visit_expr` or equivalent can benefit
@@ -14,5 +24,5 @@
 /// Should not be sprinkled around carelessly, as it causes a little bit of overhead.
 #[inline]
 pub fn ensure_sufficient_stack<R>(f: impl FnOnce() -> R) -> R {
-    stacker::maybe_grow(RED_ZONE, STACK_PER_RECURSION, f)
+    stacker::maybe_grow(RED_ZONE, STACK_PER_RECURSION.load(Ordering::Relaxed), f)
 }
@@ -41,16 +41,14 @@
 };
 use rustc_errors::emitter::stderr_destination;
 use rustc_errors::registry::Registry;
-use rustc_errors::{
-    markdown, ColorConfig, DiagCtxt, ErrCode, ErrorGuaranteed, FatalError, PResult,
-};
+use rustc_errors::{markdown, ColorConfig, Diag, DiagCtxt, ErrCode, ErrorGuaranteed, FatalError};
 use rustc_feature::find_gated_cfg;
 use rustc_interface::util::{self, get_codegen_backend};
 use rustc_interface::{interface, passes, Linker, Queries};
 use rustc_lint::unerased_lint_store;
 use rustc_metadata::creader::MetadataLoader;
 use rustc_metadata::locator;
-use rustc_parse::{new_parser_from_file, new_parser_from_source_str, unwrap_or_emit_fatal};
+use rustc_parse::{parse_crate_attrs_from_file, parse_crate_attrs_from_source_str};
 use rustc_session::config::{
     nightly_options, ErrorOutputType, Input, OutFileName, OutputType, UnstableOptions, CG_OPTIONS,
     Z_OPTIONS,
@@ -712,8 +710,10 @@ fn print_crate_info(
         let result = parse_crate_attrs(sess);
         match result {
             Ok(attrs) => Some(attrs),
-            Err(parse_error) => {
-                parse_error.emit();
+            Err(parse_errors) => {
+                for err in parse_errors {
+                    err.emit();
+                }
                 return Compilation::Stop;
             }
         }
@@ -1267,14 +1267,13 @@ pub fn handle_options(early_dcx: &EarlyDiagCtxt, args: &[String]) -> Option<geto
     Some(matches)
 }
 
-fn parse_crate_attrs<'a>(sess: &'a Session) -> PResult<'a, ast::AttrVec> {
-    let mut parser = unwrap_or_emit_fatal(match &sess.io.input {
-        Input::File(file) => new_parser_from_file(&sess.psess, file, None),
+fn parse_crate_attrs<'a>(sess: &'a Session) -> Result<ast::AttrVec, Vec<Diag<'a>>> {
+    match &sess.io.input {
+        Input::File(file) => parse_crate_attrs_from_file(file, &sess.psess),
         Input::Str { name, input } => {
-            new_parser_from_source_str(&sess.psess, name.clone(), input.clone())
+            parse_crate_attrs_from_source_str(name.clone(), input.clone(), &sess.psess)
         }
-    });
-    parser.parse_inner_attributes()
+    }
 }
 
 /// Runs a closure and catches unwinds triggered by fatal errors.
@@ -261,6 +261,10 @@ pub fn print<'tcx>(sess: &Session, ppm: PpMode, ex: PrintExtra<'tcx>) {
             debug!("pretty-printing expanded AST");
             format!("{:#?}", ex.tcx().resolver_for_lowering().borrow().1)
         }
+        ParseTreeJson => {
+            debug!("dumping parse tree as JSON");
+            ex.with_krate(|krate| rustc_parse::parse_tree_json(sess.source_map(), krate))
+        }
         Hir(s) => {
             debug!("pretty printing HIR {:?}", s);
             let tcx = ex.tcx();
@@ -30,6 +30,7 @@ expand_count_repetition_misplaced =
 expand_custom_attribute_panicked =
     custom attribute panicked
     .help = message: {$message}
+    .note = the attribute was given the item pretty-printed, as its tokens weren't available
 
 expand_duplicate_matcher_binding = duplicate matcher binding
     .label = duplicate binding
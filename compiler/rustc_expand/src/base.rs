@@ -6,7 +6,7 @@
 use rustc_ast::attr::MarkedAttrs;
 use rustc_ast::ptr::P;
 use rustc_ast::token::Nonterminal;
-use rustc_ast::tokenstream::TokenStream;
+use rustc_ast::tokenstream::{TokenStream, TokenStreamProvenance};
 use rustc_ast::visit::{AssocCtxt, Visitor};
 use rustc_ast::{self as ast, AttrVec, Attribute, HasAttrs, Item, NodeId, PatKind};
 use rustc_attr::{self as attr, Deprecation, Stability};
@@ -300,12 +300,15 @@ fn expand<'cx>(
 }
 
 pub trait AttrProcMacro {
+    /// `annotated_provenance` says whether `annotated` are the tokens of the item, or were made
+    /// by pretty-printing it, as they weren't available.
     fn expand<'cx>(
         &self,
         ecx: &'cx mut ExtCtxt<'_>,
         span: Span,
         annotation: TokenStream,
         annotated: TokenStream,
+        annotated_provenance: TokenStreamProvenance,
     ) -> Result<TokenStream, ErrorGuaranteed>;
 }
 
@@ -319,6 +322,7 @@ fn expand<'cx>(
         _span: Span,
         annotation: TokenStream,
         annotated: TokenStream,
+        _annotated_provenance: TokenStreamProvenance,
     ) -> Result<TokenStream, ErrorGuaranteed> {
         // FIXME setup implicit context in TLS before calling self.
         Ok(self(annotation, annotated))
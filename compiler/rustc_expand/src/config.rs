@@ -13,7 +13,7 @@
 };
 use rustc_lint_defs::BuiltinLintDiag;
 use rustc_parse::validate_attr;
-use rustc_session::parse::feature_err;
+use rustc_session::parse::{feature_err, CfgStrippedNode};
 use rustc_session::Session;
 use rustc_span::symbol::{sym, Symbol};
 use rustc_span::Span;
@@ -158,10 +158,23 @@ macro_rules! configure {
 impl<'a> StripUnconfigured<'a> {
     pub fn configure<T: HasAttrs + HasTokens>(&self, mut node: T) -> Option<T> {
         self.process_cfg_attrs(&mut node);
-        self.in_cfg(node.attrs()).then(|| {
-            self.try_configure_tokens(&mut node);
-            node
-        })
+        if !self.in_cfg(node.attrs()) {
+            self.keep_stripped_node(&node);
+            return None;
+        }
+        self.try_configure_tokens(&mut node);
+        Some(node)
+    }
+
+    /// Keeps `node`, which is being removed, if the driver asked for that, see
+    /// `ParseSess::enable_cfg_stripped_tokens`.
+    fn keep_stripped_node<T: HasAttrs + HasTokens>(&self, node: &T) {
+        if let Some(nodes) = &self.sess.psess.cfg_stripped_nodes
+            && let Some(tokens) = node.tokens()
+        {
+            let attrs = node.attrs().iter().cloned().collect();
+            nodes.lock().push(CfgStrippedNode { attrs, tokens: tokens.clone() });
+        }
     }
 
     fn try_configure_tokens<T: HasTokens>(&self, node: &mut T) {
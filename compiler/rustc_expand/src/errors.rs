@@ -392,6 +392,8 @@ pub(crate) struct CustomAttributePanicked {
     pub span: Span,
     #[subdiagnostic]
     pub message: Option<CustomAttributePanickedHelp>,
+    #[note]
+    pub pretty_printed_input: bool,
 }
 
 #[derive(Subdiagnostic)]
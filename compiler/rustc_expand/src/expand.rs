@@ -7,7 +7,7 @@
 use rustc_ast::mut_visit::*;
 use rustc_ast::ptr::P;
 use rustc_ast::token::{self, Delimiter};
-use rustc_ast::tokenstream::TokenStream;
+use rustc_ast::tokenstream::{TokenStream, TokenStreamProvenance};
 use rustc_ast::visit::{self, try_visit, walk_list, AssocCtxt, Visitor, VisitorResult};
 use rustc_ast::{
     AssocItemKind, AstNodeWrapper, AttrArgs, AttrStyle, AttrVec, ExprKind, ForeignItemKind,
@@ -705,15 +705,16 @@ fn expand_invoc(
                 SyntaxExtensionKind::Attr(expander) => {
                     self.gate_proc_macro_input(&item);
                     self.gate_proc_macro_attr_item(span, &item);
-                    let tokens = match &item {
+                    let (tokens, provenance) = match &item {
                         // FIXME: Collect tokens and use them instead of generating
                         // fake ones. These are unstable, so it needs to be
                         // fixed prior to stabilization
                         // Fake tokens when we are invoking an inner attribute, and
                         // we are invoking it on an out-of-line module or crate.
-                        Annotatable::Crate(krate) => {
-                            rustc_parse::fake_token_stream_for_crate(&self.cx.sess.psess, krate)
-                        }
+                        Annotatable::Crate(krate) => (
+                            rustc_parse::fake_token_stream_for_crate(&self.cx.sess.psess, krate),
+                            TokenStreamProvenance::PrettyPrinted,
+                        ),
                         Annotatable::Item(item_inner)
                             if matches!(attr.style, AttrStyle::Inner)
                                 && matches!(
@@ -724,16 +725,18 @@ fn expand_invoc(
                                     )
                                 ) =>
                         {
-                            rustc_parse::fake_token_stream_for_item(&self.cx.sess.psess, item_inner)
+                            let psess = &self.cx.sess.psess;
+                            let tokens = rustc_parse::fake_token_stream_for_item(psess, item_inner);
+                            (tokens, TokenStreamProvenance::PrettyPrinted)
                         }
-                        _ => item.to_tokens(),
+                        _ => (item.to_tokens(), TokenStreamProvenance::Verbatim),
                     };
                     let attr_item = attr.unwrap_normal_item();
                     if let AttrArgs::Eq(..) = attr_item.args {
                         self.cx.dcx().emit_err(UnsupportedKeyValue { span });
                     }
                     let inner_tokens = attr_item.args.inner_tokens();
-                    match expander.expand(self.cx, span, inner_tokens, tokens) {
+                    match expander.expand(self.cx, span, inner_tokens, tokens, provenance) {
                         Ok(tok_result) => self.parse_ast_fragment(
                             tok_result,
                             fragment_kind,
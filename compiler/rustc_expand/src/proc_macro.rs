@@ -1,6 +1,6 @@
 use rustc_ast as ast;
 use rustc_ast::ptr::P;
-use rustc_ast::tokenstream::TokenStream;
+use rustc_ast::tokenstream::{TokenStream, TokenStreamProvenance};
 use rustc_errors::ErrorGuaranteed;
 use rustc_parse::parser::{ForceCollect, Parser};
 use rustc_session::config::ProcMacroExecutionStrategy;
@@ -79,6 +79,7 @@ fn expand<'cx>(
         span: Span,
         annotation: TokenStream,
         annotated: TokenStream,
+        annotated_provenance: TokenStreamProvenance,
     ) -> Result<TokenStream, ErrorGuaranteed> {
         let _timer =
             ecx.sess.prof.generic_activity_with_arg_recorder("expand_proc_macro", |recorder| {
@@ -86,6 +87,7 @@ fn expand<'cx>(
             });
 
         let proc_macro_backtrace = ecx.ecfg.proc_macro_backtrace;
+        let pretty_printed_input = annotated_provenance == TokenStreamProvenance::PrettyPrinted;
         let strategy = exec_strategy(ecx);
         let server = proc_macro_server::Rustc::new(ecx);
         self.client.run(&strategy, server, annotation, annotated, proc_macro_backtrace).map_err(
@@ -95,6 +97,7 @@ fn expand<'cx>(
                     message: e.as_str().map(|message| errors::CustomAttributePanickedHelp {
                         message: message.into(),
                     }),
+                    pretty_printed_input,
                 })
             },
         )
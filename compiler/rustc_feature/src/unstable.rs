@@ -479,6 +479,9 @@ pub fn internal(&self, feature: Symbol) -> bool {
     (incomplete, fn_delegation, "1.76.0", Some(118212)),
     /// Allows impls for the Freeze trait.
     (internal, freeze_impls, "1.78.0", Some(121675)),
+    /// Allows a frontmatter block fenced by `---` at the start of a file, e.g. the manifest of a
+    /// cargo script.
+    (unstable, frontmatter, "CURRENT_RUSTC_VERSION", Some(136889)),
     /// Allows defining gen blocks and `gen fn`.
     (unstable, gen_blocks, "1.75.0", Some(117078)),
     /// Infer generic args for both consts and types.
@@ -376,6 +376,11 @@ pub fn run_compiler<R: Send>(config: Config, f: impl FnOnce(&Compiler) -> R + Se
     // Set parallel mode before thread pool creation, which will create `Lock`s.
     rustc_data_structures::sync::set_dyn_thread_safe_mode(config.opts.unstable_opts.threads > 1);
 
+    // Set the size of new stack segments before any code that may need them runs.
+    if let Some(size) = config.opts.unstable_opts.stack_segment_size {
+        rustc_data_structures::stack::set_stack_per_recursion(size);
+    }
+
     // Check jobserver before run_in_thread_pool_with_globals, which call jobserver::acquire_thread
     let early_dcx = EarlyDiagCtxt::new(config.opts.error_format);
     initialize_checked_jobserver(&early_dcx);
@@ -22,7 +22,7 @@
 use rustc_middle::ty::{self, GlobalCtxt, RegisteredTools, TyCtxt};
 use rustc_middle::util::Providers;
 use rustc_parse::{
-    new_parser_from_file, new_parser_from_source_str, unwrap_or_emit_fatal, validate_attr,
+    new_parser_from_crate_source_str, new_parser_from_file, unwrap_or_emit_fatal, validate_attr,
 };
 use rustc_passes::{abi_test, hir_stats, layout_test};
 use rustc_resolve::Resolver;
@@ -47,7 +47,7 @@ pub(crate) fn parse<'a>(sess: &'a Session) -> Result<ast::Crate> {
             let mut parser = unwrap_or_emit_fatal(match &sess.io.input {
                 Input::File(file) => new_parser_from_file(&sess.psess, file, None),
                 Input::Str { input, name } => {
-                    new_parser_from_source_str(&sess.psess, name.clone(), input.clone())
+                    new_parser_from_crate_source_str(&sess.psess, name.clone(), input.clone())
                 }
             });
             parser.parse_crate_mod()
@@ -67,6 +67,12 @@ pub(crate) fn parse<'a>(sess: &'a Session) -> Result<ast::Crate> {
         hir_stats::print_ast_stats(&krate, "PRE EXPANSION AST STATS", "ast-stats-1");
     }
 
+    if sess.opts.unstable_opts.verify_parse_roundtrip {
+        sess.time("verify_parse_roundtrip", || {
+            rustc_parse::verify_crate_round_trip(&sess.psess, &krate)
+        });
+    }
+
     Ok(krate)
 }
 
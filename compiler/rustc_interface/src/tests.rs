@@ -727,6 +727,7 @@ macro_rules! untracked {
     untracked!(shell_argfiles, true);
     untracked!(span_debug, true);
     untracked!(span_free_formats, true);
+    untracked!(stack_segment_size, Some(16 * 1024 * 1024));
     untracked!(temps_dir, Some(String::from("abc")));
     untracked!(threads, 99);
     untracked!(time_llvm_passes, true);
@@ -739,6 +740,7 @@ macro_rules! untracked {
     untracked!(unpretty, Some("expanded".to_string()));
     untracked!(unstable_options, true);
     untracked!(validate_mir, true);
+    untracked!(verify_parse_roundtrip, true);
     untracked!(write_long_types_to_disk, false);
     // tidy-alphabetical-end
 
@@ -836,6 +838,7 @@ macro_rules! tracked {
     tracked!(profiler_runtime, "abc".to_string());
     tracked!(relax_elf_relocations, Some(true));
     tracked!(remap_cwd_prefix, Some(PathBuf::from("abc")));
+    tracked!(remap_fake_token_spans, true);
     tracked!(sanitizer, SanitizerSet::ADDRESS);
     tracked!(sanitizer_cfi_canonical_jump_tables, None);
     tracked!(sanitizer_cfi_generalize_pointers, Some(true));
@@ -1,5 +1,7 @@
 use std::str::Chars;
 
+use crate::{is_id_continue, is_whitespace, scan};
+
 /// Peekable iterator over a char sequence.
 ///
 /// Next characters can be peeked via `first` method,
@@ -97,10 +99,49 @@ pub(crate) fn bump(&mut self) -> Option<char> {
 
     /// Eats symbols while predicate returns true or until the end of file is reached.
     pub(crate) fn eat_while(&mut self, mut predicate: impl FnMut(char) -> bool) {
-        // It was tried making optimized version of this for eg. line comments, but
-        // LLVM can inline all of this and compile it down to fast iteration over bytes.
         while predicate(self.first()) && !self.is_eof() {
             self.bump();
         }
     }
+
+    /// Eats whitespace, see [`is_whitespace`].
+    pub(crate) fn eat_whitespace(&mut self) {
+        self.eat_bytes(scan::whitespace_len);
+        // Whitespace that isn't ASCII is rare, so it is eaten one symbol at a time.
+        while is_whitespace(self.first()) {
+            self.bump();
+            self.eat_bytes(scan::whitespace_len);
+        }
+    }
+
+    /// Eats the rest of an identifier, see [`is_id_continue`].
+    pub(crate) fn eat_id_continue(&mut self) {
+        self.eat_bytes(scan::ident_continue_len);
+        while is_id_continue(self.first()) {
+            self.bump();
+            self.eat_bytes(scan::ident_continue_len);
+        }
+    }
+
+    /// Eats symbols up to the next `\n`, or until the end of file is reached.
+    pub(crate) fn eat_until_newline(&mut self) {
+        self.eat_bytes(scan::line_len);
+    }
+
+    /// Eats the first `len` bytes of the rest of the input, as returned by `len`, which must be a
+    /// whole number of symbols.
+    fn eat_bytes(&mut self, len: impl FnOnce(&[u8]) -> usize) {
+        let rest = self.chars.as_str();
+        let len = len(rest.as_bytes());
+        if len == 0 {
+            return;
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            self.prev = rest[..len].chars().next_back().unwrap();
+        }
+
+        self.chars = rest[len..].chars();
+    }
 }
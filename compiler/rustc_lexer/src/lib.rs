@@ -27,6 +27,7 @@
 // tidy-alphabetical-end
 
 mod cursor;
+mod scan;
 pub mod unescape;
 
 #[cfg(test)]
@@ -465,7 +466,7 @@ fn line_comment(&mut self) -> TokenKind {
             _ => None,
         };
 
-        self.eat_while(|c| c != '\n');
+        self.eat_until_newline();
         LineComment { doc_style }
     }
 
@@ -508,7 +509,7 @@ fn block_comment(&mut self) -> TokenKind {
 
     fn whitespace(&mut self) -> TokenKind {
         debug_assert!(is_whitespace(self.prev()));
-        self.eat_while(is_whitespace);
+        self.eat_whitespace();
         Whitespace
     }
 
@@ -524,7 +525,7 @@ fn raw_ident(&mut self) -> TokenKind {
     fn ident_or_unknown_prefix(&mut self) -> TokenKind {
         debug_assert!(is_id_start(self.prev()));
         // Start is already eaten, eat the rest of identifier.
-        self.eat_while(is_id_continue);
+        self.eat_id_continue();
         // Known prefixes must have been handled earlier. So if
         // we see a prefix here, it is definitely an unknown prefix.
         match self.first() {
@@ -691,7 +692,7 @@ fn lifetime_or_char(&mut self) -> TokenKind {
             self.bump();
             self.bump();
             self.bump();
-            self.eat_while(is_id_continue);
+            self.eat_id_continue();
             return RawLifetime;
         }
 
@@ -703,7 +704,7 @@ fn lifetime_or_char(&mut self) -> TokenKind {
         // First symbol can be a number (which isn't a valid identifier start),
         // so skip it without any checks.
         self.bump();
-        self.eat_while(is_id_continue);
+        self.eat_id_continue();
 
         match self.first() {
             // Check if after skipping literal contents we've met a closing
@@ -911,6 +912,6 @@ fn eat_identifier(&mut self) {
         }
         self.bump();
 
-        self.eat_while(is_id_continue);
+        self.eat_id_continue();
     }
 }
@@ -0,0 +1,131 @@
+//! Fast paths for the loops that the lexer spends most of its time in: runs of whitespace, the
+//! text of line comments, and identifiers. They look at 16 bytes at a time with SSE2 where it is
+//! available, which it always is on x86_64, and fall back to looking at one byte at a time
+//! elsewhere, and for the last few bytes of the input.
+//!
+//! They only handle ASCII, the callers go on one `char` at a time where they stop.
+
+/// Returns the length of the run of ASCII whitespace at the start of `bytes`.
+pub(crate) fn whitespace_len(bytes: &[u8]) -> usize {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    {
+        sse2::whitespace_len(bytes)
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+    {
+        scalar_len(bytes, is_ascii_whitespace)
+    }
+}
+
+/// Returns the length of the run of ASCII identifier characters, i.e. letters, digits and `_`,
+/// at the start of `bytes`.
+pub(crate) fn ident_continue_len(bytes: &[u8]) -> usize {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    {
+        sse2::ident_continue_len(bytes)
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+    {
+        scalar_len(bytes, is_ascii_ident_continue)
+    }
+}
+
+/// Returns the length of the start of `bytes` up to the first `\n`, or all of `bytes` if there
+/// is none.
+pub(crate) fn line_len(bytes: &[u8]) -> usize {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    {
+        sse2::line_len(bytes)
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+    {
+        scalar_len(bytes, |b| b != b'\n')
+    }
+}
+
+/// The same as `crate::is_whitespace`, for ASCII.
+fn is_ascii_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0B' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_ascii_ident_continue(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn scalar_len(bytes: &[u8], predicate: impl Fn(u8) -> bool) -> usize {
+    bytes.iter().position(|&b| !predicate(b)).unwrap_or(bytes.len())
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    use super::{is_ascii_ident_continue, is_ascii_whitespace, scalar_len};
+
+    const LANES: usize = 16;
+
+    /// Returns the length of the start of `bytes` whose bytes are all in `matches`, which returns
+    /// a mask with the bytes of a vector that it matches set to `0xFF`.
+    #[inline(always)]
+    fn run_len(
+        bytes: &[u8],
+        matches: impl Fn(__m128i) -> __m128i,
+        scalar: impl Fn(u8) -> bool,
+    ) -> usize {
+        let mut len = 0;
+        while len + LANES <= bytes.len() {
+            // SAFETY: the 16 bytes from `len` are in `bytes`, and SSE2 is enabled.
+            let mask = unsafe {
+                let v = _mm_loadu_si128(bytes.as_ptr().add(len).cast());
+                _mm_movemask_epi8(matches(v)) as u32
+            };
+            if mask != 0xFFFF {
+                return len + mask.trailing_ones() as usize;
+            }
+            len += LANES;
+        }
+        len + scalar_len(&bytes[len..], scalar)
+    }
+
+    /// Sets the bytes of `v` that are in `lo..=hi` to `0xFF`.
+    #[inline(always)]
+    unsafe fn in_range(v: __m128i, lo: u8, hi: u8) -> __m128i {
+        // `v - lo <= hi - lo` as unsigned bytes, where `min(x, y) == x` is `x <= y`.
+        unsafe {
+            let offset = _mm_sub_epi8(v, _mm_set1_epi8(lo as i8));
+            _mm_cmpeq_epi8(_mm_min_epu8(offset, _mm_set1_epi8((hi - lo) as i8)), offset)
+        }
+    }
+
+    pub(super) fn whitespace_len(bytes: &[u8]) -> usize {
+        // SAFETY: SSE2 is enabled.
+        let matches = |v| unsafe {
+            _mm_or_si128(in_range(v, b'\t', b'\r'), _mm_cmpeq_epi8(v, _mm_set1_epi8(b' ' as i8)))
+        };
+        run_len(bytes, matches, is_ascii_whitespace)
+    }
+
+    pub(super) fn ident_continue_len(bytes: &[u8]) -> usize {
+        // SAFETY: SSE2 is enabled.
+        let matches = |v| unsafe {
+            let lower = _mm_or_si128(v, _mm_set1_epi8(0x20));
+            let alpha = in_range(lower, b'a', b'z');
+            let digit = in_range(v, b'0', b'9');
+            let underscore = _mm_cmpeq_epi8(v, _mm_set1_epi8(b'_' as i8));
+            _mm_or_si128(_mm_or_si128(alpha, digit), underscore)
+        };
+        run_len(bytes, matches, is_ascii_ident_continue)
+    }
+
+    pub(super) fn line_len(bytes: &[u8]) -> usize {
+        // SAFETY: SSE2 is enabled.
+        let matches = |v| unsafe {
+            let newline = _mm_cmpeq_epi8(v, _mm_set1_epi8(b'\n' as i8));
+            _mm_xor_si128(newline, _mm_set1_epi8(-1))
+        };
+        run_len(bytes, matches, |b| b != b'\n')
+    }
+}
@@ -296,3 +296,42 @@ fn literal_suffixes() {
         "#]],
     )
 }
+
+#[test]
+fn scan_matches_char_predicates() {
+    fn check(src: &str) {
+        let expected = |predicate: fn(char) -> bool| {
+            src.find(|c: char| !c.is_ascii() || !predicate(c)).unwrap_or(src.len())
+        };
+        assert_eq!(scan::whitespace_len(src.as_bytes()), expected(is_whitespace), "{src:?}");
+        assert_eq!(scan::ident_continue_len(src.as_bytes()), expected(is_id_continue), "{src:?}");
+        assert_eq!(scan::line_len(src.as_bytes()), src.find('\n').unwrap_or(src.len()), "{src:?}");
+    }
+
+    // Each run is checked at every length, so that it ends in and after the 16-byte chunks.
+    for run in [" \t\r\n\x0B\x0C", "azAZ09_", "é// no newline"] {
+        let long = run.repeat(8);
+        for end in (0..=long.len()).filter(|&end| long.is_char_boundary(end)) {
+            for stop in ["", "\n", "\u{200E}", "é", "-", "\x7F"] {
+                check(&format!("{}{stop}{}", &long[..end], long));
+            }
+        }
+    }
+}
+
+#[test]
+fn long_runs() {
+    let ident = "a_1".repeat(20);
+    let comment = format!("// {}\u{1F980}", "é".repeat(20));
+    let src = format!("{ident}{}\u{200E} \n{comment}\n", " ".repeat(40));
+    let lens: Vec<_> = tokenize(&src).map(|token| (token.kind, token.len)).collect();
+    assert_eq!(
+        lens,
+        [
+            (TokenKind::Ident, 60),
+            (TokenKind::Whitespace, 40 + 3 + 2),
+            (TokenKind::LineComment { doc_style: None }, comment.len() as u32),
+            (TokenKind::Whitespace, 1),
+        ]
+    );
+}
@@ -68,12 +68,23 @@ parse_box_not_pat = expected pattern, found {$descr}
 parse_box_syntax_removed = `box_syntax` has been removed
 parse_box_syntax_removed_suggestion = use `Box::new()` instead
 
+parse_cancelled = parsing was cancelled
+
 parse_cannot_be_raw_ident = `{$ident}` cannot be a raw identifier
 
 parse_catch_after_try = keyword `catch` cannot follow a `try` block
     .help = try using `match` on the result of the `try` block instead
 
 parse_cfg_attr_bad_delim = wrong `cfg_attr` delimiters
+
+parse_cfg_predicate_expected_one = expected 1 cfg-pattern
+
+parse_cfg_predicate_identifier = `cfg` predicate key must be an identifier
+
+parse_cfg_predicate_unsupported_literal = unsupported literal in `cfg` predicate
+
+parse_cfg_predicate_value_not_str = literal in `cfg` predicate value must be a string
+
 parse_colon_as_semi = statements are terminated with a semicolon
     .suggestion = use a semicolon instead
 
@@ -373,6 +384,8 @@ parse_invalid_block_macro_segment = cannot use a `block` macro fragment here
     .label = the `block` fragment is within this context
     .suggestion = wrap this in another block
 
+parse_invalid_cfg_predicate = invalid predicate `{$predicate}`
+
 parse_invalid_char_in_escape = {parse_invalid_char_in_escape_msg}: `{$ch}`
     .label = {parse_invalid_char_in_escape_msg}
 
@@ -624,6 +637,9 @@ parse_nested_adt = `{$kw_str}` definition cannot be nested inside `{$keyword}`
 
 parse_nested_c_variadic_type = C-variadic type `...` may not be nested inside another type
 
+parse_nesting_too_deep = {$kind} nesting too deep
+    .note = the parser's nesting limit is {$limit}
+
 parse_no_brace_unicode_escape = incorrect unicode escape sequence
     .label = {parse_no_brace_unicode_escape}
     .use_braces = format of unicode escape sequences uses braces
@@ -702,6 +718,14 @@ parse_require_colon_after_labeled_expression = labeled expression must be follow
 parse_return_types_use_thin_arrow = return types are denoted using `->`
     .suggestion = use `->` instead
 
+parse_round_trip_added_token = the pretty-printed form of this item has `{$token}` here, which its source doesn't
+
+parse_round_trip_lost_token = the pretty-printed form of this item doesn't have this `{$token}`
+
+parse_round_trip_too_different = the pretty-printed form of this item is too different from its source to compare
+
+parse_round_trip_unlexable = the pretty-printed form of this item doesn't lex: {$message}
+
 parse_self_argument_pointer = cannot pass `self` by raw pointer
     .label = cannot pass `self` by raw pointer
 
@@ -767,6 +791,8 @@ parse_ternary_operator = Rust has no ternary operator
 parse_tilde_is_not_unary_operator = `~` cannot be used as a unary operator
     .suggestion = use `!` to perform bitwise not
 
+parse_too_many_errors = too many errors, stopped parsing
+
 parse_too_many_hashes = too many `#` symbols: raw strings may be delimited by up to 255 `#` symbols, but found {$num}
 
 parse_too_short_hex_escape = numeric character escape is too short
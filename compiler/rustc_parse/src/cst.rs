@@ -0,0 +1,233 @@
+//! A lossless view of source files, for tools that rewrite code and have to keep its formatting,
+//! like `cargo fix`.
+//!
+//! The lexer normally skips the whitespace and comments between tokens, other than doc comments,
+//! which become tokens of their own. [`lex_with_trivia`] keeps them as [`Trivia`], so the text
+//! around every token can be looked up, and a file can be put back together exactly from its
+//! tokens, see [`to_source`]. [`lex_raw`] returns the tokens as they are in the source instead,
+//! from before the lexer drops, glues and checks them.
+
+use std::ops::Range;
+
+use rustc_ast::tokenstream::{TokenStream, TokenTree};
+use rustc_errors::Diag;
+use rustc_session::parse::ParseSess;
+use rustc_span::{BytePos, Pos, SourceFile, Span};
+
+use crate::lexer::{self, FrontmatterAllowed};
+
+/// What a piece of [`Trivia`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A run of whitespace.
+    Whitespace,
+    /// A `//` comment that isn't a doc comment, without the line break after it.
+    LineComment,
+    /// A `/* */` comment that isn't a doc comment.
+    BlockComment,
+    /// The `#!` line at the start of a file.
+    Shebang,
+    /// The frontmatter block at the start of a file, see [`frontmatter`].
+    Frontmatter,
+}
+
+impl TriviaKind {
+    /// Whether this is a comment, i.e. a [`LineComment`](Self::LineComment) or a
+    /// [`BlockComment`](Self::BlockComment).
+    pub fn is_comment(self) -> bool {
+        matches!(self, TriviaKind::LineComment | TriviaKind::BlockComment)
+    }
+}
+
+/// Text between tokens, which the parser doesn't see.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+/// The trivia of a source file, in source order.
+#[derive(Clone, Debug, Default)]
+pub struct SourceTrivia {
+    trivia: Vec<Trivia>,
+}
+
+impl SourceTrivia {
+    pub fn iter(&self) -> impl Iterator<Item = &Trivia> {
+        self.trivia.iter()
+    }
+
+    /// Returns the comments, which aren't doc comments, as doc comments are tokens.
+    pub fn comments(&self) -> impl Iterator<Item = &Trivia> {
+        self.iter().filter(|trivia| trivia.kind.is_comment())
+    }
+
+    /// Returns the trivia from `lo` to `hi`, e.g. from the end of a token to the start of the
+    /// next one.
+    pub fn between(&self, lo: BytePos, hi: BytePos) -> &[Trivia] {
+        let start = self.trivia.partition_point(|trivia| trivia.span.lo() < lo);
+        let end = self.trivia.partition_point(|trivia| trivia.span.hi() <= hi);
+        &self.trivia[start..end.max(start)]
+    }
+}
+
+/// Lexes `source_file` like the parser does, but keeps the trivia between the tokens too. On
+/// failure, the errors must be consumed via `unwrap_or_emit_fatal`, `emit`, `cancel`, etc.,
+/// otherwise a panic will occur when they are dropped.
+pub fn lex_with_trivia<'psess>(
+    psess: &'psess ParseSess,
+    source_file: &SourceFile,
+) -> Result<(TokenStream, SourceTrivia), Vec<Diag<'psess>>> {
+    lex_source_with_trivia(psess, source_file, FrontmatterAllowed::Yes)
+}
+
+/// Like [`lex_with_trivia`], but for source that may not start with frontmatter, e.g. a snippet.
+pub(crate) fn lex_source_with_trivia<'psess>(
+    psess: &'psess ParseSess,
+    source_file: &SourceFile,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> Result<(TokenStream, SourceTrivia), Vec<Diag<'psess>>> {
+    let src = source_file.src.as_ref().unwrap_or_else(|| {
+        psess.dcx().bug(format!(
+            "cannot lex `source_file` without source: {}",
+            psess.source_map().filename_for_diagnostics(&source_file.name)
+        ));
+    });
+    let start_pos = source_file.start_pos;
+    let (stream, trivia) =
+        lexer::lex_token_trees_with_trivia(psess, src.as_str(), start_pos, frontmatter_allowed)?;
+    Ok((stream, SourceTrivia { trivia }))
+}
+
+/// What a [`RawToken`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawTokenKind {
+    /// The `#!` line at the start of a file.
+    Shebang,
+    /// The frontmatter block at the start of a file, see [`frontmatter`].
+    Frontmatter,
+    /// Anything else, as `rustc_lexer` lexes it, including whitespace and comments.
+    Token(rustc_lexer::TokenKind),
+}
+
+/// A token as it is in the source, before the lexer makes a token of the parser's of it, e.g.
+/// before gluing `-` and `=` into `-=`, or dropping a comment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawToken {
+    pub kind: RawTokenKind,
+    /// Where the token is in the source of its file, in bytes.
+    pub range: Range<usize>,
+}
+
+/// Lexes `source_file` like the parser does, and also returns its raw tokens, which cover all of
+/// its source, for tools that need both views of it, like formatters. On failure, the errors
+/// must be consumed via `unwrap_or_emit_fatal`, `emit`, `cancel`, etc., otherwise a panic will
+/// occur when they are dropped.
+pub fn lex_raw<'psess>(
+    psess: &'psess ParseSess,
+    source_file: &SourceFile,
+) -> Result<(TokenStream, Vec<RawToken>), Vec<Diag<'psess>>> {
+    let src = crate::source_file_src(psess, source_file);
+    let start_pos = source_file.start_pos;
+    let stream = lexer::lex_token_trees(psess, src, start_pos, None, FrontmatterAllowed::Yes)?;
+    Ok((stream, raw_tokens(src)))
+}
+
+fn raw_tokens(src: &str) -> Vec<RawToken> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    if let Some(shebang_len) = rustc_lexer::strip_shebang(src) {
+        tokens.push(RawToken { kind: RawTokenKind::Shebang, range: 0..shebang_len });
+        pos = shebang_len;
+    }
+    if let Some(frontmatter) = lexer::frontmatter(&src[pos..]) {
+        let block = pos + frontmatter.block.start..pos + frontmatter.block.end;
+        if block.start > pos {
+            let kind = RawTokenKind::Token(rustc_lexer::TokenKind::Whitespace);
+            tokens.push(RawToken { kind, range: pos..block.start });
+        }
+        pos = block.end;
+        tokens.push(RawToken { kind: RawTokenKind::Frontmatter, range: block });
+    }
+    for token in rustc_lexer::tokenize(&src[pos..]) {
+        let end = pos + token.len as usize;
+        tokens.push(RawToken { kind: RawTokenKind::Token(token.kind), range: pos..end });
+        pos = end;
+    }
+    tokens
+}
+
+/// Returns the `#!` line at the start of `source_file`, e.g. `#!/usr/bin/env rustc`, and its span,
+/// for tools that need to know the interpreter of a script. The lexer skips that line, so the
+/// parser never sees it. The text includes the `#!`, but not the line break after it.
+pub fn shebang(source_file: &SourceFile) -> Option<(&str, Span)> {
+    let src = source_file.src.as_ref()?;
+    let len = rustc_lexer::strip_shebang(src)?;
+    let lo = source_file.start_pos;
+    Some((&src[..len], Span::with_root_ctxt(lo, lo + BytePos::from_usize(len))))
+}
+
+/// A block fenced by `---` lines at the start of a file, after any shebang, e.g. the manifest of a
+/// cargo script. The lexer skips it, like the shebang.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frontmatter<'a> {
+    /// The text after the opening fence, e.g. `cargo`. Empty if there is none.
+    pub info: &'a str,
+    /// The lines between the fences.
+    pub body: &'a str,
+    /// The span of the whole block, fences included.
+    pub span: Span,
+    /// The span of `body`.
+    pub body_span: Span,
+}
+
+/// Returns the frontmatter block of `source_file`, if it has one. Using a frontmatter requires
+/// `#![feature(frontmatter)]`.
+pub fn frontmatter(source_file: &SourceFile) -> Option<Frontmatter<'_>> {
+    let src = source_file.src.as_ref()?;
+    let shebang_len = rustc_lexer::strip_shebang(src).unwrap_or(0);
+    let src = &src[shebang_len..];
+    let ranges = lexer::frontmatter(src)?;
+    let start = source_file.start_pos + BytePos::from_usize(shebang_len);
+    let span = |range: &Range<usize>| {
+        let lo = start + BytePos::from_usize(range.start);
+        Span::with_root_ctxt(lo, lo + BytePos::from_usize(range.len()))
+    };
+    Some(Frontmatter {
+        info: &src[ranges.info.clone()],
+        body: &src[ranges.body.clone()],
+        span: span(&ranges.block),
+        body_span: span(&ranges.body),
+    })
+}
+
+/// Puts the text of `source_file` back together from the tokens and trivia that
+/// [`lex_with_trivia`] returned for it. Where the lexer dropped text, e.g. after reporting an
+/// unknown character, that text is missing, otherwise this is exactly the file's source.
+pub fn to_source(source_file: &SourceFile, stream: &TokenStream, trivia: &SourceTrivia) -> String {
+    fn token_spans(stream: &TokenStream, spans: &mut Vec<Span>) {
+        for tree in stream.trees() {
+            match tree {
+                TokenTree::Token(token, _) => spans.push(token.span),
+                TokenTree::Delimited(dspan, _, _, stream) => {
+                    spans.push(dspan.open);
+                    token_spans(stream, spans);
+                    spans.push(dspan.close);
+                }
+            }
+        }
+    }
+
+    let mut spans: Vec<_> = trivia.iter().map(|trivia| trivia.span).collect();
+    token_spans(stream, &mut spans);
+    spans.sort_by_key(|span| span.lo());
+
+    let src = source_file.src.as_ref().expect("cannot rebuild a file without source");
+    let mut text = String::with_capacity(src.len());
+    for span in spans {
+        let lo = source_file.relative_position(span.lo()).to_usize();
+        let hi = source_file.relative_position(span.hi()).to_usize();
+        text.push_str(&src[lo..hi]);
+    }
+    text
+}
@@ -3248,3 +3248,94 @@ pub(crate) struct PolarityAndModifiers {
     pub polarity: &'static str,
     pub modifiers_concatenated: String,
 }
+
+#[derive(Diagnostic)]
+#[diag(parse_cancelled)]
+pub(crate) struct ParseCancelled {
+    #[primary_span]
+    pub span: Span,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_too_many_errors)]
+pub(crate) struct TooManyErrors {
+    #[primary_span]
+    pub span: Span,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_nesting_too_deep)]
+#[note]
+pub(crate) struct NestingTooDeep {
+    #[primary_span]
+    pub span: Span,
+    pub kind: &'static str,
+    pub limit: u32,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_invalid_cfg_predicate)]
+pub(crate) struct InvalidCfgPredicate {
+    #[primary_span]
+    pub span: Span,
+    pub predicate: String,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_cfg_predicate_expected_one)]
+pub(crate) struct CfgPredicateExpectedOne {
+    #[primary_span]
+    pub span: Span,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_cfg_predicate_identifier)]
+pub(crate) struct CfgPredicateIdentifier {
+    #[primary_span]
+    pub span: Span,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_cfg_predicate_unsupported_literal)]
+pub(crate) struct CfgPredicateUnsupportedLiteral {
+    #[primary_span]
+    pub span: Span,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_cfg_predicate_value_not_str)]
+pub(crate) struct CfgPredicateValueNotStr {
+    #[primary_span]
+    pub span: Span,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_round_trip_lost_token)]
+pub(crate) struct RoundTripLostToken {
+    #[primary_span]
+    pub span: Span,
+    pub token: String,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_round_trip_added_token)]
+pub(crate) struct RoundTripAddedToken {
+    #[primary_span]
+    pub span: Span,
+    pub token: String,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_round_trip_unlexable)]
+pub(crate) struct RoundTripUnlexable {
+    #[primary_span]
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Diagnostic)]
+#[diag(parse_round_trip_too_different)]
+pub(crate) struct RoundTripTooDifferent {
+    #[primary_span]
+    pub span: Span,
+}
@@ -0,0 +1,219 @@
+//! Giving the tokens of pretty-printed AST nodes back the spans of the source that they were
+//! printed from. Pretty-printed tokens all get the span of the whole node otherwise, which makes
+//! for vague diagnostics when a proc macro points at one of them.
+
+use std::ops::Range;
+
+use rustc_ast as ast;
+use rustc_ast::token::{Token, TokenKind};
+use rustc_ast::tokenstream::{DelimSpan, TokenStream, TokenTree};
+use rustc_session::parse::ParseSess;
+use rustc_span::Span;
+
+use crate::lexer::{self, LexError};
+
+/// How many tokens the printed and the source tokens may differ in before we give up on lining
+/// them up. They only differ in things like parentheses and trailing commas that the
+/// pretty-printer adds or leaves out, and attributes that were expanded, so this is plenty, and
+/// keeps the cost of lining them up in check when they don't match at all.
+const MAX_EDITS: usize = 1024;
+
+/// Gives the tokens of `stream`, which was made by pretty-printing the source in `regions`, in
+/// order, the spans of the source tokens that they line up with. Tokens are lined up by kind,
+/// keeping as many of them in order as possible, and those that don't line up with a source token
+/// keep their span. This is best-effort, e.g. regions whose source isn't available, because they
+/// come from a macro expansion, are left out, and `stream` is returned as it is if the tokens
+/// differ too much.
+pub(crate) fn remap_spans(
+    psess: &ParseSess,
+    stream: &TokenStream,
+    regions: &[Span],
+) -> TokenStream {
+    let source_tokens = source_tokens(psess, regions);
+    if source_tokens.is_empty() {
+        return stream.clone();
+    }
+    let mut printed = Vec::new();
+    flatten(stream, &mut printed);
+    let Some(source_indices) = line_up(&printed, &source_tokens) else {
+        return stream.clone();
+    };
+    let mut spans = source_indices.into_iter().map(|i| i.map(|i| source_tokens[i].span));
+    rebuild(stream, &mut spans)
+}
+
+/// The source regions that `item` was parsed from, in order: its outer attributes, which come
+/// before its span, the item itself, and the contents of an out-of-line module, which are in a
+/// file of their own.
+pub(crate) fn item_regions(item: &ast::Item) -> Vec<Span> {
+    let mut regions: Vec<Span> = item
+        .attrs
+        .iter()
+        .filter(|attr| attr.style == ast::AttrStyle::Outer)
+        .map(|attr| attr.span)
+        .collect();
+    regions.push(item.span);
+    if let ast::ItemKind::Mod(_, ast::ModKind::Loaded(_, ast::Inline::No, spans)) = &item.kind {
+        regions.push(spans.inner_span);
+    }
+    regions
+}
+
+/// The tokens of the source in `regions`, with their spans in it, with adjacent punctuation
+/// glued together as the token tree lexer does. Regions with lexing errors are left out, those
+/// were reported when the source was parsed.
+pub(crate) fn source_tokens(psess: &ParseSess, regions: &[Span]) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    for &region in regions {
+        if region.is_dummy() || region.from_expansion() {
+            continue;
+        }
+        let Ok(src) = psess.source_map().span_to_snippet(region) else { continue };
+        tokens.extend(lexer::lex_glued_tokens(psess, &src, region.lo()).unwrap_or_default());
+    }
+    tokens
+}
+
+/// Lexes `src` with `lexer::tokenize`, adding its errors to `errors`, and glues adjacent
+/// punctuation together as the token tree lexer does. The tokens get the spans that `span_of`
+/// gives for where they are in `src`.
+pub(crate) fn glued_tokens(
+    src: &str,
+    errors: &mut Vec<LexError>,
+    span_of: impl Fn(Range<usize>) -> Span,
+) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut last_end = None;
+    for (token, range) in lexer::tokenize(src, errors) {
+        let token = Token::new(token.kind, span_of(range.clone()));
+        if last_end == Some(range.start)
+            && let Some(last) = tokens.last_mut()
+            && let Some(glued) = last.glue(&token)
+        {
+            *last = glued;
+        } else {
+            tokens.push(token);
+        }
+        last_end = Some(range.end);
+    }
+    tokens
+}
+
+/// Pushes the kinds of the tokens of `stream` to `kinds`, with the delimiters of its delimited
+/// groups as tokens of their own.
+fn flatten(stream: &TokenStream, kinds: &mut Vec<TokenKind>) {
+    for tree in stream.trees() {
+        match tree {
+            TokenTree::Token(token, _) => kinds.push(token.kind.clone()),
+            TokenTree::Delimited(_, _, delim, inner) => {
+                kinds.push(TokenKind::OpenDelim(*delim));
+                flatten(inner, kinds);
+                kinds.push(TokenKind::CloseDelim(*delim));
+            }
+        }
+    }
+}
+
+/// The inverse of `flatten`, which gives the tokens of `stream` the spans from `spans` that
+/// aren't `None`.
+fn rebuild(stream: &TokenStream, spans: &mut impl Iterator<Item = Option<Span>>) -> TokenStream {
+    let mut trees = Vec::with_capacity(stream.len());
+    for tree in stream.trees() {
+        trees.push(match tree {
+            TokenTree::Token(token, spacing) => {
+                let span = spans.next().flatten().unwrap_or(token.span);
+                TokenTree::Token(Token::new(token.kind.clone(), span), *spacing)
+            }
+            TokenTree::Delimited(dspan, spacing, delim, inner) => {
+                let open = spans.next().flatten().unwrap_or(dspan.open);
+                let inner = rebuild(inner, spans);
+                let close = spans.next().flatten().unwrap_or(dspan.close);
+                TokenTree::Delimited(DelimSpan::from_pair(open, close), *spacing, *delim, inner)
+            }
+        });
+    }
+    TokenStream::new(trees)
+}
+
+/// Lines up `printed` with `source`, keeping as many tokens of the same kind in order as
+/// possible, with the algorithm of "An O(ND) Difference Algorithm and Its Variations" by Eugene
+/// Myers. Returns the index of the source token that each printed token lines up with, if any, or
+/// `None` if they differ in more than `MAX_EDITS` tokens.
+pub(crate) fn line_up(printed: &[TokenKind], source: &[Token]) -> Option<Vec<Option<usize>>> {
+    let (n, m) = (printed.len() as isize, source.len() as isize);
+    let max = (n + m).min(MAX_EDITS as isize);
+    let offset = max + 1;
+    // `v[k + offset]` is how far along `printed` the furthest path with `d` edits on diagonal `k`
+    // gets, or -1 if none gets there, and `trace[d]` keeps those of diagonals `-d..=d`, for
+    // following the path back.
+    let mut v = vec![-1isize; 2 * max as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut end = None;
+    'edits: for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let start = if d == 0 {
+                Some((0, 0))
+            } else {
+                last_edit(|k| Some(v[(k + offset) as usize]).filter(|&x| x >= 0), d, k, n, m)
+            };
+            let i = (k + offset) as usize;
+            let Some((_, mut x)) = start else {
+                v[i] = -1;
+                continue;
+            };
+            let mut y = x - k;
+            while x < n && y < m && printed[x as usize] == source[y as usize].kind {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x == n && y == m {
+                end = Some(d);
+                break 'edits;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+    let end = end?;
+
+    let mut source_indices = vec![None; printed.len()];
+    let (mut x, mut y) = (n, m);
+    for d in (1..=end).rev() {
+        let prev = &trace[d as usize - 1];
+        let prev_x = |k: isize| Some(prev[(k + d - 1) as usize]).filter(|&x| x >= 0);
+        let (prev_k, edit_x) = last_edit(prev_x, d, x - y, n, m).unwrap();
+        // The tokens after the edit, up to `(x, y)`, line up.
+        while x > edit_x {
+            x -= 1;
+            y -= 1;
+            source_indices[x as usize] = Some(y as usize);
+        }
+        x = prev_x(prev_k).unwrap();
+        y = x - prev_k;
+    }
+    while x > 0 {
+        x -= 1;
+        y -= 1;
+        source_indices[x as usize] = Some(y as usize);
+    }
+    Some(source_indices)
+}
+
+/// The edit that the furthest path with `d` edits on diagonal `k` ends with, given how far along
+/// `printed` the paths with `d - 1` edits get on each diagonal, from `prev_x`: the diagonal that
+/// it comes from, and how far along `printed` the path gets with it. An edit either skips a
+/// source token, coming from diagonal `k + 1`, or a printed one, coming from diagonal `k - 1`,
+/// and can't go past the end of either.
+fn last_edit(
+    prev_x: impl Fn(isize) -> Option<isize>,
+    d: isize,
+    k: isize,
+    n: isize,
+    m: isize,
+) -> Option<(isize, isize)> {
+    let skip_source = if k < d { prev_x(k + 1).filter(|&x| x - k <= m) } else { None };
+    let skip_printed = if k > -d { prev_x(k - 1).map(|x| x + 1).filter(|&x| x <= n) } else { None };
+    let skip_source = skip_source.map(|x| (k + 1, x));
+    let skip_printed = skip_printed.map(|x| (k - 1, x));
+    skip_source.into_iter().chain(skip_printed).max_by_key(|&(_, x)| x)
+}
@@ -13,18 +13,26 @@
 };
 use rustc_session::lint::BuiltinLintDiag;
 use rustc_session::parse::ParseSess;
-use rustc_span::symbol::Symbol;
+use rustc_span::symbol::{sym, Symbol};
 use rustc_span::{BytePos, Pos, Span};
 use tracing::debug;
 
+use crate::cst::{Trivia, TriviaKind};
 use crate::lexer::unicode_chars::UNICODE_ARRAY;
 use crate::{errors, make_unclosed_delims_error};
 
 mod diagnostics;
+mod parallel;
+mod relex;
+mod standalone;
 mod tokentrees;
 mod unescape_error_reporting;
 mod unicode_chars;
 
+pub(crate) use parallel::lex_token_trees_in_chunks;
+pub(crate) use relex::lexes_alone;
+pub use relex::relex_region;
+pub use standalone::{tokenize, LexError};
 use unescape_error_reporting::{emit_unescape_error, escaped_char};
 
 // This type is used a lot. Make sure it doesn't unintentionally get bigger.
@@ -42,33 +50,241 @@ pub(crate) struct UnmatchedDelim {
     pub candidate_span: Option<Span>,
 }
 
+/// Whether the lexer skips a frontmatter block at the start of the source, see [`frontmatter`].
+/// Only the files of crates and modules can have one. The snippets that macros and tools parse
+/// are lexed as they are, so a leading `---` line in one is a syntax error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FrontmatterAllowed {
+    Yes,
+    No,
+}
+
 pub(crate) fn lex_token_trees<'psess, 'src>(
     psess: &'psess ParseSess,
-    mut src: &'src str,
-    mut start_pos: BytePos,
+    src: &'src str,
+    start_pos: BytePos,
     override_span: Option<Span>,
+    frontmatter_allowed: FrontmatterAllowed,
 ) -> Result<TokenStream, Vec<Diag<'psess>>> {
-    // Skip `#!`, if present.
-    if let Some(shebang_len) = rustc_lexer::strip_shebang(src) {
-        src = &src[shebang_len..];
-        start_pos = start_pos + BytePos::from_usize(shebang_len);
-    }
-
-    let cursor = Cursor::new(src);
-    let string_reader = StringReader {
-        psess,
-        start_pos,
-        pos: start_pos,
-        src,
-        cursor,
-        override_span,
-        nbsp_is_whitespace: false,
-        last_lifetime: None,
-    };
-    let (stream, res, unmatched_delims) =
+    if override_span.is_none()
+        && let Some(stream) =
+            parallel::lex_token_trees_in_parallel(psess, src, start_pos, frontmatter_allowed)
+    {
+        return Ok(stream);
+    }
+    lex_token_trees_inner(psess, src, start_pos, override_span, false, frontmatter_allowed)
+        .map(|(stream, _)| stream)
+}
+
+/// Like `lex_token_trees`, but also returns the whitespace and comments that the lexer skips
+/// between the tokens, in source order.
+pub(crate) fn lex_token_trees_with_trivia<'psess, 'src>(
+    psess: &'psess ParseSess,
+    src: &'src str,
+    start_pos: BytePos,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> Result<(TokenStream, Vec<Trivia>), Vec<Diag<'psess>>> {
+    lex_token_trees_inner(psess, src, start_pos, None, true, frontmatter_allowed)
+}
+
+/// Lexes `src` into a flat list of tokens, with adjacent punctuation glued together as the token
+/// tree lexer does, for lining them up with other tokens. Returns `None` if `src` has tokens that
+/// the lexer reports errors for. This records in `psess` what lexing does, e.g. raw identifiers
+/// and buffered lints, so it is meant for source that was lexed before, which only gets the same
+/// things recorded again, and diagnostics that are deduplicated.
+pub(crate) fn lex_glued_tokens(
+    psess: &ParseSess,
+    src: &str,
+    start_pos: BytePos,
+) -> Option<Vec<Token>> {
+    if !rustc_lexer::tokenize(src).all(|token| is_clean(token.kind)) {
+        return None;
+    }
+    let mut reader = StringReader::new(psess, src, start_pos, None, None);
+    let mut tokens: Vec<Token> = Vec::new();
+    loop {
+        let (token, preceded_by_whitespace) = reader.next_token();
+        if token.kind == token::Eof {
+            return Some(tokens);
+        }
+        if !preceded_by_whitespace
+            && let Some(last) = tokens.last_mut()
+            && let Some(glued) = last.glue(&token)
+        {
+            *last = glued;
+        } else {
+            tokens.push(token);
+        }
+    }
+}
+
+/// Returns the length of the start of `src` that holds the crate's inner attributes, i.e. the
+/// shebang, `#![...]` attributes and inner doc comments, and any whitespace and comments between
+/// them, so that only that much of it needs to be lexed to parse them. An attribute that isn't
+/// closed takes the rest of `src`, for the lexer to report.
+pub(crate) fn inner_attrs_len(src: &str) -> usize {
+    enum State {
+        Between,
+        Pound,
+        Bang,
+        Attr { depth: usize },
+    }
+
+    let start = preamble_len(src, FrontmatterAllowed::Yes);
+    let (mut pos, mut len) = (start, start);
+    let mut state = State::Between;
+    for token in rustc_lexer::tokenize(&src[start..]) {
+        pos += token.len as usize;
+        state = match (state, token.kind) {
+            (
+                state,
+                rustc_lexer::TokenKind::Whitespace
+                | rustc_lexer::TokenKind::LineComment { doc_style: None }
+                | rustc_lexer::TokenKind::BlockComment { doc_style: None, .. },
+            ) => state,
+            (
+                State::Between,
+                rustc_lexer::TokenKind::LineComment { doc_style: Some(DocStyle::Inner) }
+                | rustc_lexer::TokenKind::BlockComment { doc_style: Some(DocStyle::Inner), .. },
+            ) => {
+                len = pos;
+                State::Between
+            }
+            (State::Between, rustc_lexer::TokenKind::Pound) => State::Pound,
+            (State::Pound, rustc_lexer::TokenKind::Bang) => State::Bang,
+            (State::Bang, rustc_lexer::TokenKind::OpenBracket) => State::Attr { depth: 1 },
+            (
+                State::Attr { depth },
+                rustc_lexer::TokenKind::OpenParen
+                | rustc_lexer::TokenKind::OpenBrace
+                | rustc_lexer::TokenKind::OpenBracket,
+            ) => State::Attr { depth: depth + 1 },
+            (
+                State::Attr { depth },
+                rustc_lexer::TokenKind::CloseParen
+                | rustc_lexer::TokenKind::CloseBrace
+                | rustc_lexer::TokenKind::CloseBracket,
+            ) => {
+                if depth == 1 {
+                    len = pos;
+                    State::Between
+                } else {
+                    State::Attr { depth: depth - 1 }
+                }
+            }
+            (State::Attr { depth }, _) => State::Attr { depth },
+            _ => return len,
+        };
+    }
+    match state {
+        State::Between => len,
+        State::Pound | State::Bang | State::Attr { .. } => src.len(),
+    }
+}
+
+/// Returns the length of the shebang and the frontmatter at the start of `src`, which the lexer
+/// skips, with the whitespace between them.
+fn preamble_len(src: &str, frontmatter_allowed: FrontmatterAllowed) -> usize {
+    let start = rustc_lexer::strip_shebang(src).unwrap_or(0);
+    if frontmatter_allowed == FrontmatterAllowed::No {
+        return start;
+    }
+    start + frontmatter(&src[start..]).map_or(0, |frontmatter| frontmatter.block.end)
+}
+
+/// Whether the lexer reports no errors for a token of this kind as it lexes it, e.g. for an
+/// unknown character or an unterminated literal. Other errors, like unclosed delimiters, are
+/// buffered and returned.
+fn is_clean(kind: rustc_lexer::TokenKind) -> bool {
+    match kind {
+        rustc_lexer::TokenKind::Unknown
+        | rustc_lexer::TokenKind::InvalidIdent
+        | rustc_lexer::TokenKind::InvalidPrefix
+        | rustc_lexer::TokenKind::UnknownPrefix
+        | rustc_lexer::TokenKind::UnknownPrefixLifetime => false,
+        rustc_lexer::TokenKind::BlockComment { terminated, .. } => terminated,
+        rustc_lexer::TokenKind::Lifetime { starts_with_number } => !starts_with_number,
+        rustc_lexer::TokenKind::Literal { kind, .. } => match kind {
+            LiteralKind::Char { terminated }
+            | LiteralKind::Byte { terminated }
+            | LiteralKind::Str { terminated }
+            | LiteralKind::ByteStr { terminated }
+            | LiteralKind::CStr { terminated } => terminated,
+            LiteralKind::RawStr { n_hashes }
+            | LiteralKind::RawByteStr { n_hashes }
+            | LiteralKind::RawCStr { n_hashes } => n_hashes.is_some(),
+            LiteralKind::Int { .. } | LiteralKind::Float { .. } => true,
+        },
+        _ => true,
+    }
+}
+
+/// Where a frontmatter block is in the source it was found in, see [`frontmatter`].
+pub(crate) struct FrontmatterRanges {
+    /// The whole block, from the opening fence to the end of the closing one.
+    pub block: Range<usize>,
+    /// The text after the opening fence, e.g. `cargo`, without the whitespace around it.
+    pub info: Range<usize>,
+    /// The lines between the fences, with the line break at their end.
+    pub body: Range<usize>,
+}
+
+/// Finds the frontmatter block at the start of `src`, after any shebang, e.g. the manifest of a
+/// cargo script. The block opens with a line of three or more dashes and an optional info string,
+/// and closes with a line of as many dashes. Only whitespace may come before it.
+pub(crate) fn frontmatter(src: &str) -> Option<FrontmatterRanges> {
+    let open = src.len() - src.trim_start_matches(rustc_lexer::is_whitespace).len();
+    // The fence has to be at the start of its line.
+    if open != 0 && !src[..open].ends_with('\n') {
+        return None;
+    }
+    let rest = &src[open..];
+    let dashes = rest.bytes().take_while(|&b| b == b'-').count();
+    if dashes < 3 {
+        return None;
+    }
+    let fence = &rest[..dashes];
+    let open_line = &rest[..rest.find('\n')?];
+    let after_fence = &open_line[dashes..];
+    let info_start = open + open_line.len() - after_fence.trim_start().len();
+    let info = after_fence.trim();
+
+    let body_start = open + open_line.len() + 1;
+    let mut pos = body_start;
+    for line in src[body_start..].split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Some(FrontmatterRanges {
+                block: open..pos + fence.len(),
+                info: info_start..info_start + info.len(),
+                body: body_start..pos,
+            });
+        }
+        pos += line.len();
+    }
+    None
+}
+
+fn lex_token_trees_inner<'psess, 'src>(
+    psess: &'psess ParseSess,
+    src: &'src str,
+    start_pos: BytePos,
+    override_span: Option<Span>,
+    record_trivia: bool,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> Result<(TokenStream, Vec<Trivia>), Vec<Diag<'psess>>> {
+    let reader =
+        string_reader(psess, src, start_pos, override_span, record_trivia, frontmatter_allowed);
+    lex_all_token_trees(psess, reader)
+}
+
+fn lex_all_token_trees<'psess, 'src>(
+    psess: &'psess ParseSess,
+    string_reader: StringReader<'psess, 'src>,
+) -> Result<(TokenStream, Vec<Trivia>), Vec<Diag<'psess>>> {
+    let (stream, res, unmatched_delims, trivia) =
         tokentrees::TokenTreesReader::lex_all_token_trees(string_reader);
     match res {
-        Ok(()) if unmatched_delims.is_empty() => Ok(stream),
+        Ok(()) if unmatched_delims.is_empty() => Ok((stream, trivia.unwrap_or_default())),
         _ => {
             // Return error if there are unmatched delimiters or unclosed delimiters.
             // We emit delimiter mismatch errors first, then emit the unclosing delimiter mismatch
@@ -91,6 +307,51 @@ pub(crate) fn lex_token_trees<'psess, 'src>(
     }
 }
 
+/// Creates the reader for lexing `src`, past its shebang and, if `frontmatter_allowed`, its
+/// frontmatter, if it has them.
+fn string_reader<'psess, 'src>(
+    psess: &'psess ParseSess,
+    mut src: &'src str,
+    mut start_pos: BytePos,
+    override_span: Option<Span>,
+    record_trivia: bool,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> StringReader<'psess, 'src> {
+    let mut trivia = record_trivia.then(Vec::new);
+
+    // Skip `#!`, if present.
+    if let Some(shebang_len) = rustc_lexer::strip_shebang(src) {
+        let shebang_end = start_pos + BytePos::from_usize(shebang_len);
+        if let Some(trivia) = &mut trivia {
+            let span = Span::with_root_ctxt(start_pos, shebang_end);
+            trivia.push(Trivia { kind: TriviaKind::Shebang, span });
+        }
+        src = &src[shebang_len..];
+        start_pos = shebang_end;
+    }
+
+    // Skip the frontmatter, if present and allowed.
+    if frontmatter_allowed == FrontmatterAllowed::Yes
+        && let Some(frontmatter) = frontmatter(src)
+    {
+        let lo = start_pos + BytePos::from_usize(frontmatter.block.start);
+        let hi = start_pos + BytePos::from_usize(frontmatter.block.end);
+        let span = Span::with_root_ctxt(lo, hi);
+        psess.gated_spans.gate(sym::frontmatter, span);
+        if let Some(trivia) = &mut trivia {
+            if lo > start_pos {
+                let span = Span::with_root_ctxt(start_pos, lo);
+                trivia.push(Trivia { kind: TriviaKind::Whitespace, span });
+            }
+            trivia.push(Trivia { kind: TriviaKind::Frontmatter, span });
+        }
+        src = &src[frontmatter.block.end..];
+        start_pos = hi;
+    }
+
+    StringReader::new(psess, src, start_pos, override_span, trivia)
+}
+
 struct StringReader<'psess, 'src> {
     psess: &'psess ParseSess,
     /// Initial position, read-only.
@@ -110,9 +371,33 @@ struct StringReader<'psess, 'src> {
     /// Track the `Span` for the leading `'` of the last lifetime. Used for
     /// diagnostics to detect possible typo where `"` was meant.
     last_lifetime: Option<Span>,
+
+    /// The whitespace and comments skipped so far, if they are being kept,
+    /// see `lex_token_trees_with_trivia`.
+    trivia: Option<Vec<Trivia>>,
 }
 
 impl<'psess, 'src> StringReader<'psess, 'src> {
+    fn new(
+        psess: &'psess ParseSess,
+        src: &'src str,
+        start_pos: BytePos,
+        override_span: Option<Span>,
+        trivia: Option<Vec<Trivia>>,
+    ) -> Self {
+        StringReader {
+            psess,
+            start_pos,
+            pos: start_pos,
+            src,
+            cursor: Cursor::new(src),
+            override_span,
+            nbsp_is_whitespace: false,
+            last_lifetime: None,
+            trivia,
+        }
+    }
+
     fn dcx(&self) -> DiagCtxtHandle<'psess> {
         self.psess.dcx()
     }
@@ -121,6 +406,13 @@ fn mk_sp(&self, lo: BytePos, hi: BytePos) -> Span {
         self.override_span.unwrap_or_else(|| Span::with_root_ctxt(lo, hi))
     }
 
+    /// Keeps the trivia from `start` to the current position, if trivia is being kept.
+    fn push_trivia(&mut self, kind: TriviaKind, start: BytePos) {
+        if let Some(trivia) = &mut self.trivia {
+            trivia.push(Trivia { kind, span: self.mk_sp(start, self.pos) });
+        }
+    }
+
     /// Returns the next token, paired with a bool indicating if the token was
     /// preceded by whitespace.
     fn next_token(&mut self) -> (Token, bool) {
@@ -155,6 +447,7 @@ fn next_token(&mut self) -> (Token, bool) {
                     // Skip non-doc comments
                     let Some(doc_style) = doc_style else {
                         self.lint_unicode_text_flow(start);
+                        self.push_trivia(TriviaKind::LineComment, start);
                         preceded_by_whitespace = true;
                         continue;
                     };
@@ -172,6 +465,7 @@ fn next_token(&mut self) -> (Token, bool) {
                     // Skip non-doc comments
                     let Some(doc_style) = doc_style else {
                         self.lint_unicode_text_flow(start);
+                        self.push_trivia(TriviaKind::BlockComment, start);
                         preceded_by_whitespace = true;
                         continue;
                     };
@@ -184,6 +478,7 @@ fn next_token(&mut self) -> (Token, bool) {
                     self.cook_doc_comment(content_start, content, CommentKind::Block, doc_style)
                 }
                 rustc_lexer::TokenKind::Whitespace => {
+                    self.push_trivia(TriviaKind::Whitespace, start);
                     preceded_by_whitespace = true;
                     continue;
                 }
@@ -362,6 +657,7 @@ fn next_token(&mut self) -> (Token, bool) {
                         // space characters earlier in the file, treat all
                         // subsequent occurrences as whitespace.
                         if self.nbsp_is_whitespace {
+                            self.push_trivia(TriviaKind::Whitespace, start);
                             preceded_by_whitespace = true;
                             continue;
                         }
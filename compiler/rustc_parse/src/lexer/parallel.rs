@@ -0,0 +1,186 @@
+//! Lexing a single large file on several threads, e.g. the bindings that `bindgen` generates,
+//! which can be megabytes of source.
+//!
+//! Everything that can make lexing a chunk fail is checked on one thread before any chunk is
+//! lexed, as lexing records things in the session, e.g. that the frontmatter is gated, which
+//! lexing all of the file again would record a second time. That pass also interns the symbols of
+//! the tokens in the order they are in the file, so that the threads only look them up, and the
+//! indices of the symbols don't depend on which thread gets to them first.
+
+use std::ops::Range;
+
+use rustc_ast::tokenstream::TokenStream;
+use rustc_data_structures::sync::{is_dyn_thread_safe, par_map};
+use rustc_lexer::{LiteralKind, TokenKind};
+use rustc_session::parse::ParseSess;
+use rustc_span::symbol::Symbol;
+use rustc_span::{BytePos, Pos, Span};
+
+use super::{nfc_normalize, FrontmatterAllowed, StringReader};
+
+/// Files smaller than this are lexed on one thread, as splitting them up doesn't pay off.
+const MIN_PARALLEL_LEN: usize = 1 << 20;
+
+/// How much of a file each thread lexes at a time, roughly.
+const CHUNK_LEN: usize = 1 << 18;
+
+/// Lexes `src` in chunks on several threads, and puts the token trees of the chunks together.
+/// Returns `None` if `src` is too small for that, if the compiler runs on one thread, or if
+/// lexing finds errors, so that the caller lexes all of `src` on one thread, and reports them.
+pub(super) fn lex_token_trees_in_parallel(
+    psess: &ParseSess,
+    src: &str,
+    start_pos: BytePos,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> Option<TokenStream> {
+    if src.len() < MIN_PARALLEL_LEN || !is_dyn_thread_safe() {
+        return None;
+    }
+    lex_token_trees_in_chunks(psess, src, start_pos, CHUNK_LEN, frontmatter_allowed)
+}
+
+/// Lexes `src` in chunks of about `chunk_len` each, which run in parallel if they can. Returns
+/// `None` if `src` can't be split up, or if lexing it would find errors.
+pub(crate) fn lex_token_trees_in_chunks(
+    psess: &ParseSess,
+    src: &str,
+    start_pos: BytePos,
+    chunk_len: usize,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> Option<TokenStream> {
+    let chunks = chunks(psess, src, start_pos, chunk_len, frontmatter_allowed)?;
+    if chunks.len() < 2 {
+        return None;
+    }
+    let streams: Vec<TokenStream> = par_map(chunks, |chunk: Range<usize>| {
+        let pos = start_pos + BytePos::from_usize(chunk.start);
+        // Only the first chunk can have a shebang or a frontmatter.
+        let string_reader = if chunk.start == 0 {
+            super::string_reader(psess, &src[chunk], pos, None, false, frontmatter_allowed)
+        } else {
+            StringReader::new(psess, &src[chunk], pos, None, None)
+        };
+        match super::lex_all_token_trees(psess, string_reader) {
+            Ok((stream, _)) => stream,
+            Err(errs) => {
+                for err in errs {
+                    err.cancel();
+                }
+                psess.dcx().bug("a chunk with matching delimiters failed to lex")
+            }
+        }
+    });
+
+    let mut trees = Vec::new();
+    for stream in streams {
+        trees.extend(stream.trees().cloned());
+    }
+    Some(TokenStream::new(trees))
+}
+
+/// Splits `src` into chunks of about `chunk_len` that lex the same on their own as they do in all
+/// of `src`, found by a quick pass of `rustc_lexer` over it, which also interns the symbols of its
+/// tokens, see [`intern_symbols`]. A chunk ends after whitespace outside of any delimiters, so it
+/// holds whole token trees, and its last token is followed by whitespace either way. Returns
+/// `None` if `src` has tokens that the lexer reports errors for, or delimiters that don't match
+/// up.
+fn chunks(
+    psess: &ParseSess,
+    src: &str,
+    start_pos: BytePos,
+    chunk_len: usize,
+    frontmatter_allowed: FrontmatterAllowed,
+) -> Option<Vec<Range<usize>>> {
+    let start = super::preamble_len(src, frontmatter_allowed);
+    let mut pos = start;
+    let mut open_delims = Vec::new();
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0;
+    for token in rustc_lexer::tokenize(&src[start..]) {
+        if !super::is_clean(token.kind) {
+            return None;
+        }
+        let token_start = pos;
+        pos += token.len as usize;
+        let lo = start_pos + BytePos::from_usize(token_start);
+        let span = Span::with_root_ctxt(lo, start_pos + BytePos::from_usize(pos));
+        intern_symbols(psess, &src[token_start..pos], token.kind, span)?;
+        match token.kind {
+            TokenKind::OpenParen | TokenKind::OpenBrace | TokenKind::OpenBracket => {
+                open_delims.push(token.kind)
+            }
+            TokenKind::CloseParen | TokenKind::CloseBrace | TokenKind::CloseBracket => {
+                match (open_delims.pop()?, token.kind) {
+                    (TokenKind::OpenParen, TokenKind::CloseParen)
+                    | (TokenKind::OpenBrace, TokenKind::CloseBrace)
+                    | (TokenKind::OpenBracket, TokenKind::CloseBracket) => {}
+                    _ => return None,
+                }
+            }
+            TokenKind::Whitespace
+                if open_delims.is_empty() && pos - chunk_start >= chunk_len && pos < src.len() =>
+            {
+                chunks.push(chunk_start..pos);
+                chunk_start = pos;
+            }
+            _ => {}
+        }
+    }
+    if !open_delims.is_empty() {
+        return None;
+    }
+    chunks.push(chunk_start..src.len());
+    Some(chunks)
+}
+
+/// Interns the symbols that the lexer makes of `token`, the `text` at `span`, in the same way,
+/// and puts its identifier in the symbol gallery. Returns `None` for tokens that the lexer reads
+/// differently in older editions, which this doesn't handle, so `src` is lexed on one thread.
+///
+/// Literals with invalid escapes are interned with their quotes by the lexer, which this doesn't
+/// do, but those are errors anyway.
+fn intern_symbols(psess: &ParseSess, text: &str, token: TokenKind, span: Span) -> Option<()> {
+    match token {
+        TokenKind::Ident => psess.symbol_gallery.insert(nfc_normalize(text), span),
+        TokenKind::RawIdent => psess.symbol_gallery.insert(nfc_normalize(&text[2..]), span),
+        TokenKind::Lifetime { .. } => {
+            Symbol::intern(text);
+        }
+        TokenKind::RawLifetime => return None,
+        TokenKind::LineComment { doc_style: Some(_) } => {
+            Symbol::intern(&text[3..]);
+        }
+        TokenKind::BlockComment { doc_style: Some(_), .. } => {
+            Symbol::intern(&text[3..text.len() - 2]);
+        }
+        TokenKind::Literal { kind, suffix_start } => {
+            let (lit, suffix) = text.split_at(suffix_start as usize);
+            let (prefix_len, postfix_len) = match kind {
+                LiteralKind::CStr { .. } | LiteralKind::RawCStr { .. }
+                    if !span.at_least_rust_2021() =>
+                {
+                    return None;
+                }
+                LiteralKind::Char { .. } | LiteralKind::Str { .. } => (1, 1),
+                LiteralKind::Byte { .. }
+                | LiteralKind::ByteStr { .. }
+                | LiteralKind::CStr { .. } => (2, 1),
+                LiteralKind::RawStr { n_hashes } => {
+                    let n = usize::from(n_hashes?);
+                    (2 + n, 1 + n)
+                }
+                LiteralKind::RawByteStr { n_hashes } | LiteralKind::RawCStr { n_hashes } => {
+                    let n = usize::from(n_hashes?);
+                    (3 + n, 1 + n)
+                }
+                LiteralKind::Int { .. } | LiteralKind::Float { .. } => (0, 0),
+            };
+            Symbol::intern(&lit[prefix_len..lit.len() - postfix_len]);
+            if !suffix.is_empty() && suffix != "_" {
+                Symbol::intern(suffix);
+            }
+        }
+        _ => {}
+    }
+    Some(())
+}
@@ -0,0 +1,103 @@
+//! Relexing only the part of a file that an edit changed, for tools like editors that need the
+//! tokens of a large file again after every keystroke.
+
+use std::ops::Range;
+
+use rustc_ast::tokenstream::{TokenStream, TokenTree};
+use rustc_errors::Diag;
+use rustc_session::parse::ParseSess;
+use rustc_span::{BytePos, Pos};
+
+use super::FrontmatterAllowed;
+
+/// Lexes `src` again after an edit, reusing what it can of `old_stream`, which was lexed from the
+/// text before the edit, at the same `start_pos`. The edit replaced `dirty_range` of the old text,
+/// in bytes, with `new_len` bytes.
+///
+/// Only the top-level token trees that the edit touches are relexed, with one more tree on each
+/// side of them, so that the relexed text starts and ends in text that the edit didn't change. The
+/// trees before them are kept as they are, and the ones after them are only moved by the change in
+/// length. Where that isn't enough, e.g. when the edit opens a string or a comment that goes on
+/// past the relexed text, or when it unbalances the delimiters, all of `src` is lexed instead, so
+/// the result is always the stream that lexing all of `src` would give.
+pub fn relex_region<'psess>(
+    psess: &'psess ParseSess,
+    old_stream: &TokenStream,
+    src: &str,
+    start_pos: BytePos,
+    dirty_range: Range<usize>,
+    new_len: usize,
+) -> Result<TokenStream, Vec<Diag<'psess>>> {
+    let relex_all = || super::lex_token_trees(psess, src, start_pos, None, FrontmatterAllowed::Yes);
+    let trees: Vec<&TokenTree> = old_stream.trees().collect();
+    let range = |tree: &TokenTree| {
+        let span = tree.span();
+        (span.lo() - start_pos).to_usize()..(span.hi() - start_pos).to_usize()
+    };
+
+    let first = trees.iter().position(|tree| range(tree).end >= dirty_range.start);
+    let last = trees.iter().rposition(|tree| range(tree).start <= dirty_range.end);
+    let (Some(first), Some(last)) = (first, last) else { return relex_all() };
+    if first == 0 || last + 1 == trees.len() {
+        return relex_all();
+    }
+    let (first, last) = (first - 1, last + 1);
+
+    let lo = range(trees[first]).start;
+    let hi = range(trees[last]).end + new_len - dirty_range.len();
+    let Some(chunk) = src.get(lo..hi) else { return relex_all() };
+    if !lexes_alone(&src[lo..], chunk.len()) {
+        return relex_all();
+    }
+    let chunk_pos = start_pos + BytePos::from_usize(lo);
+    let relexed = super::lex_token_trees(psess, chunk, chunk_pos, None, FrontmatterAllowed::No);
+    let mut relexed: Vec<TokenTree> = match relexed {
+        Ok(stream) => stream.trees().cloned().collect(),
+        Err(errs) => {
+            // The delimiters don't match up in the chunk, so they don't in the file either, and
+            // that is best reported for the whole file.
+            for err in errs {
+                err.cancel();
+            }
+            return relex_all();
+        }
+    };
+
+    // The last relexed tree is the same as the old one, other than its spacing, as the lexer saw
+    // the end of the chunk after it, rather than the trees that follow.
+    let from = start_pos + BytePos::from_usize(dirty_range.end);
+    let to = start_pos + BytePos::from_usize(dirty_range.start + new_len);
+    let rest = TokenStream::new(trees[last..].iter().map(|&tree| tree.clone()).collect());
+    let rest = crate::move_stream(&rest, from, to);
+    debug_assert_eq!(
+        relexed.last().map(|tree| tree.span()),
+        rest.trees().next().map(|tree| tree.span())
+    );
+    relexed.pop();
+
+    let mut new_trees: Vec<TokenTree> = trees[..first].iter().map(|&tree| tree.clone()).collect();
+    new_trees.extend(relexed);
+    new_trees.extend(rest.trees().cloned());
+    Ok(TokenStream::new(new_trees))
+}
+
+/// Whether the first `len` bytes of `src` lex the same on their own as they do with the rest of
+/// `src` after them. Tokens that the lexer reports errors for as it goes, like unknown characters
+/// and unterminated literals, are left to lexing all of the file, so they aren't reported twice.
+pub(crate) fn lexes_alone(src: &str, len: usize) -> bool {
+    let chunk = &src[..len];
+    if rustc_lexer::strip_shebang(chunk).is_some() || super::frontmatter(chunk).is_some() {
+        return false;
+    }
+    let mut pos = 0;
+    for token in rustc_lexer::tokenize(src) {
+        if !super::is_clean(token.kind) {
+            return false;
+        }
+        pos += token.len as usize;
+        if pos >= len {
+            break;
+        }
+    }
+    pos == len
+}
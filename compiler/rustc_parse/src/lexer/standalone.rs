@@ -0,0 +1,108 @@
+//! Lexing without a `ParseSess` of the caller's, for tools like syntax highlighters that want the
+//! parser's tokens, but don't have a compiler session around.
+
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+use rustc_ast::token::{self, Token};
+use rustc_data_structures::sync::{Lock, Lrc};
+use rustc_errors::emitter::Emitter;
+use rustc_errors::translation::{to_fluent_args, Translate};
+use rustc_errors::{
+    fallback_fluent_bundle, DiagCtxt, DiagInner, FatalErrorMarker, FluentBundle, LazyFallbackBundle,
+};
+use rustc_session::parse::ParseSess;
+use rustc_span::source_map::{FilePathMapping, SourceMap};
+use rustc_span::{BytePos, FileName, Pos, Span};
+
+use super::FrontmatterAllowed;
+
+/// An error that [`tokenize`] found, e.g. an unknown character or an unterminated string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LexError {
+    pub message: String,
+    /// Where the error is in the source, in bytes.
+    pub range: Range<usize>,
+}
+
+/// Collects the errors of the lexer, rather than printing them.
+struct CollectingEmitter {
+    fallback_bundle: LazyFallbackBundle,
+    start_pos: BytePos,
+    errors: Lrc<Lock<Vec<LexError>>>,
+}
+
+impl Translate for CollectingEmitter {
+    fn fluent_bundle(&self) -> Option<&Lrc<FluentBundle>> {
+        None
+    }
+
+    fn fallback_fluent_bundle(&self) -> &FluentBundle {
+        &self.fallback_bundle
+    }
+}
+
+impl Emitter for CollectingEmitter {
+    fn source_map(&self) -> Option<&Lrc<SourceMap>> {
+        None
+    }
+
+    fn emit_diagnostic(&mut self, diag: DiagInner) {
+        if !diag.is_error() {
+            return;
+        }
+        let args = to_fluent_args(diag.args.iter());
+        let message = self.translate_messages(&diag.messages, &args).into_owned();
+        let range = diag.span.primary_span().map_or(0..0, |span| range(self.start_pos, span));
+        self.errors.lock().push(LexError { message, range });
+    }
+}
+
+fn range(start_pos: BytePos, span: Span) -> Range<usize> {
+    (span.lo() - start_pos).to_usize()..(span.hi() - start_pos).to_usize()
+}
+
+/// Lexes `src` into the tokens the parser would see, with where each of them is in `src`, in
+/// bytes. Errors are added to `errors` instead of being emitted, and lexing goes on after them
+/// like it does for the parser, other than after a fatal error. As there is no source map, the
+/// spans of the tokens are only meaningful relative to each other.
+///
+/// Delimiters aren't matched up, so unbalanced ones aren't errors here. Like other lexing, this
+/// has to run with session globals set, for interning the symbols in the tokens.
+pub fn tokenize(
+    src: &str,
+    errors: &mut Vec<LexError>,
+) -> impl Iterator<Item = (Token, Range<usize>)> {
+    let source_map = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+    let source_file = source_map.new_source_file(FileName::anon_source_code(src), src.to_owned());
+    let start_pos = source_file.start_pos;
+    let collected = Lrc::new(Lock::new(Vec::new()));
+    let emitter = CollectingEmitter {
+        fallback_bundle: fallback_fluent_bundle(vec![crate::DEFAULT_LOCALE_RESOURCE], false),
+        start_pos,
+        errors: collected.clone(),
+    };
+    let psess = ParseSess::with_dcx(DiagCtxt::new(Box::new(emitter)), source_map);
+
+    let mut tokens = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut reader =
+            super::string_reader(&psess, src, start_pos, None, false, FrontmatterAllowed::No);
+        loop {
+            let (token, _) = reader.next_token();
+            if token.kind == token::Eof {
+                break;
+            }
+            let range = range(start_pos, token.span);
+            tokens.push((token, range));
+        }
+    }));
+    if let Err(value) = result {
+        if !value.is::<FatalErrorMarker>() {
+            panic::resume_unwind(value);
+        }
+    }
+    drop(psess);
+    errors.append(&mut collected.lock());
+    tokens.into_iter()
+}
@@ -1,6 +1,7 @@
 use rustc_ast::token::{self, Delimiter, Token};
 use rustc_ast::tokenstream::{DelimSpacing, DelimSpan, Spacing, TokenStream, TokenTree};
 use rustc_ast_pretty::pprust::token_to_string;
+use rustc_data_structures::stack::ensure_sufficient_stack;
 use rustc_errors::{Applicability, PErr};
 use rustc_span::symbol::kw;
 
@@ -8,6 +9,7 @@
     report_suspicious_mismatch_block, same_indentation_level, TokenTreeDiagInfo,
 };
 use super::{StringReader, UnmatchedDelim};
+use crate::cst::Trivia;
 use crate::Parser;
 
 pub(super) struct TokenTreesReader<'psess, 'src> {
@@ -21,14 +23,15 @@ pub(super) struct TokenTreesReader<'psess, 'src> {
 impl<'psess, 'src> TokenTreesReader<'psess, 'src> {
     pub(super) fn lex_all_token_trees(
         string_reader: StringReader<'psess, 'src>,
-    ) -> (TokenStream, Result<(), Vec<PErr<'psess>>>, Vec<UnmatchedDelim>) {
+    ) -> (TokenStream, Result<(), Vec<PErr<'psess>>>, Vec<UnmatchedDelim>, Option<Vec<Trivia>>)
+    {
         let mut tt_reader = TokenTreesReader {
             string_reader,
             token: Token::dummy(),
             diag_info: TokenTreeDiagInfo::default(),
         };
         let (_open_spacing, stream, res) = tt_reader.lex_token_trees(/* is_delimited */ false);
-        (stream, res, tt_reader.diag_info.unmatched_delims)
+        (stream, res, tt_reader.diag_info.unmatched_delims, tt_reader.string_reader.trivia)
     }
 
     // Lex into a token stream. The `Spacing` in the result is that of the
@@ -121,7 +124,8 @@ fn lex_token_tree_open_delim(
         // Lex the token trees within the delimiters.
         // We stop at any delimiter so we can try to recover if the user
         // uses an incorrect delimiter.
-        let (open_spacing, tts, res) = self.lex_token_trees(/* is_delimited */ true);
+        let (open_spacing, tts, res) =
+            ensure_sufficient_stack(|| self.lex_token_trees(/* is_delimited */ true));
         if let Err(errs) = res {
             return Err(self.unclosed_delim_err(tts, errs));
         }
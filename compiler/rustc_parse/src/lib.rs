@@ -14,23 +14,38 @@
 #![warn(unreachable_pub)]
 // tidy-alphabetical-end
 
+use std::io::Read;
 use std::path::Path;
 
 use rustc_ast as ast;
-use rustc_ast::tokenstream::TokenStream;
+use rustc_ast::ptr::P;
+use rustc_ast::tokenstream::{DelimSpan, TokenStream, TokenTree};
 use rustc_ast::{token, AttrItem, Attribute, MetaItem};
 use rustc_ast_pretty::pprust;
 use rustc_data_structures::sync::Lrc;
 use rustc_errors::{Diag, FatalError, PResult};
+use rustc_session::config::Cfg;
 use rustc_session::parse::ParseSess;
-use rustc_span::{FileName, SourceFile, Span};
+use rustc_span::{sym, BytePos, FileName, SourceFile, Span};
 
 pub const MACRO_ARGUMENTS: Option<&str> = Some("macro arguments");
 
+pub mod cst;
+mod fake_spans;
 #[macro_use]
 pub mod parser;
-use parser::{make_unclosed_delims_error, Parser};
+use parser::attr::{AllowLeadingUnsafe, InnerAttrPolicy};
+use parser::{
+    make_unclosed_delims_error, CommaRecoveryMode, ForceCollect, Parser, RecoverColon, RecoverComma,
+};
 pub mod lexer;
+use lexer::FrontmatterAllowed;
+mod parse_tree;
+pub use parse_tree::{parse_tree_json, PARSE_TREE_FORMAT_VERSION};
+mod reparse;
+pub use reparse::reparse_crate;
+mod roundtrip;
+pub use roundtrip::{check_item_round_trip, verify_crate_round_trip, RoundTripDiff};
 pub mod validate_attr;
 
 mod errors;
@@ -53,13 +68,27 @@ pub fn unwrap_or_emit_fatal<T>(expr: Result<T, Vec<Diag<'_>>>) -> T {
 /// Creates a new parser from a source string. On failure, the errors must be consumed via
 /// `unwrap_or_emit_fatal`, `emit`, `cancel`, etc., otherwise a panic will occur when they are
 /// dropped.
+///
+/// `source` is a snippet of code, which can't start with a frontmatter block. Use
+/// `new_parser_from_crate_source_str` for the source of a whole crate.
 pub fn new_parser_from_source_str(
     psess: &ParseSess,
     name: FileName,
     source: String,
 ) -> Result<Parser<'_>, Vec<Diag<'_>>> {
     let source_file = psess.source_map().new_source_file(name, source);
-    new_parser_from_source_file(psess, source_file)
+    new_parser_from_source_file(psess, source_file, FrontmatterAllowed::No)
+}
+
+/// Like `new_parser_from_source_str`, but for the source of a crate that isn't read from a file,
+/// e.g. from stdin, which may start with a frontmatter block as a crate root file may.
+pub fn new_parser_from_crate_source_str(
+    psess: &ParseSess,
+    name: FileName,
+    source: String,
+) -> Result<Parser<'_>, Vec<Diag<'_>>> {
+    let source_file = psess.source_map().new_source_file(name, source);
+    new_parser_from_source_file(psess, source_file, FrontmatterAllowed::Yes)
 }
 
 /// Creates a new parser from a filename. On failure, the errors must be consumed via
@@ -80,7 +109,53 @@ pub fn new_parser_from_file<'a>(
         }
         err.emit();
     });
-    new_parser_from_source_file(psess, source_file)
+    new_parser_from_source_file(psess, source_file, FrontmatterAllowed::Yes)
+}
+
+/// Like `new_parser_from_file`, but also returns the error for a file that can't be read instead
+/// of aborting, for tools that embed the parser and want to carry on. On failure, the errors must
+/// be consumed via `unwrap_or_emit_fatal`, `emit`, `cancel`, etc., otherwise a panic will occur
+/// when they are dropped.
+///
+/// If a span is given, that is used on an error as the source of the problem.
+pub fn maybe_new_parser_from_file<'a>(
+    psess: &'a ParseSess,
+    path: &Path,
+    sp: Option<Span>,
+) -> Result<Parser<'a>, Vec<Diag<'a>>> {
+    let source_file = match psess.source_map().load_file(path) {
+        Ok(source_file) => source_file,
+        Err(e) => {
+            let msg = format!("couldn't read {}: {}", path.display(), e);
+            let mut err = psess.dcx().struct_err(msg);
+            if let Some(sp) = sp {
+                err.span(sp);
+            }
+            return Err(vec![err]);
+        }
+    };
+    new_parser_from_source_file(psess, source_file, FrontmatterAllowed::Yes)
+}
+
+/// Creates a new parser from whatever `reader` yields until it is exhausted, e.g. the output of
+/// a code generator read from a pipe, without going through a file on disk. On failure, the
+/// errors must be consumed via `unwrap_or_emit_fatal`, `emit`, `cancel`, etc., otherwise a panic
+/// will occur when they are dropped.
+///
+/// The source is read in full before lexing starts, since a file in the source map can't grow
+/// once it has been added. Reading fails on source that isn't UTF-8, as with files.
+pub fn new_parser_from_reader(
+    psess: &ParseSess,
+    name: FileName,
+    mut reader: impl Read,
+) -> Result<Parser<'_>, Vec<Diag<'_>>> {
+    let mut source = String::new();
+    if let Err(e) = reader.read_to_string(&mut source) {
+        let msg =
+            format!("couldn't read {}: {}", psess.source_map().filename_for_diagnostics(&name), e);
+        return Err(vec![psess.dcx().struct_err(msg)]);
+    }
+    new_parser_from_crate_source_str(psess, name, source)
 }
 
 /// Given a session and a `source_file`, return a parser. Returns any buffered errors from lexing
@@ -88,9 +163,10 @@ pub fn new_parser_from_file<'a>(
 fn new_parser_from_source_file(
     psess: &ParseSess,
     source_file: Lrc<SourceFile>,
+    frontmatter_allowed: FrontmatterAllowed,
 ) -> Result<Parser<'_>, Vec<Diag<'_>>> {
     let end_pos = source_file.end_position();
-    let stream = source_file_to_stream(psess, source_file, None)?;
+    let stream = source_file_to_stream(psess, source_file, None, frontmatter_allowed)?;
     let mut parser = Parser::new(psess, stream, None);
     if parser.token == token::Eof {
         parser.token.span = Span::new(end_pos, end_pos, parser.token.span.ctxt(), None);
@@ -105,7 +181,59 @@ pub fn source_str_to_stream(
     override_span: Option<Span>,
 ) -> Result<TokenStream, Vec<Diag<'_>>> {
     let source_file = psess.source_map().new_source_file(name, source);
-    source_file_to_stream(psess, source_file, override_span)
+    let Some(cache) = &psess.token_stream_cache else {
+        return source_file_to_stream(psess, source_file, override_span, FrontmatterAllowed::No);
+    };
+
+    let key = (source_file.src_hash, override_span);
+    let cached = cache.lock().get(&key).cloned();
+    if let Some((start_pos, stream)) = cached {
+        // With an `override_span`, all the tokens have that span, no matter where they are from.
+        if start_pos == source_file.start_pos || override_span.is_some() {
+            return Ok(stream);
+        }
+        return Ok(move_stream(&stream, start_pos, source_file.start_pos));
+    }
+    let stream =
+        source_file_to_stream(psess, source_file.clone(), override_span, FrontmatterAllowed::No)?;
+    cache.lock().insert(key, (source_file.start_pos, stream.clone()));
+    Ok(stream)
+}
+
+/// Like `source_str_to_stream`, but also keeps the comments that aren't doc comments, which the
+/// lexer drops otherwise, for tools like macro debuggers that have to show them. The parser has
+/// no tokens for them, so they are returned next to the stream, with the rest of the trivia, see
+/// [`cst::SourceTrivia::comments`]. They can be attached to the tokens around them by their spans,
+/// see [`cst::SourceTrivia::between`], so the token stream cache, which may move spans, isn't used.
+pub fn source_str_to_stream_with_comments(
+    psess: &ParseSess,
+    name: FileName,
+    source: String,
+) -> Result<(TokenStream, cst::SourceTrivia), Vec<Diag<'_>>> {
+    let source_file = psess.source_map().new_source_file(name, source);
+    cst::lex_source_with_trivia(psess, &source_file, FrontmatterAllowed::No)
+}
+
+/// Moves the spans in `stream`, lexed from a file at `from`, to the same text in a file at `to`.
+fn move_stream(stream: &TokenStream, from: BytePos, to: BytePos) -> TokenStream {
+    let move_span = |span: Span| {
+        let data = span.data();
+        let lo = BytePos(data.lo.0 - from.0 + to.0);
+        let hi = BytePos(data.hi.0 - from.0 + to.0);
+        Span::new(lo, hi, data.ctxt, data.parent)
+    };
+    let trees = stream.trees().map(|tree| match tree {
+        TokenTree::Token(token, spacing) => {
+            TokenTree::Token(token::Token::new(token.kind.clone(), move_span(token.span)), *spacing)
+        }
+        TokenTree::Delimited(dspan, spacing, delim, stream) => TokenTree::Delimited(
+            DelimSpan::from_pair(move_span(dspan.open), move_span(dspan.close)),
+            *spacing,
+            *delim,
+            move_stream(stream, from, to),
+        ),
+    });
+    TokenStream::new(trees.collect())
 }
 
 /// Given a source file, produces a sequence of token trees. Returns any buffered errors from
@@ -114,15 +242,59 @@ fn source_file_to_stream<'psess>(
     psess: &'psess ParseSess,
     source_file: Lrc<SourceFile>,
     override_span: Option<Span>,
+    frontmatter_allowed: FrontmatterAllowed,
 ) -> Result<TokenStream, Vec<Diag<'psess>>> {
-    let src = source_file.src.as_ref().unwrap_or_else(|| {
+    let src = source_file_src(psess, &source_file);
+    lexer::lex_token_trees(psess, src, source_file.start_pos, override_span, frontmatter_allowed)
+}
+
+fn source_file_src<'a>(psess: &ParseSess, source_file: &'a SourceFile) -> &'a str {
+    source_file.src.as_ref().unwrap_or_else(|| {
         psess.dcx().bug(format!(
             "cannot lex `source_file` without source: {}",
             psess.source_map().filename_for_diagnostics(&source_file.name)
         ));
-    });
+    })
+}
 
-    lexer::lex_token_trees(psess, src.as_str(), source_file.start_pos, override_span)
+/// Parses the inner attributes at the top of the file at `path`, e.g. `#![no_std]`, for tools
+/// that only need the crate's attributes. Only as much of the file as the attributes take is
+/// lexed, so this stays fast for large files, and errors after the attributes aren't reported.
+/// On failure, the errors must be consumed via `unwrap_or_emit_fatal`, `emit`, `cancel`, etc.,
+/// otherwise a panic will occur when they are dropped.
+pub fn parse_crate_attrs_from_file<'a>(
+    path: &Path,
+    psess: &'a ParseSess,
+) -> Result<ast::AttrVec, Vec<Diag<'a>>> {
+    let source_file = match psess.source_map().load_file(path) {
+        Ok(source_file) => source_file,
+        Err(e) => {
+            let msg = format!("couldn't read {}: {}", path.display(), e);
+            return Err(vec![psess.dcx().struct_err(msg)]);
+        }
+    };
+    parse_crate_attrs_from_source_file(psess, source_file)
+}
+
+/// Like `parse_crate_attrs_from_file`, but for `source` instead of a file.
+pub fn parse_crate_attrs_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+) -> Result<ast::AttrVec, Vec<Diag<'_>>> {
+    let source_file = psess.source_map().new_source_file(name, source);
+    parse_crate_attrs_from_source_file(psess, source_file)
+}
+
+fn parse_crate_attrs_from_source_file(
+    psess: &ParseSess,
+    source_file: Lrc<SourceFile>,
+) -> Result<ast::AttrVec, Vec<Diag<'_>>> {
+    let src = source_file_src(psess, &source_file);
+    let src = &src[..lexer::inner_attrs_len(src)];
+    let start_pos = source_file.start_pos;
+    let stream = lexer::lex_token_trees(psess, src, start_pos, None, FrontmatterAllowed::Yes)?;
+    Parser::new(psess, stream, None).parse_inner_attributes().map_err(|e| vec![e])
 }
 
 /// Runs the given subparser `f` on the tokens of the given `attr`'s item.
@@ -140,32 +312,225 @@ pub fn parse_in<'a, T>(
     Ok(result)
 }
 
+/// Like `parse_in`, but returns the token trees that `f` didn't consume, rather than failing if
+/// there are any, for grammars that allow trailing tokens and leave them to the caller.
+///
+/// It still fails if `f` stops within delimiters, as what is left then isn't a sequence of token
+/// trees.
+pub fn parse_in_with_rest<'a, T>(
+    psess: &'a ParseSess,
+    tts: TokenStream,
+    name: &'static str,
+    mut f: impl FnMut(&mut Parser<'a>) -> PResult<'a, T>,
+) -> PResult<'a, (T, TokenStream)> {
+    let mut parser = Parser::new(psess, tts, Some(name));
+    let result = f(&mut parser)?;
+    let mut rest = Vec::new();
+    while !matches!(parser.token.kind, token::Eof | token::CloseDelim(_)) {
+        rest.push(parser.parse_token_tree());
+    }
+    if parser.token != token::Eof {
+        parser.unexpected()?;
+    }
+    Ok((result, TokenStream::new(rest)))
+}
+
+/// Parses `source` with the given subparser `f`, which has to consume all of it. Errors from lexing
+/// `source` are returned too, so on failure, the errors must be consumed via
+/// `unwrap_or_emit_fatal`, `emit`, `cancel`, etc., otherwise a panic will occur when they are
+/// dropped.
+fn parse_from_source_str<'a, T>(
+    psess: &'a ParseSess,
+    name: FileName,
+    source: String,
+    f: impl FnOnce(&mut Parser<'a>) -> PResult<'a, T>,
+) -> Result<T, Vec<Diag<'a>>> {
+    let mut parser = new_parser_from_source_str(psess, name, source)?;
+    let result = f(&mut parser).map_err(|e| vec![e])?;
+    if parser.token != token::Eof {
+        parser.unexpected().map_err(|e| vec![e])?;
+    }
+    Ok(result)
+}
+
+/// Parses `source` as a single expression, for tools that need to parse snippets of code. Lexing
+/// errors are returned too, and must be consumed.
+pub fn parse_expr_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+) -> Result<P<ast::Expr>, Vec<Diag<'_>>> {
+    parse_from_source_str(psess, name, source, |p| p.parse_expr())
+}
+
+/// Parses `source` as a single statement, for tools that need to parse snippets of code. A
+/// trailing `;` is included in the statement. Errors are returned rather than recovered from.
+pub fn parse_stmt_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+) -> Result<Option<ast::Stmt>, Vec<Diag<'_>>> {
+    parse_from_source_str(psess, name, source, |p| {
+        let Some(mut stmt) = p.parse_stmt_without_recovery(false, ForceCollect::No)? else {
+            return Ok(None);
+        };
+        if p.eat(&token::Semi) {
+            stmt = stmt.add_trailing_semicolon();
+            stmt.span = stmt.span.to(p.prev_token.span);
+        }
+        Ok(Some(stmt))
+    })
+}
+
+/// Parses `source` as a single pattern, for tools that need to parse snippets of code. If
+/// `allow_top_alt` is false, an or-pattern is only accepted in parentheses, as for closure and
+/// function parameters and `pat_param` macro fragments. Lexing errors are returned too, and must be
+/// consumed.
+pub fn parse_pat_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+    allow_top_alt: bool,
+) -> Result<P<ast::Pat>, Vec<Diag<'_>>> {
+    parse_from_source_str(psess, name, source, |p| {
+        if allow_top_alt {
+            p.parse_pat_allow_top_alt(
+                None,
+                RecoverComma::No,
+                RecoverColon::No,
+                CommaRecoveryMode::EitherTupleOrPipe,
+            )
+        } else {
+            p.parse_pat_no_top_alt(None, None)
+        }
+    })
+}
+
+/// Parses `source` as a single type, for tools that need to parse type strings, e.g. from
+/// intra-doc links. Lexing errors are returned too, and must be consumed.
+pub fn parse_ty_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+) -> Result<P<ast::Ty>, Vec<Diag<'_>>> {
+    parse_from_source_str(psess, name, source, |p| p.parse_ty())
+}
+
+/// Parses `source` as a single meta item, e.g. `key = "value"` or `path(list)`, as found in `cfg`
+/// predicates and attributes. Lexing errors are returned too, and must be consumed.
+pub fn parse_meta_item_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+    unsafe_allowed: AllowLeadingUnsafe,
+) -> Result<MetaItem, Vec<Diag<'_>>> {
+    parse_from_source_str(psess, name, source, |p| p.parse_meta_item(unsafe_allowed))
+}
+
+/// Parses `source` as a single attribute, inner or outer, including doc comments. Lexing errors
+/// are returned too, and must be consumed.
+pub fn parse_attribute_from_source_str(
+    name: FileName,
+    source: String,
+    psess: &ParseSess,
+) -> Result<Attribute, Vec<Diag<'_>>> {
+    parse_from_source_str(psess, name, source, |p| {
+        if let token::DocComment(comment_kind, style, data) = p.token.kind {
+            p.bump();
+            let span = p.prev_token.span;
+            Ok(ast::attr::mk_doc_comment(&psess.attr_id_generator, comment_kind, style, data, span))
+        } else if p.token == token::Pound {
+            p.parse_attribute(InnerAttrPolicy::Permitted)
+        } else {
+            p.unexpected_any()
+        }
+    })
+}
+
+/// Makes the tokens of `item` by pretty-printing it, for when its own tokens aren't available.
+/// The tokens get the span of `item`, or with `ParseSess::remap_fake_token_spans`, the spans of
+/// the matching tokens of the source of `item` where those can be found.
 pub fn fake_token_stream_for_item(psess: &ParseSess, item: &ast::Item) -> TokenStream {
     let source = pprust::item_to_string(item);
     let filename = FileName::macro_expansion_source_code(&source);
-    unwrap_or_emit_fatal(source_str_to_stream(psess, filename, source, Some(item.span)))
+    let stream =
+        unwrap_or_emit_fatal(source_str_to_stream(psess, filename, source, Some(item.span)));
+    if !psess.remap_fake_token_spans {
+        return stream;
+    }
+    fake_spans::remap_spans(psess, &stream, &fake_spans::item_regions(item))
 }
 
+/// Like `fake_token_stream_for_item`, for the whole crate.
 pub fn fake_token_stream_for_crate(psess: &ParseSess, krate: &ast::Crate) -> TokenStream {
     let source = pprust::crate_to_string_for_macros(krate);
     let filename = FileName::macro_expansion_source_code(&source);
-    unwrap_or_emit_fatal(source_str_to_stream(
+    let stream = unwrap_or_emit_fatal(source_str_to_stream(
         psess,
         filename,
         source,
         Some(krate.spans.inner_span),
-    ))
+    ));
+    if !psess.remap_fake_token_spans {
+        return stream;
+    }
+    fake_spans::remap_spans(psess, &stream, &[krate.spans.inner_span])
 }
 
 pub fn parse_cfg_attr(
     cfg_attr: &Attribute,
     psess: &ParseSess,
+) -> Option<(MetaItem, Vec<(AttrItem, Span)>)> {
+    parse_cfg_attr_args(&cfg_attr.get_normal_item().args, cfg_attr.span, psess)
+}
+
+/// Expands `cfg_attr`, and any `cfg_attr` nested in it, e.g.
+/// `#[cfg_attr(unix, cfg_attr(debug_assertions, inline))]`, to the attributes whose predicates
+/// all hold, as decided by `eval`. The attributes are returned with their own spans. Errors in
+/// the syntax of a `cfg_attr` are emitted, and it expands to nothing.
+pub fn expand_cfg_attr_recursive(
+    cfg_attr: &Attribute,
+    psess: &ParseSess,
+    mut eval: impl FnMut(&MetaItem) -> bool,
+) -> Vec<(AttrItem, Span)> {
+    let mut attrs = Vec::new();
+    if let Some((cfg_predicate, items)) = parse_cfg_attr(cfg_attr, psess) {
+        expand_cfg_attr_items(cfg_predicate, items, psess, &mut eval, &mut attrs);
+    }
+    attrs
+}
+
+fn expand_cfg_attr_items(
+    cfg_predicate: MetaItem,
+    items: Vec<(AttrItem, Span)>,
+    psess: &ParseSess,
+    eval: &mut impl FnMut(&MetaItem) -> bool,
+    attrs: &mut Vec<(AttrItem, Span)>,
+) {
+    if !eval(&cfg_predicate) {
+        return;
+    }
+    for (item, span) in items {
+        if item.path == sym::cfg_attr {
+            if let Some((cfg_predicate, items)) = parse_cfg_attr_args(&item.args, span, psess) {
+                expand_cfg_attr_items(cfg_predicate, items, psess, eval, attrs);
+            }
+        } else {
+            attrs.push((item, span));
+        }
+    }
+}
+
+fn parse_cfg_attr_args(
+    args: &ast::AttrArgs,
+    span: Span,
+    psess: &ParseSess,
 ) -> Option<(MetaItem, Vec<(AttrItem, Span)>)> {
     const CFG_ATTR_GRAMMAR_HELP: &str = "#[cfg_attr(condition, attribute, other_attribute, ...)]";
     const CFG_ATTR_NOTE_REF: &str = "for more information, visit \
         <https://doc.rust-lang.org/reference/conditional-compilation.html#the-cfg_attr-attribute>";
 
-    match cfg_attr.get_normal_item().args {
+    match *args {
         ast::AttrArgs::Delimited(ast::DelimArgs { dspan, delim, ref tokens })
             if !tokens.is_empty() =>
         {
@@ -180,11 +545,85 @@ pub fn parse_cfg_attr(
             }
         }
         _ => {
-            psess.dcx().emit_err(errors::MalformedCfgAttr {
-                span: cfg_attr.span,
-                sugg: CFG_ATTR_GRAMMAR_HELP,
-            });
+            psess.dcx().emit_err(errors::MalformedCfgAttr { span, sugg: CFG_ATTR_GRAMMAR_HELP });
         }
     }
     None
 }
+
+/// Parses `source` as a `cfg` predicate, e.g. `all(unix, target_pointer_width = "64")`, and
+/// evaluates it with `eval_cfg_predicate`. Like with `parse_ty_from_source_str`, lexing errors are
+/// returned too, and must be consumed.
+pub fn eval_cfg_predicate_from_source_str<'a>(
+    name: FileName,
+    source: String,
+    psess: &'a ParseSess,
+    cfgs: &Cfg,
+) -> Result<bool, Vec<Diag<'a>>> {
+    let cfg = parse_meta_item_from_source_str(name, source, psess, AllowLeadingUnsafe::No)?;
+    eval_cfg_predicate(psess, &cfg, cfgs).map_err(|e| vec![e])
+}
+
+/// Evaluates the `cfg` predicate `cfg` against `cfgs`, the set of names and `name = "value"` pairs
+/// that are set. This is the grammar of `any`, `all` and `not` as the compiler accepts it,
+/// without feature gates, so `version(..)`, `target(..)` and other unstable predicates are
+/// invalid.
+pub fn eval_cfg_predicate<'a>(
+    psess: &'a ParseSess,
+    cfg: &MetaItem,
+    cfgs: &Cfg,
+) -> PResult<'a, bool> {
+    let dcx = psess.dcx();
+    match &cfg.kind {
+        ast::MetaItemKind::List(mis) => {
+            let mut predicates = Vec::with_capacity(mis.len());
+            for mi in mis {
+                let Some(predicate) = mi.meta_item() else {
+                    return Err(
+                        dcx.create_err(errors::CfgPredicateUnsupportedLiteral { span: mi.span() })
+                    );
+                };
+                predicates.push(predicate);
+            }
+            match cfg.name_or_empty() {
+                sym::any => {
+                    let mut any = false;
+                    for predicate in predicates {
+                        any |= eval_cfg_predicate(psess, predicate, cfgs)?;
+                    }
+                    Ok(any)
+                }
+                sym::all => {
+                    let mut all = true;
+                    for predicate in predicates {
+                        all &= eval_cfg_predicate(psess, predicate, cfgs)?;
+                    }
+                    Ok(all)
+                }
+                sym::not => {
+                    let [predicate] = predicates[..] else {
+                        return Err(
+                            dcx.create_err(errors::CfgPredicateExpectedOne { span: cfg.span })
+                        );
+                    };
+                    Ok(!eval_cfg_predicate(psess, predicate, cfgs)?)
+                }
+                _ => Err(dcx.create_err(errors::InvalidCfgPredicate {
+                    span: cfg.span,
+                    predicate: pprust::path_to_string(&cfg.path),
+                })),
+            }
+        }
+        ast::MetaItemKind::Word | ast::MetaItemKind::NameValue(..)
+            if cfg.path.segments.len() != 1 =>
+        {
+            Err(dcx.create_err(errors::CfgPredicateIdentifier { span: cfg.path.span }))
+        }
+        ast::MetaItemKind::NameValue(lit) if !lit.kind.is_str() => {
+            Err(dcx.create_err(errors::CfgPredicateValueNotStr { span: lit.span }))
+        }
+        ast::MetaItemKind::Word | ast::MetaItemKind::NameValue(..) => {
+            Ok(cfgs.contains(&(cfg.name_or_empty(), cfg.value_str())))
+        }
+    }
+}
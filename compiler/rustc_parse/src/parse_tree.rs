@@ -0,0 +1,442 @@
+//! A dump of the AST as the parser makes it, as JSON, for `-Z unpretty=parse-tree-json`. It is
+//! for tools that compare the parser with other grammars of Rust, so unlike the `Debug` output of
+//! `-Z unpretty=ast-tree`, it leaves out what only the compiler needs, like node ids and tokens,
+//! and doesn't change without `PARSE_TREE_FORMAT_VERSION` changing too.
+//!
+//! Each node is an object with:
+//! - `node`: what sort of node it is, e.g. `item`, `expr` or `ident`,
+//! - `kind`: for items, statements, expressions, patterns and types, which variant of the AST it
+//!   is, e.g. `Fn` or `MethodCall`,
+//! - `file`: the name of its file, for the crate and for nodes in another file than their parent,
+//!   like the contents of out-of-line modules,
+//! - `span`: where it is in its file, as the byte offsets `[lo, hi]`, unless it has no span,
+//! - fields of its own, e.g. the `name` of an identifier, or the `text` of a literal,
+//! - `children`: the nodes in it, unless there are none.
+//!
+//! The crate's node also has the `version` of the format.
+
+use std::fmt::Write;
+
+use rustc_ast as ast;
+use rustc_ast::visit::{self, AssocCtxt, LifetimeCtxt, Visitor};
+use rustc_ast_pretty::pprust;
+use rustc_span::source_map::SourceMap;
+use rustc_span::symbol::{kw, Ident};
+use rustc_span::{FileName, Span};
+
+/// The version of the format of [`parse_tree_json`], which changes whenever the dump of some
+/// source does.
+pub const PARSE_TREE_FORMAT_VERSION: u32 = 1;
+
+/// Dumps `krate`, which should be as the parser made it, i.e. from before expansion, as JSON.
+pub fn parse_tree_json(source_map: &SourceMap, krate: &ast::Crate) -> String {
+    let mut root = Node::new("crate", None, krate.spans.inner_span);
+    root.fields.push(("version", PARSE_TREE_FORMAT_VERSION.to_string()));
+    let mut dumper = TreeDumper { open: vec![root] };
+    visit::walk_crate(&mut dumper, krate);
+    let mut out = String::new();
+    write_node(source_map, &dumper.open.pop().unwrap(), None, &mut out);
+    out.push('\n');
+    out
+}
+
+/// A node of the dump, see the module docs.
+struct Node {
+    node: &'static str,
+    kind: Option<&'static str>,
+    span: Span,
+    /// The fields of its own, with their values as JSON.
+    fields: Vec<(&'static str, String)>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(node: &'static str, kind: Option<&'static str>, span: Span) -> Node {
+        Node { node, kind, span, fields: Vec::new(), children: Vec::new() }
+    }
+}
+
+/// Builds the tree of nodes as it visits the AST.
+struct TreeDumper {
+    /// The nodes whose children are being visited, from the crate's in.
+    open: Vec<Node>,
+}
+
+impl TreeDumper {
+    /// Adds `node` to the node that is being visited, with the nodes that `walk` visits as its
+    /// children.
+    fn node(&mut self, node: Node, walk: impl FnOnce(&mut Self)) {
+        self.open.push(node);
+        walk(self);
+        let node = self.open.pop().unwrap();
+        self.open.last_mut().unwrap().children.push(node);
+    }
+
+    /// Adds a field to the node that is being visited.
+    fn field(&mut self, name: &'static str, value: String) {
+        self.open.last_mut().unwrap().fields.push((name, value));
+    }
+}
+
+impl<'ast> Visitor<'ast> for TreeDumper {
+    fn visit_item(&mut self, item: &'ast ast::Item) {
+        let node = Node::new("item", Some(item_kind(&item.kind)), item.span);
+        self.node(node, |this| visit::walk_item(this, item));
+    }
+
+    fn visit_foreign_item(&mut self, item: &'ast ast::ForeignItem) {
+        let node = Node::new("foreign_item", Some(foreign_item_kind(&item.kind)), item.span);
+        self.node(node, |this| visit::walk_item(this, item));
+    }
+
+    fn visit_assoc_item(&mut self, item: &'ast ast::AssocItem, ctxt: AssocCtxt) {
+        let node = Node::new("assoc_item", Some(assoc_item_kind(&item.kind)), item.span);
+        self.node(node, |this| visit::walk_assoc_item(this, item, ctxt));
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast ast::Stmt) {
+        let node = Node::new("stmt", Some(stmt_kind(&stmt.kind)), stmt.span);
+        self.node(node, |this| visit::walk_stmt(this, stmt));
+    }
+
+    fn visit_local(&mut self, local: &'ast ast::Local) {
+        self.node(Node::new("local", None, local.span), |this| visit::walk_local(this, local));
+    }
+
+    fn visit_block(&mut self, block: &'ast ast::Block) {
+        self.node(Node::new("block", None, block.span), |this| visit::walk_block(this, block));
+    }
+
+    fn visit_param(&mut self, param: &'ast ast::Param) {
+        self.node(Node::new("param", None, param.span), |this| visit::walk_param(this, param));
+    }
+
+    fn visit_arm(&mut self, arm: &'ast ast::Arm) {
+        self.node(Node::new("arm", None, arm.span), |this| visit::walk_arm(this, arm));
+    }
+
+    fn visit_pat(&mut self, pat: &'ast ast::Pat) {
+        let node = Node::new("pat", Some(pat_kind(&pat.kind)), pat.span);
+        self.node(node, |this| visit::walk_pat(this, pat));
+    }
+
+    fn visit_expr(&mut self, expr: &'ast ast::Expr) {
+        let node = Node::new("expr", Some(expr_kind(&expr.kind)), expr.span);
+        self.node(node, |this| {
+            match &expr.kind {
+                ast::ExprKind::Lit(lit) => this.field("text", json_string(&lit.to_string())),
+                ast::ExprKind::Binary(op, ..) | ast::ExprKind::AssignOp(op, ..) => {
+                    this.field("op", json_string(op.node.as_str()))
+                }
+                ast::ExprKind::Unary(op, _) => this.field("op", json_string(op.as_str())),
+                _ => {}
+            }
+            visit::walk_expr(this, expr)
+        });
+    }
+
+    fn visit_ty(&mut self, ty: &'ast ast::Ty) {
+        let node = Node::new("ty", Some(ty_kind(&ty.kind)), ty.span);
+        self.node(node, |this| visit::walk_ty(this, ty));
+    }
+
+    fn visit_generic_param(&mut self, param: &'ast ast::GenericParam) {
+        let node = Node::new("generic_param", None, param.span());
+        self.node(node, |this| visit::walk_generic_param(this, param));
+    }
+
+    fn visit_where_predicate(&mut self, pred: &'ast ast::WherePredicate) {
+        let node = Node::new("where_predicate", None, pred.span());
+        self.node(node, |this| visit::walk_where_predicate(this, pred));
+    }
+
+    fn visit_variant(&mut self, variant: &'ast ast::Variant) {
+        let node = Node::new("variant", None, variant.span);
+        self.node(node, |this| visit::walk_variant(this, variant));
+    }
+
+    fn visit_field_def(&mut self, field: &'ast ast::FieldDef) {
+        let node = Node::new("field_def", None, field.span);
+        self.node(node, |this| visit::walk_field_def(this, field));
+    }
+
+    fn visit_expr_field(&mut self, field: &'ast ast::ExprField) {
+        let node = Node::new("expr_field", None, field.span);
+        self.node(node, |this| visit::walk_expr_field(this, field));
+    }
+
+    fn visit_pat_field(&mut self, field: &'ast ast::PatField) {
+        let node = Node::new("pat_field", None, field.span);
+        self.node(node, |this| visit::walk_pat_field(this, field));
+    }
+
+    fn visit_path(&mut self, path: &'ast ast::Path, _id: ast::NodeId) {
+        self.node(Node::new("path", None, path.span), |this| visit::walk_path(this, path));
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast ast::Attribute) {
+        self.node(Node::new("attr", None, attr.span), |this| {
+            let style = match attr.style {
+                ast::AttrStyle::Outer => "outer",
+                ast::AttrStyle::Inner => "inner",
+            };
+            this.field("style", json_string(style));
+            match &attr.kind {
+                ast::AttrKind::Normal(normal) => {
+                    if let ast::AttrArgs::Delimited(args) = &normal.item.args {
+                        this.field("tokens", json_string(&pprust::tts_to_string(&args.tokens)));
+                    }
+                }
+                ast::AttrKind::DocComment(_, doc) => this.field("doc", json_string(doc.as_str())),
+            }
+            visit::walk_attribute(this, attr)
+        });
+    }
+
+    fn visit_mac_call(&mut self, mac: &'ast ast::MacCall) {
+        self.node(Node::new("mac_call", None, mac.span()), |this| {
+            this.field("tokens", json_string(&pprust::tts_to_string(&mac.args.tokens)));
+            visit::walk_mac(this, mac)
+        });
+    }
+
+    fn visit_mac_def(&mut self, mac: &'ast ast::MacroDef, _id: ast::NodeId) {
+        // The item that defines the macro is being visited.
+        self.field("macro_rules", mac.macro_rules.to_string());
+        self.field("tokens", json_string(&pprust::tts_to_string(&mac.body.tokens)));
+    }
+
+    fn visit_ident(&mut self, ident: Ident) {
+        // Items like `use` and `impl` don't have a name.
+        if ident.name != kw::Empty {
+            let mut node = Node::new("ident", None, ident.span);
+            node.fields.push(("name", json_string(ident.as_str())));
+            self.open.last_mut().unwrap().children.push(node);
+        }
+    }
+
+    fn visit_lifetime(&mut self, lifetime: &'ast ast::Lifetime, _: LifetimeCtxt) {
+        let mut node = Node::new("lifetime", None, lifetime.ident.span);
+        node.fields.push(("name", json_string(lifetime.ident.as_str())));
+        self.open.last_mut().unwrap().children.push(node);
+    }
+}
+
+/// Writes `node` to `out` as JSON. Its file is only written if it isn't `parent_file`.
+fn write_node(
+    source_map: &SourceMap,
+    node: &Node,
+    parent_file: Option<&FileName>,
+    out: &mut String,
+) {
+    write!(out, "{{\"node\":{}", json_string(node.node)).unwrap();
+    if let Some(kind) = node.kind {
+        write!(out, ",\"kind\":{}", json_string(kind)).unwrap();
+    }
+    let mut file = parent_file;
+    let loc = (!node.span.is_dummy()).then(|| source_map.lookup_byte_offset(node.span.lo()));
+    if let Some(loc) = &loc {
+        if parent_file != Some(&loc.sf.name) {
+            let name = loc.sf.name.prefer_local().to_string();
+            write!(out, ",\"file\":{}", json_string(&name)).unwrap();
+        }
+        let hi = node.span.hi() - loc.sf.start_pos;
+        write!(out, ",\"span\":[{},{}]", loc.pos.0, hi.0).unwrap();
+        file = Some(&loc.sf.name);
+    }
+    for (name, value) in &node.fields {
+        write!(out, ",{}:{value}", json_string(name)).unwrap();
+    }
+    if !node.children.is_empty() {
+        out.push_str(",\"children\":[");
+        for (i, child) in node.children.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_node(source_map, child, file, out);
+        }
+        out.push(']');
+    }
+    out.push('}');
+}
+
+/// `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn item_kind(kind: &ast::ItemKind) -> &'static str {
+    use ast::ItemKind::*;
+    match kind {
+        ExternCrate(..) => "ExternCrate",
+        Use(..) => "Use",
+        Static(..) => "Static",
+        Const(..) => "Const",
+        Fn(..) => "Fn",
+        Mod(..) => "Mod",
+        ForeignMod(..) => "ForeignMod",
+        GlobalAsm(..) => "GlobalAsm",
+        TyAlias(..) => "TyAlias",
+        Enum(..) => "Enum",
+        Struct(..) => "Struct",
+        Union(..) => "Union",
+        Trait(..) => "Trait",
+        TraitAlias(..) => "TraitAlias",
+        Impl(..) => "Impl",
+        MacCall(..) => "MacCall",
+        MacroDef(..) => "MacroDef",
+        Delegation(..) => "Delegation",
+        DelegationMac(..) => "DelegationMac",
+    }
+}
+
+fn assoc_item_kind(kind: &ast::AssocItemKind) -> &'static str {
+    use ast::AssocItemKind::*;
+    match kind {
+        Const(..) => "Const",
+        Fn(..) => "Fn",
+        Type(..) => "Type",
+        MacCall(..) => "MacCall",
+        Delegation(..) => "Delegation",
+        DelegationMac(..) => "DelegationMac",
+    }
+}
+
+fn foreign_item_kind(kind: &ast::ForeignItemKind) -> &'static str {
+    use ast::ForeignItemKind::*;
+    match kind {
+        Static(..) => "Static",
+        Fn(..) => "Fn",
+        TyAlias(..) => "TyAlias",
+        MacCall(..) => "MacCall",
+    }
+}
+
+fn stmt_kind(kind: &ast::StmtKind) -> &'static str {
+    use ast::StmtKind::*;
+    match kind {
+        Let(..) => "Let",
+        Item(..) => "Item",
+        Expr(..) => "Expr",
+        Semi(..) => "Semi",
+        Empty => "Empty",
+        MacCall(..) => "MacCall",
+    }
+}
+
+fn expr_kind(kind: &ast::ExprKind) -> &'static str {
+    use ast::ExprKind::*;
+    match kind {
+        Array(..) => "Array",
+        ConstBlock(..) => "ConstBlock",
+        Call(..) => "Call",
+        MethodCall(..) => "MethodCall",
+        Tup(..) => "Tup",
+        Binary(..) => "Binary",
+        Unary(..) => "Unary",
+        Lit(..) => "Lit",
+        Cast(..) => "Cast",
+        Type(..) => "Type",
+        Let(..) => "Let",
+        If(..) => "If",
+        While(..) => "While",
+        ForLoop { .. } => "ForLoop",
+        Loop(..) => "Loop",
+        Match(..) => "Match",
+        Closure(..) => "Closure",
+        Block(..) => "Block",
+        Gen(..) => "Gen",
+        Await(..) => "Await",
+        TryBlock(..) => "TryBlock",
+        Assign(..) => "Assign",
+        AssignOp(..) => "AssignOp",
+        Field(..) => "Field",
+        Index(..) => "Index",
+        Range(..) => "Range",
+        Underscore => "Underscore",
+        Path(..) => "Path",
+        AddrOf(..) => "AddrOf",
+        Break(..) => "Break",
+        Continue(..) => "Continue",
+        Ret(..) => "Ret",
+        InlineAsm(..) => "InlineAsm",
+        OffsetOf(..) => "OffsetOf",
+        MacCall(..) => "MacCall",
+        Struct(..) => "Struct",
+        Repeat(..) => "Repeat",
+        Paren(..) => "Paren",
+        Try(..) => "Try",
+        Yield(..) => "Yield",
+        Yeet(..) => "Yeet",
+        Become(..) => "Become",
+        IncludedBytes(..) => "IncludedBytes",
+        FormatArgs(..) => "FormatArgs",
+        Err(..) => "Err",
+        Dummy => "Dummy",
+    }
+}
+
+fn pat_kind(kind: &ast::PatKind) -> &'static str {
+    use ast::PatKind::*;
+    match kind {
+        Wild => "Wild",
+        Ident(..) => "Ident",
+        Struct(..) => "Struct",
+        TupleStruct(..) => "TupleStruct",
+        Or(..) => "Or",
+        Path(..) => "Path",
+        Tuple(..) => "Tuple",
+        Box(..) => "Box",
+        Deref(..) => "Deref",
+        Ref(..) => "Ref",
+        Lit(..) => "Lit",
+        Range(..) => "Range",
+        Slice(..) => "Slice",
+        Rest => "Rest",
+        Never => "Never",
+        Paren(..) => "Paren",
+        MacCall(..) => "MacCall",
+        Err(..) => "Err",
+    }
+}
+
+fn ty_kind(kind: &ast::TyKind) -> &'static str {
+    use ast::TyKind::*;
+    match kind {
+        Slice(..) => "Slice",
+        Array(..) => "Array",
+        Ptr(..) => "Ptr",
+        Ref(..) => "Ref",
+        BareFn(..) => "BareFn",
+        Never => "Never",
+        Tup(..) => "Tup",
+        AnonStruct(..) => "AnonStruct",
+        AnonUnion(..) => "AnonUnion",
+        Path(..) => "Path",
+        TraitObject(..) => "TraitObject",
+        ImplTrait(..) => "ImplTrait",
+        Paren(..) => "Paren",
+        Typeof(..) => "Typeof",
+        Infer => "Infer",
+        ImplicitSelf => "ImplicitSelf",
+        MacCall(..) => "MacCall",
+        CVarArgs => "CVarArgs",
+        Pat(..) => "Pat",
+        Dummy => "Dummy",
+        Err(..) => "Err",
+    }
+}
@@ -333,7 +333,10 @@ pub fn parse_inner_attributes(&mut self) -> PResult<'a, ast::AttrVec> {
                 if let Capturing::Yes = self.capture_state.capturing {
                     let end_pos = self.num_bump_calls;
                     let parser_range = ParserRange(start_pos..end_pos);
-                    self.capture_state.inner_attr_parser_ranges.insert(attr.id, parser_range);
+                    let end = self.collect_pos();
+                    self.capture_state_mut()
+                        .inner_attr_parser_ranges
+                        .insert(attr.id, (parser_range, end));
                 }
                 attrs.push(attr);
             } else {
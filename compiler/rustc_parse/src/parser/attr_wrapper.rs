@@ -18,7 +18,8 @@
 };
 
 // When collecting tokens, this fully captures the start point. Usually its
-// just after outer attributes, but occasionally it's before.
+// just after outer attributes, but occasionally it's before. It also captures
+// the end of a replaced range, where replaying goes on after the range.
 #[derive(Clone, Debug)]
 pub(super) struct CollectPos {
     start_token: (Token, Spacing),
@@ -84,6 +85,15 @@ pub(super) fn is_empty(&self) -> bool {
     }
 }
 
+impl CollectPos {
+    /// The position after the next token.
+    fn bumped(mut self) -> CollectPos {
+        self.start_token = self.cursor_snapshot.next();
+        self.start_pos += 1;
+        self
+    }
+}
+
 /// Returns `true` if `attrs` contains a `cfg` or `cfg_attr` attribute
 fn has_cfg_or_cfg_attr(attrs: &[Attribute]) -> bool {
     // NOTE: Builtin attributes like `cfg` and `cfg_attr` cannot be renamed via imports.
@@ -104,6 +114,12 @@ fn has_cfg_or_cfg_attr(attrs: &[Attribute]) -> bool {
 //
 // This also makes `Parser` very cheap to clone, since
 // there is no intermediate collection buffer to clone.
+//
+// The tokens of the nodes replaced by `node_replacements` aren't replayed:
+// their targets hold lazy token streams of their own, which share the same
+// token cursor buffer. So each token is replayed by at most one
+// `LazyAttrTokenStreamImpl`, the innermost one whose node holds it, rather
+// than once for every node around it.
 struct LazyAttrTokenStreamImpl {
     start_token: (Token, Spacing),
     cursor_snapshot: TokenCursor,
@@ -114,63 +130,83 @@ struct LazyAttrTokenStreamImpl {
 
 impl ToAttrTokenStream for LazyAttrTokenStreamImpl {
     fn to_attr_token_stream(&self) -> AttrTokenStream {
+        #[cfg(debug_assertions)]
+        for [(node_range, tokens, _), (next_node_range, next_tokens, _)] in
+            self.node_replacements.array_windows()
+        {
+            assert!(
+                node_range.0.end <= next_node_range.0.start,
+                "Node ranges should be sorted and disjoint: ({:?}, {:?}) ({:?}, {:?})",
+                node_range,
+                tokens,
+                next_node_range,
+                next_tokens,
+            );
+        }
+
         // The token produced by the final call to `{,inlined_}next` was not
-        // actually consumed by the callback. The combination of chaining the
-        // initial token and using `take` produces the desired result - we
-        // produce an empty `TokenStream` if no calls were made, and omit the
-        // final token otherwise.
+        // actually consumed by the callback, so we produce `num_calls` tokens,
+        // starting with the initial token. We produce an empty `TokenStream`
+        // if no calls were made.
+        //
+        // The tokens in the range of a node replacement are replaced with
+        // zero or one `FlatToken::AttrsTarget`s, and then we go on from the
+        // position at the end of the range, without replaying the tokens in
+        // it.
+        let mut pos = 0;
+        let mut token = self.start_token.clone();
         let mut cursor_snapshot = self.cursor_snapshot.clone();
-        let tokens = iter::once(FlatToken::Token(self.start_token.clone()))
-            .chain(iter::repeat_with(|| FlatToken::Token(cursor_snapshot.next())))
-            .take(self.num_calls as usize);
-
-        if self.node_replacements.is_empty() {
-            make_attr_token_stream(tokens, self.break_last_token)
-        } else {
-            let mut tokens: Vec<_> = tokens.collect();
-            let mut node_replacements = self.node_replacements.to_vec();
-            node_replacements.sort_by_key(|(range, _)| range.0.start);
-
-            #[cfg(debug_assertions)]
-            for [(node_range, tokens), (next_node_range, next_tokens)] in
-                node_replacements.array_windows()
-            {
-                assert!(
-                    node_range.0.end <= next_node_range.0.start,
-                    "Node ranges should be disjoint: ({:?}, {:?}) ({:?}, {:?})",
-                    node_range,
-                    tokens,
-                    next_node_range,
-                    next_tokens,
-                );
+        let mut node_replacements = self.node_replacements.iter().peekable();
+        let tokens = iter::from_fn(|| loop {
+            if pos == self.num_calls {
+                return None;
             }
-
-            // Process the replace ranges.
-            for (node_range, target) in node_replacements.into_iter() {
-                assert!(
-                    !node_range.0.is_empty(),
-                    "Cannot replace an empty node range: {:?}",
-                    node_range.0
-                );
-
-                // Replace the tokens in range with zero or one `FlatToken::AttrsTarget`s, plus
-                // enough `FlatToken::Empty`s to fill up the rest of the range. This keeps the
-                // total length of `tokens` constant throughout the replacement process, allowing
-                // us to do all replacements without adjusting indices.
-                let target_len = target.is_some() as usize;
-                tokens.splice(
-                    (node_range.0.start as usize)..(node_range.0.end as usize),
-                    target.into_iter().map(|target| FlatToken::AttrsTarget(target)).chain(
-                        iter::repeat(FlatToken::Empty).take(node_range.0.len() - target_len),
-                    ),
-                );
+            match node_replacements.next_if(|(node_range, ..)| node_range.0.start == pos) {
+                Some((node_range, target, end)) => {
+                    assert!(
+                        !node_range.0.is_empty(),
+                        "Cannot replace an empty node range: {:?}",
+                        node_range.0
+                    );
+                    pos = node_range.0.end;
+                    token = end.start_token.clone();
+                    cursor_snapshot = end.cursor_snapshot.clone();
+                    if let Some(target) = target {
+                        return Some(FlatToken::AttrsTarget(target.clone()));
+                    }
+                }
+                None => {
+                    pos += 1;
+                    let next = cursor_snapshot.next();
+                    return Some(FlatToken::Token(mem::replace(&mut token, next)));
+                }
             }
-            make_attr_token_stream(tokens.into_iter(), self.break_last_token)
-        }
+        });
+        make_attr_token_stream(tokens, self.break_last_token)
     }
 }
 
 impl<'a> Parser<'a> {
+    /// Whether the driver keeps the nodes that cfg-stripping removes, see
+    /// `ParseSess::enable_cfg_stripped_tokens`, and `attrs` may remove theirs.
+    fn keeps_cfg_stripped_tokens(&self, attrs: &[Attribute]) -> bool {
+        self.psess.cfg_stripped_nodes.is_some() && has_cfg_or_cfg_attr(attrs)
+    }
+
+    /// Whether the driver never expands proc macros, see `ParseSess::skip_token_collection`, and
+    /// nothing else observes the tokens of a node with `attrs`. Builtin `#[derive]` and
+    /// `#[cfg_eval]` still go through cfg_eval, which turns the node back into tokens, as does
+    /// the reparse it does in "possible capture mode".
+    fn skips_token_collection(&self, attrs: &[Attribute]) -> bool {
+        self.psess.skip_token_collection
+            && !self.capture_cfg
+            && !has_cfg_or_cfg_attr(attrs)
+            && !attrs.iter().any(|attr| {
+                attr.ident()
+                    .is_some_and(|ident| ident.name == sym::derive || ident.name == sym::cfg_eval)
+            })
+    }
+
     pub(super) fn collect_pos(&self) -> CollectPos {
         CollectPos {
             start_token: (self.token.clone(), self.token_spacing),
@@ -223,6 +259,12 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
         force_collect: ForceCollect,
         f: impl FnOnce(&mut Self, AttrVec) -> PResult<'a, (R, Trailing, UsePreAttrPos)>,
     ) -> PResult<'a, R> {
+        // Nothing observes the tokens if the driver never expands proc macros, unless we are
+        // force collecting them, e.g. for a macro fragment that is turned back into tokens.
+        if matches!(force_collect, ForceCollect::No) && self.skips_token_collection(&attrs.attrs) {
+            return Ok(f(self, attrs.attrs)?.0);
+        }
+
         let possible_capture_mode = self.capture_cfg;
 
         // We must collect if anything could observe the collected tokens, i.e.
@@ -237,7 +279,10 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
             || R::SUPPORTS_CUSTOM_INNER_ATTRS
             // - We are in "possible capture mode" (which requires tokens if
             //   the parsed node has `#[cfg]` or `#[cfg_attr]` attributes).
-            || possible_capture_mode;
+            || possible_capture_mode
+            // - The driver keeps the nodes that cfg-stripping removes, and
+            //   our outer attributes may remove this one.
+            || self.keeps_cfg_stripped_tokens(&attrs.attrs);
         if !needs_collection {
             return Ok(f(self, attrs.attrs)?.0);
         }
@@ -252,9 +297,10 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
         // recursive) calls (e.g. parsing `g` in the example above). This
         // distinction is used below and in `Parser::parse_inner_attributes`.
         let (mut ret, capture_trailing, use_pre_attr_pos) = {
-            let prev_capturing = mem::replace(&mut self.capture_state.capturing, Capturing::Yes);
+            let prev_capturing =
+                mem::replace(&mut self.capture_state_mut().capturing, Capturing::Yes);
             let res = f(self, attrs.attrs);
-            self.capture_state.capturing = prev_capturing;
+            self.capture_state_mut().capturing = prev_capturing;
             res?
         };
 
@@ -270,7 +316,7 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
         // wrapping layer.
         let mut seen_indices = FxHashSet::default();
         for (i, attr) in ret.attrs().iter().enumerate() {
-            let is_unseen = self.capture_state.seen_attrs.insert(attr.id);
+            let is_unseen = self.capture_state_mut().seen_attrs.insert(attr.id);
             if !is_unseen {
                 seen_indices.insert(i);
             }
@@ -323,7 +369,10 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
             //   are `#[cfg]` or `#[cfg_attr]` attributes. (During normal
             //   non-`capture_cfg` parsing, we don't need any special capturing
             //   for those attributes, because they're builtin.)
-            || definite_capture_mode;
+            || definite_capture_mode
+            // - The driver keeps the nodes that cfg-stripping removes, and
+            //   our outer or inner attributes may remove this one.
+            || self.keeps_cfg_stripped_tokens(&ret_attrs);
         if !needs_collection {
             return Ok(ret);
         }
@@ -359,10 +408,10 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
         let mut inner_attr_parser_replacements = Vec::new();
         for attr in ret_attrs.iter() {
             if attr.style == ast::AttrStyle::Inner {
-                if let Some(inner_attr_parser_range) =
-                    self.capture_state.inner_attr_parser_ranges.remove(&attr.id)
+                if let Some((inner_attr_parser_range, end)) =
+                    self.capture_state_mut().inner_attr_parser_ranges.remove(&attr.id)
                 {
-                    inner_attr_parser_replacements.push((inner_attr_parser_range, None));
+                    inner_attr_parser_replacements.push((inner_attr_parser_range, None, end));
                 } else {
                     self.dcx().span_delayed_bug(attr.span, "Missing token range for attribute");
                 }
@@ -377,17 +426,20 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
             Box::new([])
         } else {
             // Grab any replace ranges that occur *inside* the current AST node. Convert them
-            // from `ParserRange` form to `NodeRange` form. We will perform the actual
-            // replacement only when we convert the `LazyAttrTokenStream` to an
+            // from `ParserRange` form to `NodeRange` form, sorted by position. We will perform
+            // the actual replacement only when we convert the `LazyAttrTokenStream` to an
             // `AttrTokenStream`.
-            self.capture_state
+            let mut node_replacements: Vec<_> = self
+                .capture_state_mut()
                 .parser_replacements
                 .drain(parser_replacements_start..parser_replacements_end)
                 .chain(inner_attr_parser_replacements)
-                .map(|(parser_range, data)| {
-                    (NodeRange::new(parser_range, collect_pos.start_pos), data)
+                .map(|(parser_range, data, end)| {
+                    (NodeRange::new(parser_range, collect_pos.start_pos), data, end)
                 })
-                .collect()
+                .collect();
+            node_replacements.sort_by_key(|(node_range, ..)| node_range.0.start);
+            node_replacements.into_boxed_slice()
         };
 
         // What is the status here when parsing the example code at the top of this method?
@@ -444,15 +496,19 @@ pub(super) fn collect_tokens<R: HasAttrs + HasTokens>(
             let target =
                 AttrsTarget { attrs: ret_attrs.iter().cloned().collect(), tokens: tokens.clone() };
             tokens_used = true;
-            self.capture_state
-                .parser_replacements
-                .push((ParserRange(start_pos..end_pos), Some(target)));
+            let mut end = self.collect_pos();
+            if matches!(capture_trailing, Trailing::Yes) {
+                end = end.bumped();
+            }
+            let parser_range = ParserRange(start_pos..end_pos);
+            self.capture_state_mut().parser_replacements.push((parser_range, Some(target), end));
         } else if matches!(self.capture_state.capturing, Capturing::No) {
             // Only clear the ranges once we've finished capturing entirely, i.e. we've finished
             // the outermost call to this method.
-            self.capture_state.parser_replacements.clear();
-            self.capture_state.inner_attr_parser_ranges.clear();
-            self.capture_state.seen_attrs.clear();
+            let capture_state = self.capture_state_mut();
+            capture_state.parser_replacements.clear();
+            capture_state.inner_attr_parser_ranges.clear();
+            capture_state.seen_attrs.clear();
         }
 
         // If we support tokens and don't already have them, store the newly captured tokens.
@@ -509,7 +565,6 @@ struct FrameData {
             FlatToken::AttrsTarget(target) => {
                 stack_top.inner.push(AttrTokenTree::AttrsTarget(target))
             }
-            FlatToken::Empty => {}
         }
     }
 
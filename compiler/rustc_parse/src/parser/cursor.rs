@@ -0,0 +1,68 @@
+//! The supported way for other crates to drive a [`Parser`] themselves, e.g. to build their own
+//! recovery on top of it.
+//!
+//! Besides what is here, that is:
+//! - the `token` and `prev_token` fields, for the current and the previous token;
+//! - [`Parser::look_ahead`], for the tokens after the current one;
+//! - [`Parser::bump`], [`Parser::eat`] and [`Parser::eat_keyword`], for moving past tokens, and
+//!   [`Parser::parse_token_tree`] for moving past a whole delimited group.
+//!
+//! `eat` and `eat_keyword` also add to the tokens that an "expected one of ..." error lists, as
+//! when the parser itself checks for a token. Use `token` or `look_ahead` to look at a token
+//! without that.
+//!
+//! # Diagnostics and rolling back
+//!
+//! Rolling back to a [`Checkpoint`] restores the parser's position, and what it keeps track of
+//! for its errors, but it can't take back errors that were emitted in the meantime. The parser
+//! emits an error itself when it recovers from broken code, and then carries on, so parsing
+//! ahead of a checkpoint can emit errors that the caller doesn't want if it rolls back.
+//! [`Parser::speculate`] takes care of that as far as possible.
+
+use rustc_errors::PResult;
+
+use super::{Parser, Recovery};
+
+/// A position of a [`Parser`] to roll back to, see [`Parser::checkpoint`].
+#[derive(Clone)]
+pub struct Checkpoint<'a> {
+    parser: Parser<'a>,
+}
+
+impl<'a> Parser<'a> {
+    /// Returns the parser's current position, to roll back to with [`Parser::rollback`]. The
+    /// checkpoint is a copy of the parser, so that is cheap, but not free.
+    pub fn checkpoint(&self) -> Checkpoint<'a> {
+        Checkpoint { parser: self.clone() }
+    }
+
+    /// Rolls back to `checkpoint`, which must have been taken from this parser. Errors emitted
+    /// since then stay emitted, see the [module docs](self).
+    pub fn rollback(&mut self, checkpoint: Checkpoint<'a>) {
+        *self = checkpoint.parser;
+    }
+
+    /// Runs `f`, and keeps what it parsed if it succeeds. If it fails, the parser rolls back to
+    /// where it was, the error is cancelled, and this returns `None`.
+    ///
+    /// Recovery is forbidden while `f` runs, so that it fails rather than emits an error and
+    /// carries on. The few errors that the parser emits without recovering, e.g. for a misplaced
+    /// `pub`, are still emitted, whether `f` fails or not.
+    pub fn speculate<T>(&mut self, f: impl FnOnce(&mut Self) -> PResult<'a, T>) -> Option<T> {
+        let checkpoint = self.checkpoint();
+        let recovery = self.recovery;
+        if let Recovery::Allowed = recovery {
+            self.recovery = Recovery::Forbidden;
+        }
+        let result = f(self);
+        self.recovery = recovery;
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                err.cancel();
+                self.rollback(checkpoint);
+                None
+            }
+        }
+    }
+}
@@ -28,7 +28,8 @@
 
 use super::pat::Expected;
 use super::{
-    BlockMode, CommaRecoveryMode, Parser, PathStyle, Restrictions, SemiColonMode, SeqSep, TokenType,
+    BlockMode, CommaRecoveryMode, Parser, PathStyle, RecoveryKind, Restrictions, SemiColonMode,
+    SeqSep, TokenType,
 };
 use crate::errors::{
     AddParen, AmbiguousPlus, AsyncMoveBlockIn2015, AttributeOnParamType, AwaitSuggestion,
@@ -482,7 +483,8 @@ fn tokens_to_string(tokens: &[TokenType]) -> String {
             })
         }
 
-        self.expected_tokens.extend(edible.iter().chain(inedible).cloned().map(TokenType::Token));
+        self.expected_tokens_mut()
+            .extend(edible.iter().chain(inedible).cloned().map(TokenType::Token));
         let mut expected = self
             .expected_tokens
             .iter()
@@ -565,6 +567,11 @@ fn is_ident_eq_keyword(found: &TokenKind, expected: &TokenType) -> bool {
                     unexpected_token_label: None,
                     sugg: ExpectedSemiSugg::ChangeToSemi(self.token.span),
                 });
+                let found = self.token.kind.clone();
+                self.record_recovery(
+                    RecoveryKind::ReplacedToken { found, expected: token::Semi },
+                    self.token.span,
+                );
                 self.bump();
                 return Ok(guar);
             } else if self.look_ahead(0, |t| {
@@ -590,6 +597,7 @@ fn is_ident_eq_keyword(found: &TokenKind, expected: &TokenType) -> bool {
                     unexpected_token_label: Some(self.token.span),
                     sugg: ExpectedSemiSugg::AddSemi(span),
                 });
+                self.record_recovery(RecoveryKind::InsertedToken(token::Semi), span);
                 return Ok(guar);
             }
         }
@@ -1123,11 +1131,13 @@ pub(super) fn recover_closure_body(
     /// Eats and discards tokens until one of `kets` is encountered. Respects token trees,
     /// passes through any errors encountered. Used for error recovery.
     pub(super) fn eat_to_tokens(&mut self, kets: &[&TokenKind]) {
+        let (start, lo) = (self.num_bump_calls, self.token.span);
         if let Err(err) =
             self.parse_seq_to_before_tokens(kets, &[], SeqSep::none(), |p| Ok(p.parse_token_tree()))
         {
             err.cancel();
         }
+        self.record_skipped_tokens(start, lo);
     }
 
     /// This function checks if there are trailing angle brackets and produces
@@ -1976,6 +1986,10 @@ pub(super) fn recover_colon_as_semi(&mut self) -> bool {
                 span: self.token.span,
                 type_ascription: self.psess.unstable_features.is_nightly_build(),
             });
+            self.record_recovery(
+                RecoveryKind::ReplacedToken { found: token::Colon, expected: token::Semi },
+                self.token.span,
+            );
             self.bump();
             return true;
         }
@@ -2158,6 +2172,7 @@ pub(super) fn recover_stmt_(
         break_on_semi: SemiColonMode,
         break_on_block: BlockMode,
     ) {
+        let (start, lo) = (self.num_bump_calls, self.token.span);
         let mut brace_depth = 0;
         let mut bracket_depth = 0;
         let mut in_block = false;
@@ -2220,6 +2235,7 @@ pub(super) fn recover_stmt_(
                 _ => self.bump(),
             }
         }
+        self.record_skipped_tokens(start, lo);
     }
 
     pub(super) fn check_for_for_in_in_typo(&mut self, in_span: Span) {
@@ -2383,6 +2399,7 @@ pub(super) fn recover_bad_self_param(&mut self, mut param: Param) -> PResult<'a,
     }
 
     pub(super) fn consume_block(&mut self, delim: Delimiter, consume_close: ConsumeClosingDelim) {
+        let (start, lo) = (self.num_bump_calls, self.token.span);
         let mut brace_depth = 0;
         loop {
             if self.eat(&token::OpenDelim(delim)) {
@@ -2395,6 +2412,7 @@ pub(super) fn consume_block(&mut self, delim: Delimiter, consume_close: ConsumeC
                         // the parser.
                         self.bump();
                     }
+                    self.record_skipped_tokens(start, lo);
                     return;
                 } else {
                     self.bump();
@@ -2402,6 +2420,7 @@ pub(super) fn consume_block(&mut self, delim: Delimiter, consume_close: ConsumeC
                     continue;
                 }
             } else if self.token == token::Eof {
+                self.record_skipped_tokens(start, lo);
                 return;
             } else {
                 self.bump();
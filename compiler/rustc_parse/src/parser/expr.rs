@@ -35,8 +35,8 @@
 use super::pat::{CommaRecoveryMode, Expected, RecoverColon, RecoverComma};
 use super::ty::{AllowPlus, RecoverQPath, RecoverReturnSign};
 use super::{
-    AttrWrapper, BlockMode, ClosureSpans, ForceCollect, Parser, PathStyle, Restrictions,
-    SemiColonMode, SeqSep, TokenType, Trailing, UsePreAttrPos,
+    AttrWrapper, BlockMode, ClosureSpans, ForceCollect, Parser, PathStyle, RecoveryKind,
+    Restrictions, SemiColonMode, SeqSep, TokenType, Trailing, UsePreAttrPos,
 };
 use crate::{errors, maybe_recover_from_interpolated_ty_qpath};
 
@@ -153,7 +153,7 @@ pub(super) fn parse_expr_assoc_rest_with(
             return Ok((lhs, parsed_something));
         }
 
-        self.expected_tokens.push(TokenType::Operator);
+        self.expected_tokens_mut().push(TokenType::Operator);
         while let Some(op) = self.check_assoc_op() {
             let lhs_span = self.interpolated_or_expr_span(&lhs);
             let cur_op_span = self.token.span;
@@ -531,6 +531,10 @@ fn parse_expr_prefix_range(&mut self, attrs: AttrWrapper) -> PResult<'a, P<Expr>
 
     /// Parses a prefix-unary-operator expr.
     fn parse_expr_prefix(&mut self, attrs: AttrWrapper) -> PResult<'a, P<Expr>> {
+        self.nested("expression", |this| this.parse_expr_prefix_(attrs))
+    }
+
+    fn parse_expr_prefix_(&mut self, attrs: AttrWrapper) -> PResult<'a, P<Expr>> {
         let lo = self.token.span;
 
         macro_rules! make_it {
@@ -1278,6 +1282,13 @@ fn maybe_recover_struct_lit_bad_delims(
                         self.restore_snapshot(snapshot);
                         let close_paren = self.prev_token.span;
                         let span = lo.to(close_paren);
+                        self.record_recovery(
+                            RecoveryKind::ReplacedDelimiters {
+                                found: Delimiter::Parenthesis,
+                                expected: Delimiter::Brace,
+                            },
+                            open_paren.to(close_paren),
+                        );
                         // filter shorthand fields
                         let fields: Vec<_> =
                             fields.into_iter().filter(|field| !field.is_shorthand).collect();
@@ -1623,7 +1634,9 @@ fn parse_expr_path_start(&mut self) -> PResult<'a, P<Expr>> {
             }
             let lo = path.span;
             let mac = P(MacCall { path, args: self.parse_delim_args()? });
-            (lo.to(self.prev_token.span), ExprKind::MacCall(mac))
+            let span = lo.to(self.prev_token.span);
+            self.observe(|observer| observer.mac_call(&mac, span));
+            (span, ExprKind::MacCall(mac))
         } else if self.check(&token::OpenDelim(Delimiter::Brace))
             && let Some(expr) = self.maybe_parse_struct_expr(&qself, &path)
         {
@@ -2380,7 +2393,7 @@ fn parse_expr_closure(&mut self) -> PResult<'a, P<Expr>> {
         }
 
         if self.token == TokenKind::Semi
-            && matches!(self.token_cursor.stack.last(), Some((.., Delimiter::Parenthesis)))
+            && matches!(self.token_cursor.innermost(), Some((_, _, Delimiter::Parenthesis, _)))
             && self.may_recover()
         {
             // It is likely that the closure body is a block but where the
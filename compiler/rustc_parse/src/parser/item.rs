@@ -11,6 +11,7 @@
 use rustc_ast_pretty::pprust;
 use rustc_errors::codes::*;
 use rustc_errors::{struct_span_code_err, Applicability, PResult, StashKey};
+use rustc_session::parse::ParseSess;
 use rustc_span::edit_distance::edit_distance;
 use rustc_span::edition::Edition;
 use rustc_span::symbol::{kw, sym, Ident, Symbol};
@@ -33,6 +34,90 @@ pub fn parse_crate_mod(&mut self) -> PResult<'a, ast::Crate> {
         Ok(ast::Crate { attrs, items, spans, id: DUMMY_NODE_ID, is_placeholder: false })
     }
 
+    /// Like `parse_crate_mod`, but always produces a crate, for tools like IDEs that want the
+    /// rest of the tree when some of it doesn't parse. Errors are emitted as they are found.
+    ///
+    /// Within the items that parse, recovery leaves `ExprKind::Err`, `TyKind::Err` and
+    /// `PatKind::Err` nodes where it had to give up. Items have no such node, so an item that
+    /// can't be parsed is left out, and parsing resumes at the next token at the top level of the
+    /// crate that can start an item.
+    ///
+    /// If the parse is cancelled, or hits `ParserOptions::max_errors`, the crate ends with the
+    /// items parsed until then. [`Parser::is_cancelled`] and [`Parser::error_limit_reached`] tell
+    /// whether that happened.
+    pub fn parse_crate_mod_recovering(&mut self) -> ast::Crate {
+        let lo = self.token.span;
+        let attrs = self.parse_inner_attributes().unwrap_or_else(|err| {
+            err.emit();
+            AttrVec::new()
+        });
+
+        let post_attr_lo = self.token.span;
+        let mut items: ThinVec<P<_>> = ThinVec::new();
+
+        loop {
+            while self.maybe_consume_incorrect_semicolon(items.last().map(|x| &**x)) {}
+            let start = self.num_bump_calls;
+            match self.parse_item(ForceCollect::No) {
+                Ok(Some(item)) => {
+                    items.push(item);
+                    continue;
+                }
+                Ok(None) if self.token == token::Eof => break,
+                Ok(None) => {
+                    let token_str = super::token_descr(&self.token);
+                    let msg = format!("expected item, found {token_str}");
+                    self.dcx()
+                        .struct_span_err(self.token.span, msg)
+                        .with_span_label(self.token.span, "expected item")
+                        .emit();
+                }
+                Err(err) => {
+                    err.emit();
+                }
+            }
+            if self.is_stopped() {
+                break;
+            }
+            self.recover_to_next_crate_item(start);
+        }
+
+        let inject_use_span = post_attr_lo.data().with_hi(post_attr_lo.lo());
+        let spans = ModSpans { inner_span: lo.to(self.prev_token.span), inject_use_span };
+        ast::Crate { attrs, items, spans, id: DUMMY_NODE_ID, is_placeholder: false }
+    }
+
+    /// Skips tokens after a failed item until the next one at the top level of the crate that
+    /// can start an item, always making progress past `start`, where the failed item began.
+    fn recover_to_next_crate_item(&mut self, start: u32) {
+        let (skip_start, lo) = (self.num_bump_calls, self.token.span);
+        loop {
+            // The crate's own tokens are the outermost stream, so an empty stack means that we
+            // are at its top level, rather than within delimiters in the failed item.
+            let at_top_level = self.token_cursor.stack.is_empty();
+            if self.token == token::Eof
+                || (at_top_level
+                    && self.num_bump_calls > start
+                    && (self.token.can_begin_item() || self.token == token::Pound))
+            {
+                break;
+            }
+            self.bump();
+        }
+        self.record_skipped_tokens(skip_start, lo);
+    }
+
+    /// Like `parse_crate_mod`, but skips the bodies of functions, for drivers that only need
+    /// their signatures, or want to parse the bodies later, e.g. in parallel. Each function gets
+    /// an empty block for a body, and the body's tokens are returned as a `LazyFnBody` with the
+    /// same span, see `LazyFnBody::parse`.
+    pub fn parse_crate_mod_lazy_bodies(&mut self) -> PResult<'a, (ast::Crate, Vec<LazyFnBody>)> {
+        let outer = self.lazy_fn_bodies.replace(Vec::new());
+        let krate = self.parse_crate_mod();
+        let bodies = mem::replace(&mut self.lazy_fn_bodies, outer).unwrap_or_default();
+        Ok((krate?, bodies))
+    }
+
     /// Parses a `mod <foo> { ... }` or `mod <foo>;` item.
     fn parse_item_mod(&mut self, attrs: &mut AttrVec) -> PResult<'a, ItemInfo> {
         let safety = self.parse_safety(Case::Sensitive);
@@ -101,6 +186,22 @@ pub fn parse_mod(
 
 pub(super) type ItemInfo = (Ident, ItemKind);
 
+/// The tokens of a function body that `Parser::parse_crate_mod_lazy_bodies` skipped.
+#[derive(Clone, Debug)]
+pub struct LazyFnBody {
+    /// The span of the body, from `{` to `}`, which the function's placeholder block has too.
+    pub span: Span,
+    tokens: TokenStream,
+}
+
+impl LazyFnBody {
+    /// Parses the body, to replace the function's placeholder block with. Functions in the body
+    /// are parsed with it.
+    pub fn parse<'a>(&self, psess: &'a ParseSess) -> PResult<'a, P<Block>> {
+        Parser::new(psess, self.tokens.clone(), None).parse_block()
+    }
+}
+
 impl<'a> Parser<'a> {
     pub fn parse_item(&mut self, force_collect: ForceCollect) -> PResult<'a, Option<P<Item>>> {
         let fn_parse_mode = FnParseMode { req_name: |_| true, req_body: true };
@@ -130,6 +231,7 @@ pub(super) fn parse_item_common(
             attrs.prepend_to_nt_inner(&mut item.attrs);
             Some(item.into_inner())
         });
+        self.check_stopped()?;
 
         self.collect_tokens(None, attrs, force_collect, |this, mut attrs| {
             let lo = this.token.span;
@@ -149,6 +251,8 @@ pub(super) fn parse_item_common(
                 let span = lo.to(this.prev_token.span);
                 let id = DUMMY_NODE_ID;
                 let item = Item { ident, attrs, id, kind, vis, span, tokens: None };
+                this.check_strict()?;
+                this.observe(|observer| observer.item(&item));
                 return Ok((Some(item), Trailing::No, UsePreAttrPos::No));
             }
 
@@ -164,6 +268,7 @@ pub(super) fn parse_item_common(
             if !attrs_allowed {
                 this.recover_attrs_no_item(&attrs)?;
             }
+            this.check_strict()?;
             Ok((None, Trailing::No, UsePreAttrPos::No))
         })
     }
@@ -469,7 +574,10 @@ fn parse_item_macro(&mut self, vis: &Visibility) -> PResult<'a, MacCall> {
             Ok(args) => {
                 self.eat_semi_for_macro_if_needed(&args);
                 self.complain_if_pub_macro(vis, false);
-                Ok(MacCall { path, args })
+                let span = path.span.to(self.prev_token.span);
+                let mac = MacCall { path, args };
+                self.observe(|observer| observer.mac_call(&mac, span));
+                Ok(mac)
             }
 
             Err(mut err) => {
@@ -820,6 +928,7 @@ fn parse_item_list<T>(
                     break;
                 }
                 Ok(Some(item)) => items.extend(item),
+                Err(err) if self.is_stopped() => return Err(err),
                 Err(err) => {
                     self.consume_block(Delimiter::Brace, ConsumeClosingDelim::Yes);
                     err.with_span_label(
@@ -2457,6 +2566,20 @@ fn parse_fn_body(
             self.expect_semi()?;
             *sig_hi = self.prev_token.span;
             (AttrVec::new(), None)
+        } else if self.lazy_fn_bodies.is_some()
+            && self.check(&token::OpenDelim(Delimiter::Brace))
+            && !self.look_ahead(1, |t| {
+                *t == token::Pound || matches!(t.kind, token::DocComment(_, AttrStyle::Inner, _))
+            })
+        {
+            // Skip the body, leaving an empty block in its place. Bodies that start with an
+            // attribute or an inner doc comment are parsed right away, in case it is an inner
+            // attribute of the function.
+            let lo = self.token.span;
+            let tokens = TokenStream::new(vec![self.parse_token_tree()]);
+            let span = lo.to(self.prev_token.span);
+            self.lazy_fn_bodies.as_mut().unwrap().push(LazyFnBody { span, tokens });
+            (AttrVec::new(), Some(self.mk_block(ThinVec::new(), BlockCheckMode::Default, span)))
         } else if self.check(&token::OpenDelim(Delimiter::Brace)) || self.token.is_whole_block() {
             self.parse_block_common(self.token.span, BlockCheckMode::Default, false)
                 .map(|(attrs, body)| (attrs, Some(body)))?
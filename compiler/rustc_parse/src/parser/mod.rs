@@ -1,10 +1,12 @@
 pub mod attr;
 mod attr_wrapper;
+pub mod cursor;
 mod diagnostics;
 mod expr;
 mod generics;
 mod item;
 mod nonterminal;
+mod outline;
 mod pat;
 mod path;
 mod stmt;
@@ -12,18 +14,22 @@
 
 use std::assert_matches::debug_assert_matches;
 use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::{fmt, mem, slice};
 
-use attr_wrapper::{AttrWrapper, UsePreAttrPos};
+use attr_wrapper::{AttrWrapper, CollectPos, UsePreAttrPos};
 pub use diagnostics::AttemptLocalParseRecovery;
 pub(crate) use expr::ForbiddenLetReason;
 pub(crate) use item::FnParseMode;
+pub use item::LazyFnBody;
+pub use outline::{outline_crate, OutlineItem, OutlineItemKind};
 pub use pat::{CommaRecoveryMode, RecoverColon, RecoverComma};
 use path::PathStyle;
 use rustc_ast::ptr::P;
 use rustc_ast::token::{self, Delimiter, IdentIsRaw, Nonterminal, Token, TokenKind};
 use rustc_ast::tokenstream::{
-    AttrsTarget, DelimSpacing, DelimSpan, Spacing, TokenStream, TokenTree, TokenTreeCursor,
+    AttrsTarget, DelimSpacing, DelimSpan, Spacing, TokenStream, TokenTree,
 };
 use rustc_ast::util::case::Case;
 use rustc_ast::{
@@ -33,6 +39,7 @@
 };
 use rustc_ast_pretty::pprust;
 use rustc_data_structures::fx::FxHashMap;
+use rustc_data_structures::stack::ensure_sufficient_stack;
 use rustc_data_structures::sync::Lrc;
 use rustc_errors::{Applicability, Diag, FatalError, MultiSpan, PResult};
 use rustc_index::interval::IntervalSet;
@@ -128,7 +135,105 @@ macro_rules! maybe_recover_from_interpolated_ty_qpath {
 #[derive(Clone, Copy, Debug)]
 pub enum Recovery {
     Allowed,
+    /// No recovery that looks ahead to guess what was meant, see `Parser::may_recover`. The
+    /// parser still carries on after errors it can report without guessing.
     Forbidden,
+    /// Like `Forbidden`, but the first error also ends the parse, for consumers that want it to
+    /// be final, like grammar tests. An error the parser fails with is returned right away.
+    /// Errors it can report without failing, e.g. a misplaced `pub`, are emitted, and the
+    /// expression, type, pattern, statement or item that they are in then fails too, with an
+    /// error that is only a delayed bug, as the emitted one is the one to report.
+    Strict,
+}
+
+/// What the parser did to recover from broken code, see [`RecoveryEvent`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecoveryKind {
+    /// A missing token was assumed, e.g. a `;` at the end of a statement.
+    InsertedToken(TokenKind),
+    /// A token was taken to be another one, e.g. a `,` or `:` at the end of a statement to be a
+    /// `;`.
+    ReplacedToken { found: TokenKind, expected: TokenKind },
+    /// Tokens were skipped, e.g. the rest of a statement or block that didn't parse.
+    SkippedTokens,
+    /// A delimited group was taken to have other delimiters, e.g. a struct literal written with
+    /// parentheses to have braces.
+    ReplacedDelimiters { found: Delimiter, expected: Delimiter },
+}
+
+/// A place where the parser recovered from broken code, so that the AST there doesn't reflect
+/// the source faithfully. Recorded if `ParserOptions::record_recovery` is set, see
+/// [`Parser::take_recovery_events`].
+///
+/// Only the common ways of recovering are recorded. Others are only visible through the errors
+/// that come with them, as is a broken file that can't be lexed in the first place, e.g. for
+/// unbalanced delimiters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveryEvent {
+    pub kind: RecoveryKind,
+    /// Where the token was assumed, or the tokens that were skipped or reinterpreted.
+    pub span: Span,
+}
+
+/// Gets told about what a [`Parser`] parses as it goes, e.g. to report progress or index a file
+/// without walking the AST afterwards, see `ParserOptions::observer`. To stop the parse early,
+/// an observer can set the parser's `ParserOptions::cancel` flag.
+///
+/// When the parser backtracks to try another way to parse something, what it parsed on the way
+/// may have been reported already, so the same node can be reported more than once.
+pub trait ParseObserver {
+    /// Called after an item is parsed, including associated and foreign items, and items in
+    /// statement position.
+    fn item(&self, _item: &ast::Item) {}
+
+    /// Called after a statement in a block is parsed, with its trailing semicolon.
+    fn stmt(&self, _stmt: &ast::Stmt) {}
+
+    /// Called after a macro call is parsed, wherever it is. `span` is the whole call.
+    fn mac_call(&self, _mac: &ast::MacCall, _span: Span) {}
+}
+
+/// How a new [`Parser`] behaves, see [`Parser::with_options`].
+#[derive(Clone)]
+pub struct ParserOptions {
+    /// Whether the parser may recover from broken code, see [`Parser::recovery`].
+    pub recovery: Recovery,
+    /// Whether to collect the tokens of nodes with `#[cfg]` or `#[cfg_attr]` attributes, so that
+    /// those can be expanded eagerly, as `#[derive]` needs.
+    pub capture_cfg: bool,
+    /// If present, the parser is not parsing Rust code but rather a macro call.
+    pub subparser_name: Option<&'static str>,
+    /// If present, another thread, e.g. an IDE's, can set this to stop the parse early. It is
+    /// checked before each item and statement, see [`Parser::is_cancelled`].
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// If present, the parser stops recovering once it has emitted this many errors, and fails
+    /// with a "too many errors" error at the next item or statement, see
+    /// [`Parser::error_limit_reached`]. Useful for input that is mostly garbage, where recovery
+    /// would only produce more and more cascading errors. A limit of zero is taken as one, as
+    /// the parser can't stop before it has reported what is wrong.
+    pub max_errors: Option<usize>,
+    /// If present, how deeply expressions, types and patterns may be nested in each other before
+    /// the parser fails with a "nesting too deep" error, rather than risk running out of stack.
+    pub max_nesting_depth: Option<u32>,
+    /// If present, this is told about the items, statements and macro calls the parser parses.
+    pub observer: Option<Lrc<dyn ParseObserver>>,
+    /// Whether to record where the parser recovers from broken code, see [`RecoveryEvent`].
+    pub record_recovery: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            recovery: Recovery::Allowed,
+            capture_cfg: false,
+            subparser_name: None,
+            cancel: None,
+            max_errors: None,
+            max_nesting_depth: None,
+            observer: None,
+            record_recovery: false,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -142,7 +247,11 @@ pub struct Parser<'a> {
     pub prev_token: Token,
     pub capture_cfg: bool,
     restrictions: Restrictions,
-    expected_tokens: Vec<TokenType>,
+    /// The tokens that would have been accepted at the current token, for
+    /// "expected one of ..." errors. This is shared with the clones of the
+    /// parser, e.g. snapshots for recovery, until one of them changes it, see
+    /// `expected_tokens_mut`.
+    expected_tokens: Lrc<Vec<TokenType>>,
     token_cursor: TokenCursor,
     // The number of calls to `bump`, i.e. the position in the token stream.
     num_bump_calls: u32,
@@ -172,13 +281,30 @@ pub struct Parser<'a> {
     last_unexpected_token_span: Option<Span>,
     /// If present, this `Parser` is not parsing Rust code but rather a macro call.
     subparser_name: Option<&'static str>,
-    capture_state: CaptureState,
+    /// What `collect_tokens` keeps track of, shared with the clones of the
+    /// parser until one of them changes it, see `capture_state_mut`.
+    capture_state: Lrc<CaptureState>,
     /// This allows us to recover when the user forget to add braces around
     /// multiple statements in the closure body.
     current_closure: Option<ClosureSpans>,
     /// Whether the parser is allowed to do recovery.
     /// This is disabled when parsing macro arguments, see #103534
     recovery: Recovery,
+    /// The function bodies skipped so far, if they are being skipped, see
+    /// `parse_crate_mod_lazy_bodies`.
+    lazy_fn_bodies: Option<Vec<LazyFnBody>>,
+    /// Set from elsewhere to stop the parse, see `ParserOptions::cancel`.
+    cancel: Option<Arc<AtomicBool>>,
+    /// How many errors the parser may emit, see `ParserOptions::max_errors`, and how many had been
+    /// emitted before it was created.
+    max_errors: Option<usize>,
+    errors_before: usize,
+    /// How deeply the expression, type or pattern being parsed is nested, see `Parser::nested`.
+    nesting_depth: u32,
+    max_nesting_depth: Option<u32>,
+    observer: Option<Lrc<dyn ParseObserver>>,
+    /// Where the parser recovered so far, if recorded, see `ParserOptions::record_recovery`.
+    recovery_events: Option<Vec<RecoveryEvent>>,
 }
 
 // This type is used a lot, e.g. it's cloned when matching many declarative macro rules with nonterminals. Make sure
@@ -223,10 +349,16 @@ struct ClosureSpans {
 /// Each replacement starts off in `ParserReplacement` form but is converted to
 /// `NodeReplacement` form when it is attached to a single AST node, via
 /// `LazyAttrTokenStreamImpl`.
-type ParserReplacement = (ParserRange, Option<AttrsTarget>);
+///
+/// Each replacement also holds the parser's position at the end of its range.
+/// When the tokens of an enclosing node are replayed, they go on from there
+/// after the range, rather than replaying the tokens in the range only to drop
+/// them. This keeps the cost of token collection linear in the number of
+/// tokens, however deeply the replaced nodes are nested.
+type ParserReplacement = (ParserRange, Option<AttrsTarget>, CollectPos);
 
 /// See the comment on `ParserReplacement`.
-type NodeReplacement = (NodeRange, Option<AttrsTarget>);
+type NodeReplacement = (NodeRange, Option<AttrsTarget>, CollectPos);
 
 impl NodeRange {
     // Converts a range within a parser's tokens to a range within a
@@ -261,7 +393,7 @@ enum Capturing {
 struct CaptureState {
     capturing: Capturing,
     parser_replacements: Vec<ParserReplacement>,
-    inner_attr_parser_ranges: FxHashMap<AttrId, ParserRange>,
+    inner_attr_parser_ranges: FxHashMap<AttrId, (ParserRange, CollectPos)>,
     // `IntervalSet` is good for perf because attrs are mostly added to this
     // set in contiguous ranges.
     seen_attrs: IntervalSet<AttrId>,
@@ -271,20 +403,72 @@ struct CaptureState {
 /// we (a) lex tokens into a nice tree structure (`TokenStream`), and then (b)
 /// use this type to emit them as a linear sequence. But a linear sequence is
 /// what the parser expects, for the most part.
+///
+/// The trees are flattened into one buffer up front, with markers where the
+/// delimited groups start and end, so that getting the next token is an index
+/// into that buffer rather than a walk of the trees. The buffer is shared by
+/// the clones of the cursor, which makes them cheap.
 #[derive(Clone, Debug)]
 struct TokenCursor {
-    // Cursor for the current (innermost) token stream. The delimiters for this
-    // token stream are found in `self.stack.last()`; when that is `None` then
-    // we are in the outermost token stream which never has delimiters.
-    tree_cursor: TokenTreeCursor,
-
-    // Token streams surrounding the current one. The delimiters for stack[n]'s
-    // tokens are in `stack[n-1]`. `stack[0]` (when present) has no delimiters
-    // because it's the outermost token stream which never has delimiters.
-    stack: Vec<(TokenTreeCursor, DelimSpan, DelimSpacing, Delimiter)>,
+    // All the tokens, shared by the clones of this cursor.
+    tokens: Lrc<Vec<CursorToken>>,
+
+    // The index in `tokens` of the next token.
+    index: usize,
+
+    // The indices in `tokens` of the `CursorToken::Open`s of the delimited
+    // groups that the cursor is in, innermost last. It is empty in the
+    // outermost token stream, which never has delimiters.
+    stack: Vec<usize>,
+}
+
+/// A token of a `TokenCursor`.
+#[derive(Clone, Debug)]
+enum CursorToken {
+    Token(Token, Spacing),
+    /// The start of a delimited group, with the group's tokens, for
+    /// `Parser::parse_token_tree`.
+    Open(DelimSpan, DelimSpacing, Delimiter, TokenStream),
+    /// The end of the innermost delimited group.
+    Close,
 }
 
 impl TokenCursor {
+    fn new(stream: TokenStream) -> TokenCursor {
+        // This uses a stack rather than recursion, so deeply nested groups
+        // can't overflow the stack.
+        let mut tokens = Vec::new();
+        let mut stack = vec![stream.trees()];
+        while let Some(trees) = stack.last_mut() {
+            match trees.next() {
+                Some(&TokenTree::Token(ref token, spacing)) => {
+                    tokens.push(CursorToken::Token(token.clone(), spacing));
+                }
+                Some(&TokenTree::Delimited(sp, spacing, delim, ref tts)) => {
+                    tokens.push(CursorToken::Open(sp, spacing, delim, tts.clone()));
+                    stack.push(tts.trees());
+                }
+                None => {
+                    stack.pop();
+                    if !stack.is_empty() {
+                        tokens.push(CursorToken::Close);
+                    }
+                }
+            }
+        }
+        TokenCursor { tokens: Lrc::new(tokens), index: 0, stack: Vec::new() }
+    }
+
+    /// Returns the delimited group that the cursor is in, if it isn't in the
+    /// outermost token stream.
+    fn innermost(&self) -> Option<(DelimSpan, DelimSpacing, Delimiter, &TokenStream)> {
+        let &open = self.stack.last()?;
+        let CursorToken::Open(sp, spacing, delim, ref tts) = self.tokens[open] else {
+            unreachable!()
+        };
+        Some((sp, spacing, delim, tts))
+    }
+
     fn next(&mut self) -> (Token, Spacing) {
         self.inlined_next()
     }
@@ -296,41 +480,37 @@ fn inlined_next(&mut self) -> (Token, Spacing) {
             // FIXME: we currently don't return `Delimiter::Invisible` open/close delims. To fix
             // #67062 we will need to, whereupon the `delim != Delimiter::Invisible` conditions
             // below can be removed.
-            if let Some(tree) = self.tree_cursor.next_ref() {
-                match tree {
-                    &TokenTree::Token(ref token, spacing) => {
-                        debug_assert!(!matches!(
-                            token.kind,
-                            token::OpenDelim(_) | token::CloseDelim(_)
-                        ));
-                        return (token.clone(), spacing);
-                    }
-                    &TokenTree::Delimited(sp, spacing, delim, ref tts) => {
-                        let trees = tts.clone().into_trees();
-                        self.stack.push((
-                            mem::replace(&mut self.tree_cursor, trees),
-                            sp,
-                            spacing,
-                            delim,
-                        ));
-                        if delim != Delimiter::Invisible {
-                            return (Token::new(token::OpenDelim(delim), sp.open), spacing.open);
-                        }
-                        // No open delimiter to return; continue on to the next iteration.
-                    }
-                };
-            } else if let Some((tree_cursor, span, spacing, delim)) = self.stack.pop() {
-                // We have exhausted this token stream. Move back to its parent token stream.
-                self.tree_cursor = tree_cursor;
-                if delim != Delimiter::Invisible {
-                    return (Token::new(token::CloseDelim(delim), span.close), spacing.close);
-                }
-                // No close delimiter to return; continue on to the next iteration.
-            } else {
+            let Some(token) = self.tokens.get(self.index) else {
                 // We have exhausted the outermost token stream. The use of
                 // `Spacing::Alone` is arbitrary and immaterial, because the
                 // `Eof` token's spacing is never used.
                 return (Token::new(token::Eof, DUMMY_SP), Spacing::Alone);
+            };
+            self.index += 1;
+            match *token {
+                CursorToken::Token(ref token, spacing) => {
+                    debug_assert!(!matches!(
+                        token.kind,
+                        token::OpenDelim(_) | token::CloseDelim(_)
+                    ));
+                    return (token.clone(), spacing);
+                }
+                CursorToken::Open(sp, spacing, delim, _) => {
+                    self.stack.push(self.index - 1);
+                    if delim != Delimiter::Invisible {
+                        return (Token::new(token::OpenDelim(delim), sp.open), spacing.open);
+                    }
+                    // No open delimiter to return; continue on to the next iteration.
+                }
+                CursorToken::Close => {
+                    // We have exhausted this token stream. Move back to its parent token stream.
+                    let (sp, spacing, delim, _) = self.innermost().unwrap();
+                    self.stack.pop();
+                    if delim != Delimiter::Invisible {
+                        return (Token::new(token::CloseDelim(delim), sp.close), spacing.close);
+                    }
+                    // No close delimiter to return; continue on to the next iteration.
+                }
             }
         }
     }
@@ -443,29 +623,53 @@ pub fn new(
         stream: TokenStream,
         subparser_name: Option<&'static str>,
     ) -> Self {
+        Parser::with_options(psess, stream, ParserOptions { subparser_name, ..Default::default() })
+    }
+
+    /// Creates a parser that behaves as `options` say, for callers that need more control than
+    /// [`Parser::new`] gives them, e.g. tools that want to forbid recovery from the start.
+    pub fn with_options(psess: &'a ParseSess, stream: TokenStream, options: ParserOptions) -> Self {
+        let ParserOptions {
+            recovery,
+            capture_cfg,
+            subparser_name,
+            cancel,
+            max_errors,
+            max_nesting_depth,
+            observer,
+            record_recovery,
+        } = options;
         let mut parser = Parser {
             psess,
             token: Token::dummy(),
             token_spacing: Spacing::Alone,
             prev_token: Token::dummy(),
-            capture_cfg: false,
+            capture_cfg,
             restrictions: Restrictions::empty(),
-            expected_tokens: Vec::new(),
-            token_cursor: TokenCursor { tree_cursor: stream.into_trees(), stack: Vec::new() },
+            expected_tokens: Lrc::default(),
+            token_cursor: TokenCursor::new(stream),
             num_bump_calls: 0,
             break_last_token: false,
             unmatched_angle_bracket_count: 0,
             angle_bracket_nesting: 0,
             last_unexpected_token_span: None,
             subparser_name,
-            capture_state: CaptureState {
+            capture_state: Lrc::new(CaptureState {
                 capturing: Capturing::No,
                 parser_replacements: Vec::new(),
                 inner_attr_parser_ranges: Default::default(),
                 seen_attrs: IntervalSet::new(u32::MAX as usize),
-            },
+            }),
             current_closure: None,
-            recovery: Recovery::Allowed,
+            recovery,
+            lazy_fn_bodies: None,
+            cancel,
+            max_errors: max_errors.map(|max| max.max(1)),
+            errors_before: psess.dcx().err_count(),
+            nesting_depth: 0,
+            max_nesting_depth,
+            observer,
+            recovery_events: record_recovery.then(Vec::new),
         };
 
         // Make parser point to the first token.
@@ -494,7 +698,108 @@ pub fn recovery(mut self, recovery: Recovery) -> Self {
     /// But making the distinction is very subtle, and simply forbidding all recovery is a lot simpler to uphold.
     #[inline]
     fn may_recover(&self) -> bool {
-        matches!(self.recovery, Recovery::Allowed)
+        matches!(self.recovery, Recovery::Allowed) && !self.error_limit_reached()
+    }
+
+    /// Whether the parse was cancelled through `ParserOptions::cancel`. Once it is, the parser
+    /// fails with a "parsing was cancelled" error at the next item or statement, and doesn't
+    /// recover from that error. Callers that cancel should check this and cancel the error
+    /// rather than emit it.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Whether the parser has emitted as many errors as `ParserOptions::max_errors` allows. Once
+    /// it has, it doesn't recover anymore, and fails with a "too many errors" error at the next
+    /// item or statement, so what was parsed until then is all there is.
+    pub fn error_limit_reached(&self) -> bool {
+        // Without recovery, there is no going on after the first error, whenever it was forbidden.
+        let max_errors = match self.recovery {
+            Recovery::Strict => Some(self.max_errors.unwrap_or(1).min(1)),
+            _ => self.max_errors,
+        };
+        max_errors.is_some_and(|max| self.dcx().err_count() >= self.errors_before + max)
+    }
+
+    /// Whether the parser has to stop, because the parse was cancelled or because of too many
+    /// errors. Loops that recover from an error must not do so then, and never do with
+    /// `Recovery::Strict`.
+    fn is_stopped(&self) -> bool {
+        matches!(self.recovery, Recovery::Strict)
+            || self.is_cancelled()
+            || self.error_limit_reached()
+    }
+
+    /// Fails if the parser has to stop, see [`Parser::is_stopped`].
+    fn check_stopped(&self) -> PResult<'a, ()> {
+        let span = self.token.span;
+        if self.is_cancelled() {
+            return Err(self.dcx().create_err(errors::ParseCancelled { span }));
+        }
+        if self.error_limit_reached() {
+            let mut err = self.dcx().create_err(errors::TooManyErrors { span });
+            // The error that ends a strict parse was emitted, and is the one to report.
+            if matches!(self.recovery, Recovery::Strict) {
+                err.downgrade_to_delayed_bug();
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// With `Recovery::Strict`, fails once the parser has emitted an error, so that what was
+    /// being parsed when it did fails too, even if the parser could go on after it.
+    fn check_strict(&self) -> PResult<'a, ()> {
+        if matches!(self.recovery, Recovery::Strict) { self.check_stopped() } else { Ok(()) }
+    }
+
+    /// Returns where the parser recovered from broken code since this was last called, if
+    /// `ParserOptions::record_recovery` is set, in the order it recovered.
+    pub fn take_recovery_events(&mut self) -> Vec<RecoveryEvent> {
+        self.recovery_events.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    fn record_recovery(&mut self, kind: RecoveryKind, span: Span) {
+        if let Some(events) = &mut self.recovery_events {
+            events.push(RecoveryEvent { kind, span });
+        }
+    }
+
+    /// Records the tokens skipped since the `start`th call to `bump`, at `lo`, if any.
+    fn record_skipped_tokens(&mut self, start: u32, lo: Span) {
+        if self.num_bump_calls > start {
+            self.record_recovery(RecoveryKind::SkippedTokens, lo.to(self.prev_token.span));
+        }
+    }
+
+    /// Tells the observer, if there is one, about something that was parsed.
+    fn observe(&self, f: impl FnOnce(&dyn ParseObserver)) {
+        if let Some(observer) = &self.observer {
+            f(&**observer);
+        }
+    }
+
+    /// Runs `f` to parse a `kind` (e.g. "expression") that may be nested in others, failing
+    /// instead if that is too deep, see `ParserOptions::max_nesting_depth`. Otherwise, deeply
+    /// nested code only needs more stack, which `f` gets more of as it runs out.
+    fn nested<T>(
+        &mut self,
+        kind: &'static str,
+        f: impl FnOnce(&mut Self) -> PResult<'a, T>,
+    ) -> PResult<'a, T> {
+        if let Some(limit) = self.max_nesting_depth
+            && self.nesting_depth >= limit
+        {
+            let span = self.token.span;
+            return Err(self.dcx().create_err(errors::NestingTooDeep { span, kind, limit }));
+        }
+        self.check_strict()?;
+        self.nesting_depth += 1;
+        let res = ensure_sufficient_stack(|| f(self));
+        self.nesting_depth -= 1;
+        let res = res?;
+        self.check_strict()?;
+        Ok(res)
     }
 
     /// Version of [`unexpected`](Parser::unexpected) that "returns" any type in the `Ok`
@@ -585,7 +890,7 @@ fn ident_or_err(&mut self, recover: bool) -> PResult<'a, (Ident, IdentIsRaw)> {
     fn check(&mut self, tok: &TokenKind) -> bool {
         let is_present = self.token == *tok;
         if !is_present {
-            self.expected_tokens.push(TokenType::Token(tok.clone()));
+            self.expected_tokens_mut().push(TokenType::Token(tok.clone()));
         }
         is_present
     }
@@ -626,7 +931,7 @@ pub fn eat(&mut self, tok: &TokenKind) -> bool {
     #[inline]
     #[must_use]
     fn check_keyword(&mut self, kw: Symbol) -> bool {
-        self.expected_tokens.push(TokenType::Keyword(kw));
+        self.expected_tokens_mut().push(TokenType::Keyword(kw));
         self.token.is_keyword(kw)
     }
 
@@ -714,7 +1019,7 @@ fn check_or_expected(&mut self, ok: bool, typ: TokenType) -> bool {
         if ok {
             true
         } else {
-            self.expected_tokens.push(typ);
+            self.expected_tokens_mut().push(typ);
             false
         }
     }
@@ -791,7 +1096,7 @@ fn break_and_eat(&mut self, expected: TokenKind) -> bool {
                 true
             }
             _ => {
-                self.expected_tokens.push(TokenType::Token(expected));
+                self.expected_tokens_mut().push(TokenType::Token(expected));
                 false
             }
         }
@@ -1139,7 +1444,7 @@ fn inlined_bump_with(&mut self, (next_token, next_spacing): (Token, Spacing)) {
         self.token_spacing = next_spacing;
 
         // Diagnostics.
-        self.expected_tokens.clear();
+        self.clear_expected_tokens();
     }
 
     /// Advance the parser by one token.
@@ -1175,31 +1480,23 @@ pub fn look_ahead<R>(&self, dist: usize, looker: impl FnOnce(&Token) -> R) -> R
         // Typically around 98% of the `dist > 0` cases have `dist == 1`, so we
         // have a fast special case for that.
         if dist == 1 {
-            // The index is zero because the tree cursor's index always points
-            // to the next token to be gotten.
-            match self.token_cursor.tree_cursor.look_ahead(0) {
-                Some(tree) => {
-                    // Indexing stayed within the current token tree.
-                    match tree {
-                        TokenTree::Token(token, _) => return looker(token),
-                        &TokenTree::Delimited(dspan, _, delim, _) => {
-                            if delim != Delimiter::Invisible {
-                                return looker(&Token::new(token::OpenDelim(delim), dspan.open));
-                            }
-                        }
-                    };
+            // The token cursor's index always points to the next token to be
+            // gotten.
+            match self.token_cursor.tokens.get(self.token_cursor.index) {
+                Some(CursorToken::Token(token, _)) => return looker(token),
+                Some(&CursorToken::Open(dspan, _, delim, _)) => {
+                    if delim != Delimiter::Invisible {
+                        return looker(&Token::new(token::OpenDelim(delim), dspan.open));
+                    }
                 }
-                None => {
-                    // The tree cursor lookahead went (one) past the end of the
-                    // current token tree. Try to return a close delimiter.
-                    if let Some(&(_, span, _, delim)) = self.token_cursor.stack.last()
-                        && delim != Delimiter::Invisible
-                    {
-                        // We are not in the outermost token stream, so we have
-                        // delimiters. Also, those delimiters are not skipped.
-                        return looker(&Token::new(token::CloseDelim(delim), span.close));
+                Some(CursorToken::Close) => {
+                    // The next token closes the current delimited group.
+                    let (dspan, _, delim, _) = self.token_cursor.innermost().unwrap();
+                    if delim != Delimiter::Invisible {
+                        return looker(&Token::new(token::CloseDelim(delim), dspan.close));
                     }
                 }
+                None => {}
             }
         }
 
@@ -1385,8 +1682,8 @@ pub fn parse_token_tree(&mut self) -> TokenTree {
         match self.token.kind {
             token::OpenDelim(..) => {
                 // Grab the tokens within the delimiters.
-                let stream = self.token_cursor.tree_cursor.stream.clone();
-                let (_, span, spacing, delim) = *self.token_cursor.stack.last().unwrap();
+                let (span, spacing, delim, stream) = self.token_cursor.innermost().unwrap();
+                let stream = stream.clone();
 
                 // Advance the token cursor through the entire delimited
                 // sequence. After getting the `OpenDelim` we are *within* the
@@ -1603,7 +1900,7 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 if let Some(subparser) = parser.subparser_name {
                     dbg_fmt.field("subparser_name", &subparser);
                 }
-                if let Recovery::Forbidden = parser.recovery {
+                if !matches!(parser.recovery, Recovery::Allowed) {
                     dbg_fmt.field("recovery", &parser.recovery);
                 }
 
@@ -1616,7 +1913,22 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 
     pub fn clear_expected_tokens(&mut self) {
-        self.expected_tokens.clear();
+        if self.expected_tokens.is_empty() {
+            return;
+        }
+        match Lrc::get_mut(&mut self.expected_tokens) {
+            Some(expected_tokens) => expected_tokens.clear(),
+            // Shared with a clone of the parser, which keeps its tokens.
+            None => self.expected_tokens = Lrc::default(),
+        }
+    }
+
+    fn expected_tokens_mut(&mut self) -> &mut Vec<TokenType> {
+        Lrc::make_mut(&mut self.expected_tokens)
+    }
+
+    fn capture_state_mut(&mut self) -> &mut CaptureState {
+        Lrc::make_mut(&mut self.capture_state)
     }
 
     pub fn approx_token_stream_pos(&self) -> u32 {
@@ -1659,10 +1971,6 @@ enum FlatToken {
     /// directly into the constructed `AttrTokenStream` as an
     /// `AttrTokenTree::AttrsTarget`.
     AttrsTarget(AttrsTarget),
-    /// A special 'empty' token that is ignored during the conversion
-    /// to an `AttrTokenStream`. This is used to simplify the
-    /// handling of replace ranges.
-    Empty,
 }
 
 // Metavar captures of various kinds.
@@ -0,0 +1,240 @@
+//! A fast, approximate pass over a crate's tokens that only finds its items, for tools like IDE
+//! indexers that want names, kinds and spans without building the AST.
+
+use rustc_ast::token::{self, Delimiter};
+use rustc_ast::tokenstream::TokenStream;
+use rustc_session::parse::ParseSess;
+use rustc_span::symbol::{kw, Ident};
+use rustc_span::Span;
+
+use super::Parser;
+
+/// The kind of an [`OutlineItem`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutlineItemKind {
+    ExternCrate,
+    Use,
+    Static,
+    Const,
+    Fn,
+    Mod,
+    ForeignMod,
+    TyAlias,
+    Enum,
+    Struct,
+    Union,
+    Trait,
+    Impl,
+    MacCall,
+    MacroDef,
+}
+
+/// An item found by [`outline_crate`].
+#[derive(Clone, Debug)]
+pub struct OutlineItem {
+    pub kind: OutlineItemKind,
+    /// The item's name, if it has one. For macro calls, this is the name of the macro.
+    pub name: Option<Ident>,
+    /// The item from its visibility to its end, without its outer attributes.
+    pub span: Span,
+    /// How many items this one is nested in, e.g. 1 for a method in an `impl` at the top level.
+    pub depth: usize,
+}
+
+/// Finds the items in `stream`, the tokens of a crate, including those nested in modules, traits,
+/// impls and `extern` blocks, in source order.
+///
+/// Only the tokens before an item's name are looked at. The rest of the item, like the body of a
+/// function, is skipped as token trees, without being parsed. So this is several times faster
+/// than parsing the crate, but it also doesn't report errors: tokens that don't start an item are
+/// skipped.
+pub fn outline_crate(psess: &ParseSess, stream: TokenStream) -> Vec<OutlineItem> {
+    let mut parser = Parser::new(psess, stream, None);
+    let mut items = Vec::new();
+    parser.outline_items(0, &mut items);
+    items
+}
+
+impl<'a> Parser<'a> {
+    /// Finds the items up to the end of the current delimited group, or the end of the stream.
+    fn outline_items(&mut self, depth: usize, items: &mut Vec<OutlineItem>) {
+        loop {
+            self.outline_skip_attrs();
+            if matches!(self.token.kind, token::Eof | token::CloseDelim(_)) {
+                return;
+            }
+
+            let lo = self.token.span;
+            let Some((kind, name)) = self.outline_item_header() else {
+                self.outline_skip_token_tree();
+                continue;
+            };
+
+            let index = items.len();
+            items.push(OutlineItem { kind, name, span: lo, depth });
+            match kind {
+                OutlineItemKind::Mod
+                | OutlineItemKind::ForeignMod
+                | OutlineItemKind::Trait
+                | OutlineItemKind::Impl => {
+                    if self.outline_skip_to_body() {
+                        self.bump();
+                        self.outline_items(depth + 1, items);
+                        self.bump();
+                    }
+                }
+                OutlineItemKind::ExternCrate
+                | OutlineItemKind::Use
+                | OutlineItemKind::Static
+                | OutlineItemKind::Const
+                | OutlineItemKind::TyAlias => self.outline_skip_item_rest(false),
+                _ => self.outline_skip_item_rest(true),
+            }
+            items[index].span = lo.to(self.prev_token.span);
+        }
+    }
+
+    fn outline_skip_attrs(&mut self) {
+        loop {
+            if let token::DocComment(..) = self.token.kind {
+                self.bump();
+            } else if self.token == token::Pound {
+                self.bump();
+                self.eat(&token::Not);
+                if self.token == token::OpenDelim(Delimiter::Bracket) {
+                    self.parse_token_tree();
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Eats an item's visibility, qualifiers and keyword, and returns its kind and its name.
+    fn outline_item_header(&mut self) -> Option<(OutlineItemKind, Option<Ident>)> {
+        if self.eat_keyword(kw::Pub) && self.token == token::OpenDelim(Delimiter::Parenthesis) {
+            self.parse_token_tree();
+        }
+
+        let kind = loop {
+            let kind = if self.eat_keyword(kw::Fn) {
+                OutlineItemKind::Fn
+            } else if self.eat_keyword(kw::Struct) {
+                OutlineItemKind::Struct
+            } else if self.eat_keyword(kw::Enum) {
+                OutlineItemKind::Enum
+            } else if self.token.is_keyword(kw::Union) && self.look_ahead(1, |t| t.is_ident()) {
+                self.bump();
+                OutlineItemKind::Union
+            } else if self.eat_keyword(kw::Trait) {
+                OutlineItemKind::Trait
+            } else if self.eat_keyword(kw::Impl) {
+                return Some((OutlineItemKind::Impl, None));
+            } else if self.eat_keyword(kw::Mod) {
+                OutlineItemKind::Mod
+            } else if self.eat_keyword(kw::Use) {
+                return Some((OutlineItemKind::Use, None));
+            } else if self.eat_keyword(kw::Static) {
+                self.eat_keyword(kw::Mut);
+                OutlineItemKind::Static
+            } else if self.eat_keyword(kw::Type) {
+                OutlineItemKind::TyAlias
+            } else if self.eat_keyword(kw::Macro) {
+                OutlineItemKind::MacroDef
+            } else if self.token.is_keyword(kw::MacroRules)
+                && self.look_ahead(1, |t| *t == token::Not)
+            {
+                self.bump();
+                self.bump();
+                OutlineItemKind::MacroDef
+            } else if self.eat_keyword(kw::Const) {
+                if [kw::Fn, kw::Unsafe, kw::Async, kw::Extern]
+                    .iter()
+                    .any(|&kw| self.token.is_keyword(kw))
+                {
+                    continue;
+                }
+                OutlineItemKind::Const
+            } else if self.eat_keyword(kw::Extern) {
+                if let token::Literal(..) = self.token.kind {
+                    self.bump();
+                }
+                if self.eat_keyword(kw::Crate) {
+                    OutlineItemKind::ExternCrate
+                } else if self.token == token::OpenDelim(Delimiter::Brace) {
+                    return Some((OutlineItemKind::ForeignMod, None));
+                } else {
+                    continue;
+                }
+            } else if [kw::Unsafe, kw::Async, kw::Safe, kw::Default, kw::Auto]
+                .iter()
+                .any(|&kw| self.eat_keyword(kw))
+            {
+                continue;
+            } else if self.token.is_ident() || self.token == token::PathSep {
+                // A macro call, named by the last segment of its path.
+                self.eat(&token::PathSep);
+                let mut name = None;
+                while let Some((ident, _)) = self.token.ident() {
+                    name = Some(ident);
+                    self.bump();
+                    if !self.eat(&token::PathSep) {
+                        break;
+                    }
+                }
+                if !self.eat(&token::Not) {
+                    return None;
+                }
+                return Some((OutlineItemKind::MacCall, name));
+            } else {
+                return None;
+            };
+            break kind;
+        };
+
+        let name = self.token.ident().map(|(ident, _)| ident);
+        Some((kind, name))
+    }
+
+    /// Skips to the `{` that opens the body of a module, trait, impl or `extern` block, and
+    /// returns whether there is one.
+    fn outline_skip_to_body(&mut self) -> bool {
+        loop {
+            match self.token.kind {
+                token::Eof | token::CloseDelim(_) => return false,
+                token::Semi => {
+                    self.bump();
+                    return false;
+                }
+                token::OpenDelim(Delimiter::Brace) => return true,
+                _ => self.outline_skip_token_tree(),
+            }
+        }
+    }
+
+    /// Skips the rest of an item, which ends with a `;`, or a block if `ends_with_block`.
+    fn outline_skip_item_rest(&mut self, ends_with_block: bool) {
+        loop {
+            match self.token.kind {
+                token::Eof | token::CloseDelim(_) => return,
+                token::Semi => {
+                    self.bump();
+                    return;
+                }
+                token::OpenDelim(Delimiter::Brace) if ends_with_block => {
+                    self.parse_token_tree();
+                    return;
+                }
+                _ => self.outline_skip_token_tree(),
+            }
+        }
+    }
+
+    fn outline_skip_token_tree(&mut self) {
+        if let token::OpenDelim(_) = self.token.kind {
+            self.parse_token_tree();
+        } else {
+            self.bump();
+        }
+    }
+}
@@ -438,6 +438,17 @@ fn parse_pat_with_range_pat(
         allow_range_pat: bool,
         expected: Option<Expected>,
         syntax_loc: Option<PatternLocation>,
+    ) -> PResult<'a, P<Pat>> {
+        self.nested("pattern", |this| {
+            this.parse_pat_with_range_pat_(allow_range_pat, expected, syntax_loc)
+        })
+    }
+
+    fn parse_pat_with_range_pat_(
+        &mut self,
+        allow_range_pat: bool,
+        expected: Option<Expected>,
+        syntax_loc: Option<PatternLocation>,
     ) -> PResult<'a, P<Pat>> {
         maybe_recover_from_interpolated_ty_qpath!(self, true);
         maybe_whole!(self, NtPat, |pat| pat);
@@ -852,7 +863,9 @@ fn recover_additional_muts(&mut self) {
     fn parse_pat_mac_invoc(&mut self, path: Path) -> PResult<'a, PatKind> {
         self.bump();
         let args = self.parse_delim_args()?;
+        let span = path.span.to(self.prev_token.span);
         let mac = P(MacCall { path, args });
+        self.observe(|observer| observer.mac_call(&mac, span));
         Ok(PatKind::MacCall(mac))
     }
 
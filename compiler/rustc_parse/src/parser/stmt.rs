@@ -221,6 +221,7 @@ fn parse_stmt_mac(&mut self, lo: Span, attrs: AttrVec, path: ast::Path) -> PResu
         };
 
         let mac = P(MacCall { path, args });
+        self.observe(|observer| observer.mac_call(&mac, lo.to(hi)));
 
         let kind = if (style == MacStmtStyle::Braces
             && self.token != token::Dot
@@ -594,7 +595,7 @@ pub(crate) fn parse_block_tail(
                 snapshot = Some(self.create_snapshot_for_diagnostic());
             }
             let stmt = match self.parse_full_stmt(recover) {
-                Err(mut err) if recover.yes() => {
+                Err(mut err) if recover.yes() && !self.is_stopped() => {
                     if let Some(ref mut snapshot) = snapshot {
                         snapshot.recover_vcs_conflict_marker();
                     }
@@ -657,6 +658,7 @@ pub fn parse_full_stmt(
     ) -> PResult<'a, Option<Stmt>> {
         // Skip looking for a trailing semicolon when we have an interpolated statement.
         maybe_whole!(self, NtStmt, |stmt| Some(stmt.into_inner()));
+        self.check_stopped()?;
 
         let Some(mut stmt) = self.parse_stmt_without_recovery(true, ForceCollect::No)? else {
             return Ok(None);
@@ -840,6 +842,8 @@ pub fn parse_full_stmt(
         }
 
         stmt.span = stmt.span.to(self.prev_token.span);
+        self.check_strict()?;
+        self.observe(|observer| observer.stmt(&stmt));
         Ok(Some(stmt))
     }
 
@@ -1,27 +1,47 @@
 use std::assert_matches::assert_matches;
+use std::cell::RefCell;
 use std::io::prelude::*;
 use std::iter::Peekable;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::{io, str};
 
 use ast::token::IdentIsRaw;
 use rustc_ast::ptr::P;
 use rustc_ast::token::{self, Delimiter, Token};
-use rustc_ast::tokenstream::{DelimSpacing, DelimSpan, Spacing, TokenStream, TokenTree};
+use rustc_ast::tokenstream::{
+    AttrTokenTree, DelimSpacing, DelimSpan, Spacing, TokenStream, TokenTree,
+};
 use rustc_ast::{self as ast, visit, PatKind};
-use rustc_ast_pretty::pprust::item_to_string;
+use rustc_ast_pretty::pprust::{self, item_to_string};
+use rustc_data_structures::fx::FxIndexSet;
 use rustc_data_structures::sync::Lrc;
 use rustc_errors::emitter::HumanEmitter;
 use rustc_errors::{DiagCtxt, MultiSpan, PResult};
+use rustc_session::config::Cfg;
 use rustc_session::parse::ParseSess;
 use rustc_span::source_map::{FilePathMapping, SourceMap};
 use rustc_span::symbol::{kw, sym, Symbol};
 use rustc_span::{create_default_session_globals_then, BytePos, FileName, Pos, Span};
 use termcolor::WriteColor;
 
-use crate::parser::{ForceCollect, Parser};
-use crate::{new_parser_from_source_str, source_str_to_stream, unwrap_or_emit_fatal};
+use crate::cst::{RawTokenKind, TriviaKind};
+use crate::lexer::{lex_token_trees, FrontmatterAllowed};
+use crate::parser::attr::AllowLeadingUnsafe;
+use crate::parser::{
+    outline_crate, ForceCollect, OutlineItemKind, ParseObserver, Parser, ParserOptions, Recovery,
+    RecoveryKind,
+};
+use crate::{
+    check_item_round_trip, eval_cfg_predicate_from_source_str, expand_cfg_attr_recursive,
+    fake_token_stream_for_item, maybe_new_parser_from_file, new_parser_from_crate_source_str,
+    new_parser_from_reader, new_parser_from_source_str, parse_attribute_from_source_str,
+    parse_crate_attrs_from_source_str, parse_expr_from_source_str, parse_in_with_rest,
+    parse_meta_item_from_source_str, parse_pat_from_source_str, parse_stmt_from_source_str,
+    parse_tree_json, parse_ty_from_source_str, reparse_crate, source_str_to_stream,
+    source_str_to_stream_with_comments, unwrap_or_emit_fatal, RoundTripDiff,
+};
 
 fn psess() -> ParseSess {
     ParseSess::new(vec![crate::DEFAULT_LOCALE_RESOURCE, crate::DEFAULT_LOCALE_RESOURCE])
@@ -1347,14 +1367,6 @@ fn crlf_doc_comments() {
 
 #[test]
 fn ttdelim_span() {
-    fn parse_expr_from_source_str(
-        name: FileName,
-        source: String,
-        psess: &ParseSess,
-    ) -> PResult<'_, P<ast::Expr>> {
-        unwrap_or_emit_fatal(new_parser_from_source_str(psess, name, source)).parse_expr()
-    }
-
     create_default_session_globals_then(|| {
         let psess = psess();
         let expr = parse_expr_from_source_str(
@@ -1496,6 +1508,31 @@ fn look_ahead_non_outermost_stream() {
     });
 }
 
+#[test]
+fn bump_through_nested_groups() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = format!("a {}b{} ((c) [d {{}}]) e", "(".repeat(1000), ")".repeat(1000));
+        let mut p = string_to_parser(&psess, source.clone());
+        let mut text = String::new();
+        while p.token != token::Eof {
+            text.push_str(&pprust::token_to_string(&p.token));
+            p.bump();
+        }
+        assert_eq!(text, source.replace(' ', ""));
+
+        // Moving past a whole group leaves the parser after its close delimiter.
+        let mut p = string_to_parser(&psess, "((c) [d {}]) e".to_string());
+        let TokenTree::Delimited(.., delim, stream) = p.parse_token_tree() else {
+            panic!("expected a delimited group")
+        };
+        assert_eq!(delim, Delimiter::Parenthesis);
+        assert_eq!(pprust::tts_to_string(&stream), "(c) [d {}]");
+        look(&p, 0, token::Ident(Symbol::intern("e"), IdentIsRaw::No));
+        look(&p, 1, token::Eof);
+    });
+}
+
 // FIXME(nnethercote) All the output is currently wrong.
 #[test]
 fn debug_lookahead() {
@@ -1782,3 +1819,1025 @@ fn non_pattern_whitespace() {
     assert_eq!(matches_codepattern("\u{205F}a   b", "ab"), false);
     assert_eq!(matches_codepattern("a  \u{3000}b", "ab"), false);
 }
+
+#[test]
+fn maybe_new_parser_from_missing_file() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let path = Path::new("this/file/does/not/exist.rs");
+        let Err(errs) = maybe_new_parser_from_file(&psess, path, None) else {
+            panic!("parser created from a missing file");
+        };
+        assert_eq!(errs.len(), 1);
+        for err in errs {
+            err.cancel();
+        }
+    });
+}
+
+#[test]
+fn parse_expr_from_source_str_requires_eof() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |source: &str| {
+            let name = FileName::anon_source_code(source);
+            parse_expr_from_source_str(name, source.to_string(), &psess)
+        };
+        assert_matches!(parse("a + b").unwrap().kind, ast::ExprKind::Binary(..));
+        parse("a + b c").unwrap_err().into_iter().for_each(|err| err.cancel());
+    });
+}
+
+#[test]
+fn parse_stmt_from_source_str_takes_trailing_semi() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |source: &str| {
+            let name = FileName::anon_source_code(source);
+            parse_stmt_from_source_str(name, source.to_string(), &psess)
+        };
+        assert_matches!(parse("f(x);").unwrap().unwrap().kind, ast::StmtKind::Semi(..));
+        assert_matches!(parse("let x = 1;").unwrap().unwrap().kind, ast::StmtKind::Let(..));
+        parse("f(x); g(x)").unwrap_err().into_iter().for_each(|err| err.cancel());
+    });
+}
+
+#[test]
+fn parse_ty_from_source_str_buffers_errors() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |source: &str| {
+            let name = FileName::anon_source_code(source);
+            parse_ty_from_source_str(name, source.to_string(), &psess)
+        };
+        assert_matches!(parse("Vec<u8>").unwrap().kind, ast::TyKind::Path(..));
+        for source in ["Vec<u8> u8", "(u8"] {
+            let errs = parse(source).unwrap_err();
+            assert!(!errs.is_empty());
+            errs.into_iter().for_each(|err| err.cancel());
+        }
+    });
+}
+
+#[test]
+fn parse_meta_item_from_source_str_requires_eof() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |source: &str, unsafe_allowed| {
+            let name = FileName::anon_source_code(source);
+            parse_meta_item_from_source_str(name, source.to_string(), &psess, unsafe_allowed)
+        };
+        let meta = parse(r#"feature = "std""#, AllowLeadingUnsafe::No).unwrap();
+        assert!(meta.has_name(sym::feature));
+        assert_eq!(meta.value_str(), Some(sym::std));
+        let meta = parse("any(unix, windows)", AllowLeadingUnsafe::No).unwrap();
+        assert_eq!(meta.meta_item_list().map(|list| list.len()), Some(2));
+        let meta = parse("unsafe(no_mangle)", AllowLeadingUnsafe::Yes).unwrap();
+        assert!(meta.has_name(sym::no_mangle));
+        for source in ["unix windows", "any(unix", "unsafe(no_mangle)"] {
+            let errs = parse(source, AllowLeadingUnsafe::No).unwrap_err();
+            assert!(!errs.is_empty());
+            errs.into_iter().for_each(|err| err.cancel());
+        }
+    });
+}
+
+#[test]
+fn parse_attribute_from_source_str_styles() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |source: &str| {
+            let name = FileName::anon_source_code(source);
+            parse_attribute_from_source_str(name, source.to_string(), &psess)
+        };
+        let attr = parse("#[derive(Debug)]").unwrap();
+        assert_eq!(attr.style, ast::AttrStyle::Outer);
+        assert!(attr.has_name(sym::derive));
+        let attr = parse("#![allow(unused)]").unwrap();
+        assert_eq!(attr.style, ast::AttrStyle::Inner);
+        assert!(attr.has_name(sym::allow));
+        let attr = parse("/// Docs.").unwrap();
+        assert!(attr.is_doc_comment());
+        assert_eq!(attr.doc_str(), Some(Symbol::intern(" Docs.")));
+        for source in ["derive(Debug)", "#[inline] fn f() {}", "#[inline"] {
+            let errs = parse(source).unwrap_err();
+            assert!(!errs.is_empty());
+            errs.into_iter().for_each(|err| err.cancel());
+        }
+    });
+}
+
+#[test]
+fn expand_cfg_attr_recursive_flattens_nested_cfg_attrs() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let name = FileName::Custom("cfg_attr".to_string());
+        let source = "#[cfg_attr(unix, inline, cfg_attr(unix, cold), cfg_attr(windows, must_use))]";
+        let attr = parse_attribute_from_source_str(name, source.to_string(), &psess).unwrap();
+        let mut predicates = vec![];
+        let attrs = expand_cfg_attr_recursive(&attr, &psess, |cfg| {
+            predicates.push(cfg.name_or_empty());
+            cfg.has_name(sym::unix)
+        });
+        assert_eq!(predicates, [sym::unix, sym::unix, sym::windows]);
+        let paths: Vec<_> =
+            attrs.iter().map(|(item, _)| pprust::path_to_string(&item.path)).collect();
+        assert_eq!(paths, ["inline", "cold"]);
+        let sm = psess.source_map();
+        let snippets: Vec<_> =
+            attrs.iter().map(|&(_, span)| sm.span_to_snippet(span).unwrap()).collect();
+        assert_eq!(snippets, ["inline", "cold"]);
+
+        assert!(expand_cfg_attr_recursive(&attr, &psess, |_| false).is_empty());
+    });
+}
+
+#[test]
+fn eval_cfg_predicate_from_source_str_matches_cfgs() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let cfgs: Cfg = FxIndexSet::from_iter([
+            (sym::unix, None),
+            (sym::target_os, Some(Symbol::intern("linux"))),
+            (sym::target_pointer_width, Some(Symbol::intern("64"))),
+        ]);
+        let eval = |source: &str| {
+            let name = FileName::anon_source_code(source);
+            eval_cfg_predicate_from_source_str(name, source.to_string(), &psess, &cfgs)
+        };
+        assert!(eval("unix").unwrap());
+        assert!(!eval("windows").unwrap());
+        assert!(eval(r#"all(unix, target_pointer_width = "64")"#).unwrap());
+        assert!(!eval(r#"any(windows, target_os = "macos")"#).unwrap());
+        assert!(eval(r#"not(target_os = "macos")"#).unwrap());
+        let invalid = [
+            "foo(unix)",
+            "not(unix, unix)",
+            "a::b",
+            "target_os = 1",
+            "any(\"unix\")",
+            "target(os = \"linux\")",
+        ];
+        for source in invalid {
+            let errs = eval(source).unwrap_err();
+            assert!(!errs.is_empty());
+            errs.into_iter().for_each(|err| err.cancel());
+        }
+    });
+}
+
+#[test]
+fn parse_crate_attrs_from_source_str_stops_at_first_item() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        // Only the attributes are lexed, so the unclosed delimiter after them doesn't get in the
+        // way, and neither does the `]` in the string literal.
+        let source = r#"#!/usr/bin/env rustc
+//! Docs.
+#![no_std]
+/* Comment. */ #![doc(html_root_url = "]")]
+fn main() {
+"#;
+        let name = FileName::Custom("crate_attrs".to_string());
+        let attrs = parse_crate_attrs_from_source_str(name, source.to_string(), &psess).unwrap();
+        assert_eq!(attrs.len(), 3);
+        assert!(attrs[0].is_doc_comment());
+        assert!(attrs[1].has_name(sym::no_std));
+        assert!(attrs[2].has_name(sym::doc));
+        assert!(psess.dcx().has_errors().is_none());
+
+        let name = FileName::Custom("unclosed_crate_attrs".to_string());
+        let source = "#![no_std(".to_string();
+        let errs = parse_crate_attrs_from_source_str(name, source, &psess).unwrap_err();
+        assert!(!errs.is_empty());
+        errs.into_iter().for_each(|err| err.cancel());
+    });
+}
+
+#[test]
+fn parse_pat_from_source_str_top_alt() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |source: &str, allow_top_alt| {
+            let name = FileName::anon_source_code(source);
+            parse_pat_from_source_str(name, source.to_string(), &psess, allow_top_alt)
+        };
+        assert_matches!(parse("Some(x) | None", true).unwrap().kind, PatKind::Or(..));
+        assert_matches!(parse("(a | b)", false).unwrap().kind, PatKind::Paren(..));
+        parse("a | b", false).unwrap_err().into_iter().for_each(|err| err.cancel());
+    });
+}
+
+#[test]
+fn parse_crate_mod_recovering_skips_bad_items() {
+    create_default_session_globals_then(|| {
+        let (handler, source_map, output) = create_test_handler();
+        let psess = ParseSess::with_dcx(handler, source_map);
+        let source = "fn a() {}\nfn (x: u8) {}\nlet y = 1;\nfn b() {}\n;\n".to_string();
+        let krate = string_to_parser(&psess, source).parse_crate_mod_recovering();
+        let names: Vec<_> = krate.items.iter().map(|item| item.ident.name).collect();
+        assert_eq!(names, [Symbol::intern("a"), Symbol::intern("b")]);
+        assert!(psess.dcx().has_errors().is_some());
+        assert!(!output.lock().unwrap().is_empty());
+    });
+}
+
+#[test]
+fn reparse_crate_replaces_edited_item() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn a() {}\nfn b() { 1 }\nfn c() {}\n".to_string();
+        let name = FileName::Custom("v1".to_string());
+        let parser = new_parser_from_crate_source_str(&psess, name, source);
+        let krate = unwrap_or_emit_fatal(parser).parse_crate_mod().unwrap();
+        let old_file = psess.source_map().lookup_source_file(krate.items[0].span.lo());
+
+        let edit = 19..20;
+        assert_eq!(&old_file.src.as_ref().unwrap()[edit.clone()], "1");
+        let name = FileName::Custom("v2".to_string());
+        let krate = reparse_crate(&psess, krate, &old_file, edit, "2 + 3", name).unwrap();
+
+        let snippets: Vec<_> = krate
+            .items
+            .iter()
+            .map(|item| psess.source_map().span_to_snippet(item.span).unwrap())
+            .collect();
+        assert_eq!(snippets, ["fn a() {}", "fn b() { 2 + 3 }", "fn c() {}"]);
+        let new_file = psess.source_map().lookup_source_file(krate.items[0].span.lo());
+        assert_eq!(new_file.name, FileName::Custom("v2".to_string()));
+    });
+}
+
+#[test]
+fn reparse_crate_parses_all_when_edit_changes_lexing() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn a() {}\nfn b() { 1 }\nfn c() {}\n// \"#; }\n".to_string();
+        let name = FileName::Custom("v1".to_string());
+        let parser = new_parser_from_crate_source_str(&psess, name, source);
+        let krate = unwrap_or_emit_fatal(parser).parse_crate_mod().unwrap();
+        let old_file = psess.source_map().lookup_source_file(krate.items[0].span.lo());
+
+        // The raw string goes on to the comment at the end, past `fn b`.
+        let name = FileName::Custom("v2".to_string());
+        let krate = reparse_crate(&psess, krate, &old_file, 19..20, "r#\"", name).unwrap();
+
+        let snippets: Vec<_> = krate
+            .items
+            .iter()
+            .map(|item| psess.source_map().span_to_snippet(item.span).unwrap())
+            .collect();
+        assert_eq!(snippets, ["fn a() {}", "fn b() { r#\" }\nfn c() {}\n// \"#; }"]);
+        assert!(psess.dcx().has_errors().is_none());
+    });
+}
+
+#[test]
+fn lexer_tokenize_collects_errors() {
+    create_default_session_globals_then(|| {
+        let source = "let s = \"a\" ¤ 1;)";
+        let mut errors = vec![];
+        let tokens: Vec<_> = crate::lexer::tokenize(source, &mut errors).collect();
+        let texts: Vec<_> = tokens.iter().map(|(_, range)| &source[range.clone()]).collect();
+        assert_eq!(texts, ["let", "s", "=", "\"a\"", "1", ";", ")"]);
+        assert_matches!(tokens[3].0.kind, token::Literal(token::Lit { kind: token::Str, .. }));
+
+        let [error] = &errors[..] else { panic!("expected one error, found {errors:?}") };
+        assert_eq!(error.message, "unknown start of token: \\u{a4}");
+        assert_eq!(&source[error.range.clone()], "¤");
+    });
+}
+
+#[test]
+fn lexer_relex_region_matches_full_lex() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let old = "fn a() {}\nfn b() { 1 + 2 }\nfn c() {}\nfn d() {}\n";
+        let start_pos = BytePos(0);
+        let lex = |src| {
+            let stream = lex_token_trees(&psess, src, start_pos, None, FrontmatterAllowed::Yes);
+            unwrap_or_emit_fatal(stream)
+        };
+        let old_stream = lex(old);
+        let edit = |range: std::ops::Range<usize>, text: &str| {
+            let mut src = old.to_string();
+            src.replace_range(range.clone(), text);
+            let relexed =
+                crate::lexer::relex_region(&psess, &old_stream, &src, start_pos, range, text.len());
+            assert_eq!(unwrap_or_emit_fatal(relexed), lex(&src));
+        };
+
+        // Within `b`, which is relexed with `a` and `c`, and `d` is moved.
+        let one = old.find('1').unwrap();
+        edit(one..one + 1, "10 * x");
+        // Removing `c`.
+        let c = old.find("fn c").unwrap();
+        edit(c..c + "fn c() {}\n".len(), "");
+        // At the start, where all of the file is relexed.
+        edit(0..2, "const fn");
+    });
+}
+
+#[test]
+fn lexer_lex_in_chunks_matches_full_lex() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let start_pos = BytePos(0);
+        let in_chunks = |src, chunk_len| {
+            crate::lexer::lex_token_trees_in_chunks(
+                &psess,
+                src,
+                start_pos,
+                chunk_len,
+                FrontmatterAllowed::Yes,
+            )
+        };
+        let src = "#!/usr/bin/env rustc\nfn a() {}\nconst B: u8 = 1+1u8;\n\
+                   static C: &str = \"c d\";\n/// Docs.\nfn d<'a>() { x >>= b'x'; }\n\
+                   const E: &[u8] = br#\"e\"#;\n";
+        let lexed = lex_token_trees(&psess, src, start_pos, None, FrontmatterAllowed::Yes);
+        let lexed = unwrap_or_emit_fatal(lexed);
+        for chunk_len in [1, 8, 32] {
+            assert_eq!(in_chunks(src, chunk_len).as_ref(), Some(&lexed), "chunks of {chunk_len}");
+        }
+
+        // Unbalanced delimiters are left to lexing all of the file, which reports them. That is
+        // found before any chunk is lexed, so the frontmatter isn't gated twice.
+        assert!(in_chunks("fn a() {\n", 1).is_none());
+        assert!(in_chunks("---\n---\nfn a() {}\nfn b() { (] }\n", 1).is_none());
+        assert!(!psess.gated_spans.spans.borrow().contains_key(&sym::frontmatter));
+    });
+}
+
+#[test]
+fn cst_frontmatter() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "#!/usr/bin/env cargo\n\n--- cargo\n[dependencies]\nregex = \"1\"\n---\n\n\
+                      fn main() {}\n";
+        let name = FileName::Custom("frontmatter".to_string());
+        let file = psess.source_map().new_source_file(name, source.to_string());
+        let frontmatter = crate::cst::frontmatter(&file).unwrap();
+        assert_eq!(frontmatter.info, "cargo");
+        assert_eq!(frontmatter.body, "[dependencies]\nregex = \"1\"\n");
+        let sm = psess.source_map();
+        assert_eq!(sm.span_to_snippet(frontmatter.body_span).unwrap(), frontmatter.body);
+        let block = sm.span_to_snippet(frontmatter.span).unwrap();
+        assert!(block.starts_with("--- cargo\n") && block.ends_with("\"1\"\n---"));
+
+        // The parser doesn't see the frontmatter, but it is gated, and kept as trivia.
+        let (stream, trivia) = unwrap_or_emit_fatal(crate::cst::lex_with_trivia(&psess, &file));
+        assert_eq!(crate::cst::to_source(&file, &stream, &trivia), source);
+        let kinds: Vec<_> = trivia.iter().map(|trivia| trivia.kind).collect();
+        assert_eq!(
+            &kinds[..3],
+            [TriviaKind::Shebang, TriviaKind::Whitespace, TriviaKind::Frontmatter]
+        );
+        let mut parser = Parser::new(&psess, stream, None);
+        assert_eq!(parser.parse_crate_mod().unwrap().items.len(), 1);
+        let gated_spans = psess.gated_spans.spans.borrow();
+        assert_eq!(gated_spans[&sym::frontmatter], [frontmatter.span]);
+    });
+}
+
+#[test]
+fn source_str_to_stream_keeps_frontmatter() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        // Only files can start with frontmatter, so in a snippet this is just a lot of `-`.
+        let source = "--- cargo\n---\nfn main() {}\n".to_string();
+        let name = FileName::Custom("snippet".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        assert_matches!(
+            stream.trees().next(),
+            Some(TokenTree::Token(Token { kind: token::BinOp(token::Minus), .. }, _))
+        );
+        assert!(!psess.gated_spans.spans.borrow().contains_key(&sym::frontmatter));
+    });
+}
+
+#[test]
+fn cst_raw_tokens() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "#!/usr/bin/env rustc\n// one\nx -= 1;\n";
+        let name = FileName::Custom("raw".to_string());
+        let file = psess.source_map().new_source_file(name, source.to_string());
+        let (stream, raw) = unwrap_or_emit_fatal(crate::cst::lex_raw(&psess, &file));
+        assert_eq!(pprust::tts_to_string(&stream), "x -= 1;");
+
+        let texts: Vec<_> = raw.iter().map(|token| &source[token.range.clone()]).collect();
+        assert_eq!(texts.concat(), source);
+        assert_eq!(
+            texts,
+            ["#!/usr/bin/env rustc", "\n", "// one", "\n", "x", " ", "-", "=", " ", "1", ";", "\n"]
+        );
+        assert_eq!(raw[0].kind, RawTokenKind::Shebang);
+        let comment = rustc_lexer::TokenKind::LineComment { doc_style: None };
+        assert_eq!(raw[2].kind, RawTokenKind::Token(comment));
+    });
+}
+
+#[test]
+fn cst_shebang() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        // Each source needs a name of its own, the source map hands out the file it already has
+        // for a name otherwise.
+        let file = |source: &str| {
+            let name = FileName::anon_source_code(source);
+            psess.source_map().new_source_file(name, source.to_string())
+        };
+        let file_with_shebang = file("#!/usr/bin/env -S cargo +nightly -Zscript\r\nfn main() {}\n");
+        let (text, span) = crate::cst::shebang(&file_with_shebang).unwrap();
+        assert_eq!(text, "#!/usr/bin/env -S cargo +nightly -Zscript");
+        assert_eq!(psess.source_map().span_to_snippet(span).unwrap(), text);
+
+        assert_eq!(crate::cst::shebang(&file("#![no_std]\n")), None);
+        assert_eq!(crate::cst::shebang(&file("fn main() {}\n")), None);
+    });
+}
+
+#[test]
+fn cst_round_trip() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "#!/usr/bin/env rust\n// one\nfn f( x: u8 ) /* two */ {\n    /// three\n    \
+                      x >>= 1;\n}\n";
+        let name = FileName::Custom("cst".to_string());
+        let file = psess.source_map().new_source_file(name, source.to_string());
+        let (stream, trivia) = unwrap_or_emit_fatal(crate::cst::lex_with_trivia(&psess, &file));
+        assert_eq!(crate::cst::to_source(&file, &stream, &trivia), source);
+
+        let kinds: Vec<_> = trivia.iter().map(|trivia| trivia.kind).collect();
+        assert_eq!(
+            &kinds[..3],
+            [TriviaKind::Shebang, TriviaKind::Whitespace, TriviaKind::LineComment]
+        );
+        let comment = trivia.iter().find(|trivia| trivia.kind == TriviaKind::BlockComment).unwrap();
+        let lo = comment.span.lo() - BytePos(1);
+        let hi = comment.span.hi() + BytePos(1);
+        assert_eq!(trivia.between(lo, hi).len(), 3);
+    });
+}
+
+#[test]
+fn source_str_to_stream_keeps_comments() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "// one\nlet x = /* two */ 1; /// three\n";
+        let name = FileName::Custom("comments".to_string());
+        let (stream, trivia) = unwrap_or_emit_fatal(source_str_to_stream_with_comments(
+            &psess,
+            name,
+            source.to_string(),
+        ));
+        // The doc comment is a token, the others aren't.
+        assert_eq!(pprust::tts_to_string(&stream), "let x = 1; /// three");
+        let comments: Vec<_> = trivia
+            .comments()
+            .map(|comment| psess.source_map().span_to_snippet(comment.span).unwrap())
+            .collect();
+        assert_eq!(comments, ["// one", "/* two */"]);
+
+        // The block comment is between the `=` and the `1`.
+        let mut tokens = stream.trees();
+        let Some(TokenTree::Token(eq, _)) = tokens.nth(2) else { panic!("expected `=`") };
+        let Some(TokenTree::Token(one, _)) = tokens.next() else { panic!("expected `1`") };
+        let between = trivia.between(eq.span.hi(), one.span.lo());
+        assert_eq!(between.iter().filter(|trivia| trivia.kind.is_comment()).count(), 1);
+    });
+}
+
+#[test]
+fn outline_crate_finds_items() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "
+            #![allow(dead_code)]
+            use std::fmt;
+            /// Doc.
+            #[derive(Debug)]
+            pub(crate) struct S(u8);
+            const fn f() -> u8 { let s = S(1); s.0 }
+            static X: [u8; 2] = { [0; 2] };
+            impl fmt::Display for S {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { todo!() }
+            }
+            mod m { pub unsafe extern \"C\" fn g() {} }
+            thread_local! { static Y: u8 = 0; }
+            macro_rules! mac { () => {}; }
+        ";
+        let stream = string_to_stream(source.to_string());
+        let items: Vec<_> = outline_crate(&psess, stream)
+            .into_iter()
+            .map(|item| (item.kind, item.name.map(|name| name.to_string()), item.depth))
+            .collect();
+        let item = |kind, name: Option<&str>, depth| (kind, name.map(str::to_string), depth);
+        assert_eq!(
+            items,
+            [
+                item(OutlineItemKind::Use, None, 0),
+                item(OutlineItemKind::Struct, Some("S"), 0),
+                item(OutlineItemKind::Fn, Some("f"), 0),
+                item(OutlineItemKind::Static, Some("X"), 0),
+                item(OutlineItemKind::Impl, None, 0),
+                item(OutlineItemKind::Fn, Some("fmt"), 1),
+                item(OutlineItemKind::Mod, Some("m"), 0),
+                item(OutlineItemKind::Fn, Some("g"), 1),
+                item(OutlineItemKind::MacCall, Some("thread_local"), 0),
+                item(OutlineItemKind::MacroDef, Some("mac"), 0),
+            ]
+        );
+    });
+}
+
+#[test]
+fn parse_crate_mod_lazy_bodies_skips_bodies() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn a() { 1 }\nfn b() { #![allow(x)] 2 }\nimpl S { fn c(&self) { self.0 } }\n\
+                      fn d() { //! Docs.\n 3 }\n";
+        let (krate, bodies) =
+            string_to_parser(&psess, source.to_string()).parse_crate_mod_lazy_bodies().unwrap();
+        assert_eq!(krate.items.len(), 4);
+        assert_eq!(bodies.len(), 2);
+
+        let ast::ItemKind::Fn(f) = &krate.items[0].kind else { panic!("not a function") };
+        let placeholder = f.body.as_ref().unwrap();
+        assert!(placeholder.stmts.is_empty());
+        assert_eq!(placeholder.span, bodies[0].span);
+        let body = bodies[0].parse(&psess).unwrap();
+        assert_matches!(body.stmts[..], [ast::Stmt { kind: ast::StmtKind::Expr(_), .. }]);
+
+        let ast::ItemKind::Fn(f) = &krate.items[1].kind else { panic!("not a function") };
+        assert_eq!(f.body.as_ref().unwrap().stmts.len(), 1);
+        assert_eq!(krate.items[1].attrs.len(), 1);
+        bodies[1].parse(&psess).unwrap();
+
+        let ast::ItemKind::Fn(f) = &krate.items[3].kind else { panic!("not a function") };
+        assert_eq!(f.body.as_ref().unwrap().stmts.len(), 1);
+        assert_eq!(krate.items[3].attrs.len(), 1);
+    });
+}
+
+#[test]
+fn token_stream_cache_moves_spans() {
+    create_default_session_globals_then(|| {
+        let mut psess = psess();
+        psess.enable_token_stream_cache();
+        let source = "fn f(x: u8) { x }";
+        let lex = |name: &str| {
+            let name = FileName::Custom(name.to_string());
+            unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source.to_string(), None))
+        };
+        let first = lex("first");
+        let second = lex("second");
+        assert!(first.eq_unspanned(&second));
+        assert_eq!(psess.token_stream_cache.as_ref().unwrap().lock().len(), 1);
+
+        let sm = psess.source_map();
+        let last_span = |stream: &TokenStream| stream.trees().last().unwrap().span();
+        assert_eq!(sm.span_to_snippet(last_span(&second)).unwrap(), "{ x }");
+        let file = sm.lookup_source_file(last_span(&second).lo());
+        assert_eq!(file.name, FileName::Custom("second".to_string()));
+    });
+}
+
+#[test]
+fn new_parser_from_reader_reads_to_end() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let name = || FileName::Custom("generated".to_string());
+        let source: &[u8] = b"fn f() {}\nstruct S;";
+        let mut parser = unwrap_or_emit_fatal(new_parser_from_reader(&psess, name(), source));
+        let krate = parser.parse_crate_mod().unwrap();
+        assert_eq!(krate.items.len(), 2);
+
+        let source: &[u8] = b"fn \xff() {}";
+        let Err(errs) = new_parser_from_reader(&psess, name(), source) else {
+            panic!("parser created from source that isn't UTF-8");
+        };
+        assert_eq!(errs.len(), 1);
+        for err in errs {
+            err.cancel();
+        }
+    });
+}
+
+#[test]
+fn parser_stops_when_cancelled() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { let x = 1; }\nfn g() {}".to_string();
+        let name = FileName::Custom("cancel".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ParserOptions { cancel: Some(cancel.clone()), ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+
+        assert!(parser.parse_item(ForceCollect::No).unwrap().is_some());
+        assert!(!parser.is_cancelled());
+        cancel.store(true, Ordering::Relaxed);
+        let Err(err) = parser.parse_item(ForceCollect::No) else {
+            panic!("parsed an item after the parse was cancelled");
+        };
+        assert!(parser.is_cancelled());
+        err.cancel();
+    });
+}
+
+#[test]
+fn parser_stops_at_error_limit() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn a() { 1 + }\nfn b() { 2 + }\nfn c() { 3 + }\nfn d() {}".to_string();
+        let name = FileName::Custom("garbage".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let options = ParserOptions { max_errors: Some(2), ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+        let krate = parser.parse_crate_mod_recovering();
+        assert!(parser.error_limit_reached());
+        assert_eq!(krate.items.len(), 2);
+        // The two errors in the items, and the one that stopped the parse.
+        assert_eq!(psess.dcx().err_count(), 3);
+    });
+}
+
+#[test]
+fn parser_takes_zero_error_limit_as_one() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn a() {}\nfn b() { 2 + }\nfn c() {}".to_string();
+        let name = FileName::Custom("zero_limit".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let options = ParserOptions { max_errors: Some(0), ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+        let krate = parser.parse_crate_mod_recovering();
+        // `a` parses before any error, and the parse stops after the one in `b`.
+        assert_eq!(krate.items.len(), 2);
+        assert!(parser.error_limit_reached());
+    });
+}
+
+#[test]
+fn parser_reports_nesting_too_deep() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let parse = |depth: usize| {
+            let source = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+            let name = FileName::Custom(format!("nested_{depth}"));
+            let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+            let options = ParserOptions { max_nesting_depth: Some(16), ..Default::default() };
+            Parser::with_options(&psess, stream, options).parse_expr()
+        };
+        parse(8).unwrap();
+        let err = parse(32).unwrap_err();
+        // The parser gives up at the first `(` past the limit.
+        let lo = err.span.primary_span().unwrap().lo();
+        assert_eq!(psess.source_map().lookup_char_pos(lo).col.0, 16);
+        err.cancel();
+    });
+}
+
+#[test]
+fn deeply_nested_expr_grows_the_stack() {
+    // Test threads have a small stack, which this much nesting would overflow.
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let depth = 2_000;
+        let source = format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        let mut parser = string_to_parser(&psess, source);
+        let mut expr = parser.parse_expr().unwrap();
+        let mut found = 1;
+        while let ast::ExprKind::Array(mut exprs) = expr.into_inner().kind {
+            expr = exprs.pop().unwrap();
+            found += 1;
+        }
+        assert_eq!(found, depth + 1);
+    });
+}
+
+#[test]
+fn parse_observer_sees_items_stmts_and_mac_calls() {
+    #[derive(Default)]
+    struct Seen(RefCell<Vec<String>>);
+
+    impl ParseObserver for Seen {
+        fn item(&self, item: &ast::Item) {
+            self.0.borrow_mut().push(format!("item {}", item.ident));
+        }
+        fn stmt(&self, _stmt: &ast::Stmt) {
+            self.0.borrow_mut().push("stmt".to_string());
+        }
+        fn mac_call(&self, mac: &ast::MacCall, _span: Span) {
+            self.0.borrow_mut().push(format!("mac {}", pprust::path_to_string(&mac.path)));
+        }
+    }
+
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { let x: t!() = m!(); g(x); }".to_string();
+        let name = FileName::Custom("observed".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let seen = Lrc::new(Seen::default());
+        let options = ParserOptions { observer: Some(seen.clone()), ..Default::default() };
+        Parser::with_options(&psess, stream, options).parse_crate_mod().unwrap();
+        assert_eq!(*seen.0.borrow(), ["mac t", "mac m", "stmt", "stmt", "item f"]);
+    });
+}
+
+#[test]
+fn parser_records_recovery_events() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { let x = 1 let y = 2; }\n@ fn g() {}".to_string();
+        let name = FileName::Custom("broken".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let options = ParserOptions { record_recovery: true, ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+        let krate = parser.parse_crate_mod_recovering();
+        assert_eq!(krate.items.len(), 2);
+
+        let events = parser.take_recovery_events();
+        let sm = psess.source_map();
+        let kinds: Vec<_> = events.iter().map(|event| event.kind.clone()).collect();
+        assert_eq!(kinds, [RecoveryKind::InsertedToken(token::Semi), RecoveryKind::SkippedTokens]);
+        assert!(sm.span_to_prev_source(events[0].span).unwrap().ends_with("let x = 1"));
+        assert_eq!(sm.span_to_snippet(events[1].span).unwrap(), "@");
+        assert!(parser.take_recovery_events().is_empty());
+    });
+}
+
+#[test]
+fn strict_parse_stops_at_first_error() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { let x = 1 let y = 2; }\nfn g() { 3 + }\nfn h() {}".to_string();
+        let name = FileName::Custom("strict".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let options = ParserOptions { recovery: Recovery::Strict, ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+        let krate = parser.parse_crate_mod_recovering();
+        // Without recovery, the missing `;` fails the first function, and nothing comes after.
+        assert!(krate.items.is_empty());
+        assert_eq!(psess.dcx().err_count(), 1);
+    });
+}
+
+#[test]
+fn strict_parse_fails_at_first_emitted_error() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { pub let x = 1; }\nfn g() { 3 + }\nfn h() {}".to_string();
+        let name = FileName::Custom("strict_emitted".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let options = ParserOptions { recovery: Recovery::Strict, ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+        let krate = parser.parse_crate_mod_recovering();
+        // The misplaced `pub` is reported without failing the statement, but it still ends the
+        // parse, and nothing else is reported.
+        assert!(krate.items.is_empty());
+        assert_eq!(psess.dcx().err_count(), 1);
+    });
+}
+
+#[test]
+fn strict_recovery_set_after_creation_limits_errors() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { let x = 1 let y = 2; }\nfn g() {}".to_string();
+        let name = FileName::Custom("strict_later".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let mut parser = Parser::new(&psess, stream, None).recovery(Recovery::Strict);
+        let krate = parser.parse_crate_mod_recovering();
+        assert!(krate.items.is_empty());
+        assert!(parser.error_limit_reached());
+    });
+}
+
+#[test]
+fn parse_in_with_rest_returns_trailing_tokens() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let stream = string_to_stream("a, (b c) d".to_string());
+        let (ident, rest) =
+            parse_in_with_rest(&psess, stream, "test input", |p| p.parse_ident()).unwrap();
+        assert_eq!(ident.as_str(), "a");
+        assert_eq!(pprust::tts_to_string(&rest), ", (b c) d");
+
+        let stream = string_to_stream("(a b)".to_string());
+        let result = parse_in_with_rest(&psess, stream, "test input", |p| {
+            p.bump();
+            p.parse_ident()
+        });
+        result.unwrap_err().cancel();
+    });
+}
+
+#[test]
+fn speculate_rolls_back_on_failure() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let mut parser = string_to_parser(&psess, "a + b".to_string());
+        let ty = parser.speculate(|p| {
+            p.bump();
+            p.parse_ty()
+        });
+        assert!(ty.is_none());
+        assert_eq!(parser.token.ident().unwrap().0.as_str(), "a");
+
+        let checkpoint = parser.checkpoint();
+        parser.bump();
+        assert!(parser.eat(&token::BinOp(token::Plus)));
+        parser.rollback(checkpoint);
+        let expr = parser.speculate(|p| p.parse_expr()).unwrap();
+        assert_matches!(expr.kind, ast::ExprKind::Binary(..));
+        assert_eq!(parser.token, token::Eof);
+    });
+}
+
+#[test]
+fn rollback_restores_expected_tokens() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let mut parser = string_to_parser(&psess, "x".to_string());
+        assert!(!parser.eat(&token::Comma));
+        let checkpoint = parser.checkpoint();
+        // The checkpoint shares the expected tokens until the parser adds `;` to them.
+        assert!(!parser.eat(&token::Semi));
+        parser.rollback(checkpoint);
+
+        let err = parser.unexpected_any::<()>().unwrap_err();
+        let message = err.messages[0].0.as_str().unwrap().to_string();
+        err.cancel();
+        assert!(message.starts_with("expected `,`, found"), "{message}");
+    });
+}
+
+#[test]
+fn captured_tokens_skip_replaced_nodes() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "#[derive(Debug)] struct S { #[cfg(a)] x: u8, y: u8 }".to_string();
+        let name = FileName::Custom("capture".to_string());
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let options = ParserOptions { capture_cfg: true, ..Default::default() };
+        let mut parser = Parser::with_options(&psess, stream, options);
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+
+        // The field with `#[cfg]` and its trailing comma are replaced by a target, and the tokens
+        // after them are replayed from the end of the field.
+        let stream = item.tokens.as_ref().unwrap().to_attr_token_stream();
+        let [_, _, AttrTokenTree::Delimited(.., Delimiter::Brace, fields)] = &stream.0[..] else {
+            panic!("unexpected tokens: {stream:?}");
+        };
+        let [AttrTokenTree::AttrsTarget(target), rest @ ..] = &fields.0[..] else {
+            panic!("unexpected tokens: {fields:?}");
+        };
+        let rest: Vec<_> = rest
+            .iter()
+            .map(|tree| match tree {
+                AttrTokenTree::Token(token, _) => pprust::token_to_string(token).into_owned(),
+                tree => panic!("unexpected tree: {tree:?}"),
+            })
+            .collect();
+        assert_eq!(rest, ["y", ":", "u8"]);
+
+        assert_eq!(target.attrs.len(), 1);
+        // `x: u8,`
+        assert_eq!(target.tokens.to_attr_token_stream().0.len(), 4);
+    });
+}
+
+#[test]
+fn skip_token_collection() {
+    create_default_session_globals_then(|| {
+        let mut psess = psess();
+        psess.skip_token_collection = true;
+        let source = "#[my_attr] struct S; #[my_attr] struct T; \
+                      #[derive(Debug)] struct U { #[cfg(FALSE)] a: u8 } #[cfg_attr(x, y)] struct V;"
+            .to_string();
+        let mut parser = string_to_parser(&psess, source);
+
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+        assert!(item.tokens.is_none());
+        // Forced collection still collects.
+        let item = parser.parse_item(ForceCollect::Yes).unwrap().unwrap();
+        assert!(item.tokens.is_some());
+        // cfg_eval turns items with `#[derive]` and `#[cfg_attr]` back into tokens.
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+        assert!(item.tokens.is_some());
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+        assert!(item.tokens.is_some());
+
+        // So does its reparse, which is in "possible capture mode".
+        let mut parser = string_to_parser(&psess, "#[my_attr] struct W;".to_string());
+        parser.capture_cfg = true;
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+        assert!(item.tokens.is_some());
+    });
+}
+
+#[test]
+fn cfg_stripped_tokens_collect_cfg_items() {
+    create_default_session_globals_then(|| {
+        let mut psess = psess();
+        psess.enable_cfg_stripped_tokens();
+        let source = "#[cfg(FALSE)] fn f() {} fn g() {}".to_string();
+        let mut parser = string_to_parser(&psess, source);
+
+        // `f` may be removed by cfg-stripping, so its tokens are kept for that.
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+        assert!(item.tokens.is_some());
+        let item = parser.parse_item(ForceCollect::No).unwrap().unwrap();
+        assert!(item.tokens.is_none());
+    });
+}
+
+#[test]
+fn fake_token_streams_get_source_spans() {
+    create_default_session_globals_then(|| {
+        let mut psess = psess();
+        let name = FileName::Custom("remap".to_string());
+        // The pretty-printer leaves out the trailing comma.
+        let source = "#[inline]\nfn f(a: u8,) -> u8 {\n    a + 1\n}".to_string();
+        let stream = unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source, None));
+        let item = Parser::new(&psess, stream, None).parse_item(ForceCollect::No).unwrap().unwrap();
+        // Unless it is enabled, all the tokens get the span of the item.
+        let fake = fake_token_stream_for_item(&psess, &item);
+        assert!(fake.trees().all(|tree| tree.span() == item.span));
+        psess.remap_fake_token_spans = true;
+        let fake = fake_token_stream_for_item(&psess, &item);
+
+        fn check(psess: &ParseSess, stream: &TokenStream, count: &mut usize) {
+            let snippet = |span| psess.source_map().span_to_snippet(span).unwrap();
+            for tree in stream.trees() {
+                match tree {
+                    TokenTree::Token(token, _) => {
+                        assert_eq!(snippet(token.span), pprust::token_to_string(token));
+                    }
+                    TokenTree::Delimited(dspan, _, delim, inner) => {
+                        assert_eq!(
+                            snippet(dspan.open),
+                            pprust::token_kind_to_string(&token::OpenDelim(*delim))
+                        );
+                        check(psess, inner, count);
+                        assert_eq!(
+                            snippet(dspan.close),
+                            pprust::token_kind_to_string(&token::CloseDelim(*delim))
+                        );
+                    }
+                }
+                *count += 1;
+            }
+        }
+        let mut count = 0;
+        check(&psess, &fake, &mut count);
+        assert_eq!(count, 15);
+    });
+}
+
+#[test]
+fn round_trip_reports_lost_tokens() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let item = |source: &str| {
+            let name = FileName::Custom("round_trip".to_string());
+            let stream =
+                unwrap_or_emit_fatal(source_str_to_stream(&psess, name, source.to_string(), None));
+            Parser::new(&psess, stream, None).parse_item(ForceCollect::No).unwrap().unwrap()
+        };
+        assert_eq!(check_item_round_trip(&psess, &item("struct S(u8, u16);")), vec![]);
+
+        // The pretty-printer leaves out the trailing comma.
+        let item = item("fn f(a: u8,) {}");
+        let diffs = check_item_round_trip(&psess, &item);
+        let [RoundTripDiff::Lost(token::Comma, span)] = diffs[..] else { panic!("{diffs:?}") };
+        assert_eq!(psess.source_map().span_to_snippet(span).unwrap(), ",");
+        assert_eq!(span.lo(), item.span.lo() + BytePos(10));
+    });
+}
+
+#[test]
+fn parse_tree_json_dump() {
+    create_default_session_globals_then(|| {
+        let psess = psess();
+        let source = "fn f() { 1 + x }".to_string();
+        let krate = string_to_parser(&psess, source).parse_crate_mod().unwrap();
+        let expected = concat!(
+            r#"{"node":"crate","file":"bogofile","span":[0,16],"version":1,"children":["#,
+            r#"{"node":"item","kind":"Fn","span":[0,16],"children":["#,
+            r#"{"node":"ident","span":[3,4],"name":"f"},"#,
+            r#"{"node":"block","span":[7,16],"children":["#,
+            r#"{"node":"stmt","kind":"Expr","span":[9,14],"children":["#,
+            r#"{"node":"expr","kind":"Binary","span":[9,14],"op":"+","children":["#,
+            r#"{"node":"expr","kind":"Lit","span":[9,10],"text":"1"},"#,
+            r#"{"node":"expr","kind":"Path","span":[13,14],"children":["#,
+            r#"{"node":"path","span":[13,14],"children":["#,
+            r#"{"node":"ident","span":[13,14],"name":"x"}]}]}]}]}]}]}]}"#,
+            "\n",
+        );
+        assert_eq!(parse_tree_json(psess.source_map(), &krate), expected);
+    });
+}
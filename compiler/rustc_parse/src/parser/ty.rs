@@ -255,6 +255,27 @@ fn parse_ty_common(
         recover_return_sign: RecoverReturnSign,
         ty_generics: Option<&Generics>,
         recover_question_mark: RecoverQuestionMark,
+    ) -> PResult<'a, P<Ty>> {
+        self.nested("type", |this| {
+            this.parse_ty_common_(
+                allow_plus,
+                allow_c_variadic,
+                recover_qpath,
+                recover_return_sign,
+                ty_generics,
+                recover_question_mark,
+            )
+        })
+    }
+
+    fn parse_ty_common_(
+        &mut self,
+        allow_plus: AllowPlus,
+        allow_c_variadic: AllowCVariadic,
+        recover_qpath: RecoverQPath,
+        recover_return_sign: RecoverReturnSign,
+        ty_generics: Option<&Generics>,
+        recover_question_mark: RecoverQuestionMark,
     ) -> PResult<'a, P<Ty>> {
         let allow_qpath_recovery = recover_qpath == RecoverQPath::Yes;
         maybe_recover_from_interpolated_ty_qpath!(self, allow_qpath_recovery);
@@ -769,7 +790,9 @@ fn parse_path_start_ty(
         let path = self.parse_path_inner(PathStyle::Type, ty_generics)?;
         if self.eat(&token::Not) {
             // Macro invocation in type position
-            Ok(TyKind::MacCall(P(MacCall { path, args: self.parse_delim_args()? })))
+            let mac = P(MacCall { path, args: self.parse_delim_args()? });
+            self.observe(|observer| observer.mac_call(&mac, lo.to(self.prev_token.span)));
+            Ok(TyKind::MacCall(mac))
         } else if allow_plus == AllowPlus::Yes && self.check_plus() {
             // `Trait1 + Trait2 + 'a`
             self.parse_remaining_bounds_path(ThinVec::new(), path, lo, true)
@@ -1279,7 +1302,7 @@ fn recover_fn_trait_with_lifetime_params(
     }
 
     pub(super) fn check_lifetime(&mut self) -> bool {
-        self.expected_tokens.push(TokenType::Lifetime);
+        self.expected_tokens_mut().push(TokenType::Lifetime);
         self.token.is_lifetime()
     }
 
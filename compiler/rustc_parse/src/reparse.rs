@@ -0,0 +1,146 @@
+//! Reparsing a crate after an edit to its source, for tools like IDEs that keep the AST of a file
+//! around while it is being edited and don't want to parse all of it on every keystroke.
+
+use std::ops::Range;
+
+use rustc_ast::mut_visit::MutVisitor;
+use rustc_ast::{self as ast, token};
+use rustc_errors::Diag;
+use rustc_session::parse::ParseSess;
+use rustc_span::{BytePos, FileName, Pos, SourceFile, Span, SpanData};
+use thin_vec::ThinVec;
+
+use crate::lexer::{self, FrontmatterAllowed};
+use crate::new_parser_from_source_file;
+use crate::parser::{ForceCollect, Parser};
+
+/// Reparses `krate`, which was parsed from `old_file`, after the bytes `edit` of that file's
+/// source were replaced by `replacement`. The new source is added to the source map as `name`,
+/// which must not be the name of a file that is in there already, e.g. the old one's.
+///
+/// Only the top-level items that the edit touches, and whatever is between them, are lexed and
+/// parsed again. The other items are taken from `krate`, with their spans moved to the new file.
+/// An edit before the end of the first item, e.g. to the crate's inner attributes, makes it parse
+/// the whole file again, as does one that changes how the text after the edited items lexes. On
+/// failure, the errors must be consumed via `unwrap_or_emit_fatal`, `emit`, `cancel`, etc.,
+/// otherwise a panic will occur when they are dropped.
+pub fn reparse_crate<'a>(
+    psess: &'a ParseSess,
+    mut krate: ast::Crate,
+    old_file: &SourceFile,
+    edit: Range<usize>,
+    replacement: &str,
+    name: FileName,
+) -> Result<ast::Crate, Vec<Diag<'a>>> {
+    let old_src = old_file.src.as_ref().expect("cannot reparse a file without source");
+    let mut new_src = String::with_capacity(old_src.len() - edit.len() + replacement.len());
+    new_src.push_str(&old_src[..edit.start]);
+    new_src.push_str(replacement);
+    new_src.push_str(&old_src[edit.end..]);
+    let new_file = psess.source_map().new_source_file(name, new_src);
+
+    let old_len = old_file.source_len.to_usize();
+    let new_len = new_file.source_len.to_usize();
+    let ranges: Vec<_> = krate.items.iter().map(|item| item_range(old_file, item)).collect();
+
+    // The items to parse again are those from the first one that doesn't end before the edit,
+    // to the last one that doesn't start after it. An edit between two items touches neither.
+    let parse_all = || {
+        let file = new_file.clone();
+        let mut parser = new_parser_from_source_file(psess, file, FrontmatterAllowed::Yes)?;
+        parser.parse_crate_mod().map_err(|err| vec![err])
+    };
+    let first = ranges.iter().position(|range| range.end >= edit.start).unwrap_or(ranges.len());
+    if first == 0 {
+        return parse_all();
+    }
+    let end = ranges.iter().rposition(|range| range.start <= edit.end).map_or(0, |i| i + 1);
+    let end = end.max(first);
+
+    let lo = ranges[first - 1].end;
+    let hi = ranges.get(end).map_or(old_len, |range| range.start) + new_len - old_len;
+    let new_src = new_file.src.as_ref().unwrap();
+    // As in `relex_region`, the text to parse again has to lex the same on its own as it does in
+    // the file, which it doesn't when the edit opens a string or a comment that goes on past it.
+    if !lexer::lexes_alone(&new_src[lo..], hi - lo) {
+        return parse_all();
+    }
+    let pos = new_file.start_pos + BytePos::from_usize(lo);
+    let stream = lexer::lex_token_trees(psess, &new_src[lo..hi], pos, None, FrontmatterAllowed::No);
+    let stream = match stream {
+        Ok(stream) => stream,
+        Err(errs) => {
+            // The delimiters don't match up in the text, so they don't in the file either, and
+            // that is best reported for the whole file.
+            for err in errs {
+                err.cancel();
+            }
+            return parse_all();
+        }
+    };
+
+    let mut parser = Parser::new(psess, stream, None);
+    let mut items = ThinVec::new();
+    loop {
+        while parser.maybe_consume_incorrect_semicolon(items.last().map(|x| &**x)) {}
+        match parser.parse_item(ForceCollect::No).map_err(|err| vec![err])? {
+            Some(item) => items.push(item),
+            None => break,
+        }
+    }
+    if parser.token != token::Eof {
+        parser.unexpected().map_err(|err| vec![err])?;
+    }
+
+    let kept = krate.items.split_off(end);
+    krate.items.truncate(first);
+    krate.items.extend(items);
+    krate.items.extend(kept);
+    let mut mover = MoveSpans { old_file, new_file: &new_file, edit, old_len, new_len };
+    mover.visit_crate(&mut krate);
+    if end == ranges.len() {
+        // The crate's inner span ended in what was parsed again, so it ends elsewhere now.
+        if let Some(last) = krate.items.last() {
+            krate.spans.inner_span = krate.spans.inner_span.with_hi(last.span.hi());
+        }
+    }
+    Ok(krate)
+}
+
+/// The bytes of `old_file` that `item` covers, including its outer attributes.
+fn item_range(old_file: &SourceFile, item: &ast::Item) -> Range<usize> {
+    let span = item.attrs.iter().fold(item.span, |span, attr| span.to(attr.span));
+    let lo = old_file.relative_position(span.lo()).to_usize();
+    let hi = old_file.relative_position(span.hi()).to_usize();
+    lo..hi
+}
+
+/// Moves the spans of what was kept from the old file to the same text in the new one. Spans in
+/// the new items are in the new file already, so they are left alone.
+struct MoveSpans<'a> {
+    old_file: &'a SourceFile,
+    new_file: &'a SourceFile,
+    edit: Range<usize>,
+    old_len: usize,
+    new_len: usize,
+}
+
+impl MoveSpans<'_> {
+    fn move_pos(&self, pos: BytePos) -> BytePos {
+        let pos = self.old_file.relative_position(pos).to_usize();
+        let pos = if pos >= self.edit.end { pos + self.new_len - self.old_len } else { pos };
+        self.new_file.start_pos + BytePos::from_usize(pos)
+    }
+}
+
+impl MutVisitor for MoveSpans<'_> {
+    // Macro calls keep their arguments as tokens, and those have spans to move too.
+    const VISIT_TOKENS: bool = true;
+
+    fn visit_span(&mut self, span: &mut Span) {
+        let SpanData { lo, hi, ctxt, parent } = span.data();
+        if !span.is_dummy() && self.old_file.contains(lo) {
+            *span = Span::new(self.move_pos(lo), self.move_pos(hi), ctxt, parent);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Checking that items come out of pretty-printing as the tokens that they were parsed from, for
+//! `-Z verify-parse-roundtrip`. Where they don't, the pretty-printer either loses something of
+//! the source, or writes the same code differently, e.g. with a trailing comma less.
+
+use std::ops::Range;
+
+use rustc_ast as ast;
+use rustc_ast::token::TokenKind;
+use rustc_ast_pretty::pprust;
+use rustc_session::parse::ParseSess;
+use rustc_span::{Span, DUMMY_SP};
+
+use crate::{errors, fake_spans};
+
+/// A difference between the tokens of the source of an item and those of its pretty-printed
+/// form, see [`check_item_round_trip`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoundTripDiff {
+    /// A token of the source, with its span, that the printed form doesn't have.
+    Lost(TokenKind, Span),
+    /// A token of the printed form that the source doesn't have, with the span of the source
+    /// token that it comes before, or of the item if it comes after all of them.
+    Added(TokenKind, Span),
+    /// The printed form doesn't lex, with the message of the first error of the lexer.
+    Unlexable(String),
+    /// The printed form differs from the source in too many tokens to line them up.
+    TooDifferent,
+}
+
+/// Pretty-prints `item`, lexes the result, and compares its tokens to those of the source of
+/// `item` by kind, in order. Items whose source isn't available, e.g. because they come from a
+/// macro expansion, have no differences. Like other lexing, this has to run with session globals
+/// set.
+pub fn check_item_round_trip(psess: &ParseSess, item: &ast::Item) -> Vec<RoundTripDiff> {
+    let source = fake_spans::source_tokens(psess, &fake_spans::item_regions(item));
+    if source.is_empty() {
+        return Vec::new();
+    }
+    let mut errors = Vec::new();
+    let printed: Vec<TokenKind> =
+        fake_spans::glued_tokens(&pprust::item_to_string(item), &mut errors, |_| DUMMY_SP)
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+    if let Some(error) = errors.into_iter().next() {
+        return vec![RoundTripDiff::Unlexable(error.message)];
+    }
+    let Some(source_indices) = fake_spans::line_up(&printed, &source) else {
+        return vec![RoundTripDiff::TooDifferent];
+    };
+
+    let lost = |range: Range<usize>| {
+        source[range].iter().map(|token| RoundTripDiff::Lost(token.kind.clone(), token.span))
+    };
+    let mut diffs = Vec::new();
+    let mut next_source = 0;
+    for (kind, source_index) in printed.into_iter().zip(source_indices) {
+        match source_index {
+            Some(i) => {
+                diffs.extend(lost(next_source..i));
+                next_source = i + 1;
+            }
+            None => {
+                let span = source.get(next_source).map_or(item.span, |token| token.span);
+                diffs.push(RoundTripDiff::Added(kind, span));
+            }
+        }
+    }
+    diffs.extend(lost(next_source..source.len()));
+    diffs
+}
+
+/// Runs [`check_item_round_trip`] on each item of `krate`, and warns about the differences.
+pub fn verify_crate_round_trip(psess: &ParseSess, krate: &ast::Crate) {
+    for item in &krate.items {
+        for diff in check_item_round_trip(psess, item) {
+            match diff {
+                RoundTripDiff::Lost(kind, span) => {
+                    let token = pprust::token_kind_to_string(&kind).into_owned();
+                    psess.dcx().emit_warn(errors::RoundTripLostToken { span, token })
+                }
+                RoundTripDiff::Added(kind, span) => {
+                    let token = pprust::token_kind_to_string(&kind).into_owned();
+                    psess.dcx().emit_warn(errors::RoundTripAddedToken { span, token })
+                }
+                RoundTripDiff::Unlexable(message) => {
+                    psess.dcx().emit_warn(errors::RoundTripUnlexable { span: item.span, message })
+                }
+                RoundTripDiff::TooDifferent => {
+                    psess.dcx().emit_warn(errors::RoundTripTooDifferent { span: item.span })
+                }
+            }
+        }
+    }
+}
@@ -2743,6 +2743,7 @@ fn parse_pretty(early_dcx: &EarlyDiagCtxt, unstable_opts: &UnstableOptions) -> O
         "expanded,hygiene" => Source(PpSourceMode::ExpandedHygiene),
         "ast-tree" => AstTree,
         "ast-tree,expanded" => AstTreeExpanded,
+        "parse-tree-json" => ParseTreeJson,
         "hir" => Hir(PpHirMode::Normal),
         "hir,identified" => Hir(PpHirMode::Identified),
         "hir,typed" => Hir(PpHirMode::Typed),
@@ -2755,9 +2756,9 @@ fn parse_pretty(early_dcx: &EarlyDiagCtxt, unstable_opts: &UnstableOptions) -> O
         name => early_dcx.early_fatal(format!(
             "argument to `unpretty` must be one of `normal`, `identified`, \
                             `expanded`, `expanded,identified`, `expanded,hygiene`, \
-                            `ast-tree`, `ast-tree,expanded`, `hir`, `hir,identified`, \
-                            `hir,typed`, `hir-tree`, `thir-tree`, `thir-flat`, `mir`, `stable-mir`, or \
-                            `mir-cfg`; got {name}"
+                            `ast-tree`, `ast-tree,expanded`, `parse-tree-json`, `hir`, \
+                            `hir,identified`, `hir,typed`, `hir-tree`, `thir-tree`, `thir-flat`, \
+                            `mir`, `stable-mir`, or `mir-cfg`; got {name}"
         )),
     };
     debug!("got unpretty option: {first:?}");
@@ -2921,6 +2922,8 @@ pub enum PpMode {
     AstTree,
     /// `-Zunpretty=ast-tree,expanded`
     AstTreeExpanded,
+    /// `-Zunpretty=parse-tree-json`
+    ParseTreeJson,
     /// Options that print the HIR, i.e. `-Zunpretty=hir`
     Hir(PpHirMode),
     /// `-Zunpretty=hir-tree`
@@ -2942,7 +2945,7 @@ pub fn needs_ast_map(&self) -> bool {
         use PpMode::*;
         use PpSourceMode::*;
         match *self {
-            Source(Normal | Identified) | AstTree => false,
+            Source(Normal | Identified) | AstTree | ParseTreeJson => false,
 
             Source(Expanded | ExpandedIdentified | ExpandedHygiene)
             | AstTreeExpanded
@@ -2958,7 +2961,7 @@ pub fn needs_ast_map(&self) -> bool {
     pub fn needs_hir(&self) -> bool {
         use PpMode::*;
         match *self {
-            Source(_) | AstTree | AstTreeExpanded => false,
+            Source(_) | AstTree | AstTreeExpanded | ParseTreeJson => false,
 
             Hir(_) | HirTree | ThirTree | ThirFlat | Mir | MirCFG | StableMir => true,
         }
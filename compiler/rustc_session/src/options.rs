@@ -1979,6 +1979,9 @@ pub(crate) fn parse_mir_include_spans(slot: &mut MirIncludeSpans, v: Option<&str
         "whether ELF relocations can be relaxed"),
     remap_cwd_prefix: Option<PathBuf> = (None, parse_opt_pathbuf, [TRACKED],
         "remap paths under the current working directory to this path prefix"),
+    remap_fake_token_spans: bool = (false, parse_bool, [TRACKED],
+        "give the tokens that proc macros get for pretty-printed items the spans of the source \
+        tokens they match (default: no)"),
     remap_path_scope: RemapPathScopeComponents = (RemapPathScopeComponents::all(), parse_remap_path_scope, [TRACKED],
         "remap path scope (default: all)"),
     remark_dir: Option<PathBuf> = (None, parse_opt_pathbuf, [UNTRACKED],
@@ -2050,6 +2053,9 @@ pub(crate) fn parse_mir_include_spans(slot: &mut MirIncludeSpans, v: Option<&str
     #[rustc_lint_opt_deny_field_access("use `Session::stack_protector` instead of this field")]
     stack_protector: StackProtector = (StackProtector::None, parse_stack_protector, [TRACKED],
         "control stack smash protection strategy (`rustc --print stack-protector-strategies` for details)"),
+    stack_segment_size: Option<usize> = (None, parse_opt_number, [UNTRACKED],
+        "size in bytes of the stacks the compiler adds when deeply nested code needs more stack \
+        (default: 1MiB)"),
     staticlib_allow_rdylib_deps: bool = (false, parse_bool, [TRACKED],
         "allow staticlibs to have rust dylib dependencies"),
     staticlib_prefer_dynamic: bool = (false, parse_bool, [TRACKED],
@@ -2125,6 +2131,7 @@ pub(crate) fn parse_mir_include_spans(slot: &mut MirIncludeSpans, v: Option<&str
         `expanded,hygiene` (with internal representations),
         `ast-tree` (raw AST before expansion),
         `ast-tree,expanded` (raw AST after expansion),
+        `parse-tree-json` (AST before expansion, as JSON),
         `hir` (the HIR), `hir,identified`,
         `hir,typed` (HIR with types for each node),
         `hir-tree` (dump the raw HIR),
@@ -2151,6 +2158,9 @@ pub(crate) fn parse_mir_include_spans(slot: &mut MirIncludeSpans, v: Option<&str
     #[rustc_lint_opt_deny_field_access("use `Session::verify_llvm_ir` instead of this field")]
     verify_llvm_ir: bool = (false, parse_bool, [TRACKED],
         "verify LLVM IR (default: no)"),
+    verify_parse_roundtrip: bool = (false, parse_bool, [UNTRACKED],
+        "warn where the tokens of pretty-printed items differ from those of their source \
+        (default: no)"),
     virtual_function_elimination: bool = (false, parse_bool, [TRACKED],
         "enables dead virtual function elimination optimization. \
         Requires `-Clto[=[fat,yes]]`"),
@@ -5,6 +5,8 @@
 
 use rustc_ast::attr::AttrIdGenerator;
 use rustc_ast::node_id::NodeId;
+use rustc_ast::tokenstream::{LazyAttrTokenStream, TokenStream};
+use rustc_ast::AttrVec;
 use rustc_data_structures::fx::{FxHashMap, FxIndexMap, FxIndexSet};
 use rustc_data_structures::sync::{AppendOnlyVec, Lock, Lrc};
 use rustc_errors::emitter::{stderr_destination, HumanEmitter, SilentEmitter};
@@ -16,7 +18,7 @@
 use rustc_span::edition::Edition;
 use rustc_span::hygiene::ExpnId;
 use rustc_span::source_map::{FilePathMapping, SourceMap};
-use rustc_span::{Span, Symbol};
+use rustc_span::{BytePos, SourceFileHash, Span, Symbol};
 
 use crate::config::{Cfg, CheckCfg};
 use crate::errors::{
@@ -232,6 +234,36 @@ pub struct ParseSess {
     proc_macro_quoted_spans: AppendOnlyVec<Span>,
     /// Used to generate new `AttrId`s. Every `AttrId` is unique.
     pub attr_id_generator: AttrIdGenerator,
+    /// Token streams lexed from source strings, if enabled, see `enable_token_stream_cache`.
+    pub token_stream_cache: Option<TokenStreamCache>,
+    /// Whether parsers skip collecting the tokens of AST nodes that only proc macro attributes
+    /// would need. This is for drivers that never expand proc macros, e.g. analysis tools, which
+    /// would pay for the tokens for nothing. Tokens that builtin expansion needs, i.e. those of
+    /// nodes with `#[derive]`, `#[cfg_eval]`, `#[cfg]` or `#[cfg_attr]` and those collected while
+    /// cfg_eval reparses a node, are still collected, as are those that the parser is forced to
+    /// collect, e.g. those of macro fragments.
+    pub skip_token_collection: bool,
+    /// The nodes that cfg-stripping removed, if enabled, see `enable_cfg_stripped_tokens`.
+    pub cfg_stripped_nodes: Option<Lock<Vec<CfgStrippedNode>>>,
+    /// Whether the tokens that are made by pretty-printing an item, when its own tokens aren't
+    /// available, get the spans of the source tokens that they match, rather than all getting the
+    /// span of the item. Lining them up costs a diff of the two per item, so it is opt-in.
+    pub remap_fake_token_spans: bool,
+}
+
+/// Token streams keyed by the hash of the source they were lexed from and the span that was
+/// given for all their tokens, if any. The position of the file they were lexed from is kept
+/// too, to move the spans of the tokens to another file with the same source.
+pub type TokenStreamCache = Lock<FxHashMap<(SourceFileHash, Option<Span>), (BytePos, TokenStream)>>;
+
+/// A node that cfg-stripping removed, with the tokens it was parsed from.
+#[derive(Clone, Debug)]
+pub struct CfgStrippedNode {
+    /// The attributes of the node, with `#[cfg_attr]`s expanded, including the `#[cfg]`s that
+    /// removed it.
+    pub attrs: AttrVec,
+    /// The tokens of the node, without its outer attributes.
+    pub tokens: LazyAttrTokenStream,
 }
 
 impl ParseSess {
@@ -266,6 +298,10 @@ pub fn with_dcx(dcx: DiagCtxt, source_map: Lrc<SourceMap>) -> Self {
             assume_incomplete_release: false,
             proc_macro_quoted_spans: Default::default(),
             attr_id_generator: AttrIdGenerator::new(),
+            token_stream_cache: None,
+            skip_token_collection: false,
+            cfg_stripped_nodes: None,
+            remap_fake_token_spans: false,
         }
     }
 
@@ -291,6 +327,24 @@ pub fn with_silent_emitter(
         ParseSess::with_dcx(dcx, sm)
     }
 
+    /// Makes `rustc_parse::source_str_to_stream` keep the tokens it lexes, and return them again
+    /// for the same source instead of lexing it anew, e.g. for the items that are printed and
+    /// lexed again to pass them to proc macros. Only sources that lex without errors are kept.
+    /// Tokens returned from the cache skip the side effects of lexing, like buffering lints and
+    /// recording raw identifiers in `raw_identifier_spans`.
+    pub fn enable_token_stream_cache(&mut self) {
+        self.token_stream_cache.get_or_insert_with(Default::default);
+    }
+
+    /// Makes parsers collect the tokens of the nodes with `#[cfg]` or `#[cfg_attr]` attributes,
+    /// and cfg-stripping keep the nodes it removes in `cfg_stripped_nodes`, so that tools can look
+    /// at the code that the current configuration leaves out, without parsing it again for each
+    /// configuration. Nodes that can't hold tokens, or were parsed before this was enabled, aren't
+    /// kept.
+    pub fn enable_cfg_stripped_tokens(&mut self) {
+        self.cfg_stripped_nodes.get_or_insert_with(Default::default);
+    }
+
     #[inline]
     pub fn source_map(&self) -> &SourceMap {
         &self.source_map
@@ -1068,6 +1068,7 @@ pub fn build_session(
 
     let mut psess = ParseSess::with_dcx(dcx, source_map);
     psess.assume_incomplete_release = sopts.unstable_opts.assume_incomplete_release;
+    psess.remap_fake_token_spans = sopts.unstable_opts.remap_fake_token_spans;
 
     let host_triple = config::host_triple();
     let target_triple = sopts.target_triple.triple();
@@ -1131,6 +1131,25 @@ fn print_to_buffer_if_capture_used(args: fmt::Arguments<'_>) -> bool {
         }) == Ok(Some(()))
 }
 
+/// Appends `msg` to the current thread's capture buffer, if there is one and
+/// that is possible without blocking or allocating. This is for messages
+/// from signal handlers, which can't risk either. Returns whether it did.
+pub(crate) fn try_append_to_capture(msg: &[u8]) -> bool {
+    OUTPUT_CAPTURE_USED.load(Ordering::Relaxed)
+        && OUTPUT_CAPTURE.try_with(|s| {
+            let w = s.take()?;
+            let appended = match w.try_lock() {
+                Ok(mut buf) if buf.capacity() - buf.len() >= msg.len() => {
+                    buf.extend_from_slice(msg);
+                    true
+                }
+                _ => false,
+            };
+            s.set(Some(w));
+            Some(appended)
+        }) == Ok(Some(true))
+}
+
 /// Used by impl Termination for Result to print error after `main` or a test
 /// has returned. Should avoid panicking, although we can't help it if one of
 /// the Display impls inside args decides to.
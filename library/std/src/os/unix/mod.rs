@@ -89,8 +89,29 @@ mod platform {
 pub mod net;
 pub mod process;
 pub mod raw;
+pub mod stack_overflow;
 pub mod thread;
 
+/// Returns the size of a page of memory, in bytes.
+///
+/// This is the granularity of `mmap` and `mprotect`, and the value the
+/// standard library itself uses, e.g. to size the guard pages of threads.
+/// It is queried once with `sysconf(_SC_PAGESIZE)` and cached afterwards.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(unix_page_size)]
+///
+/// let page_size = std::os::unix::page_size();
+/// assert!(page_size.is_power_of_two());
+/// ```
+#[must_use]
+#[unstable(feature = "unix_page_size", issue = "none")]
+pub fn page_size() -> usize {
+    crate::sys::os::page_size()
+}
+
 /// A prelude for conveniently writing platform-specific code.
 ///
 /// Includes all extension traits, and some important type definitions.
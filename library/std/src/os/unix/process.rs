@@ -528,3 +528,52 @@ fn from(fd: OwnedFd) -> process::ChildStderr {
 pub fn parent_id() -> u32 {
     crate::sys::os::getppid()
 }
+
+/// Redirects the messages the runtime prints right before aborting the
+/// process to `fd`.
+///
+/// When the standard library gives up on the process, e.g. because a thread
+/// overflowed its stack, it prints the reason to stderr and aborts. Daemons
+/// often have stderr pointed at `/dev/null`, so the reason gets lost. This
+/// function makes those messages go to `fd` instead, e.g. a log file, a
+/// socket of the system journal or a pipe to a supervising process. Panic
+/// messages are not affected, see [`panic::set_hook`] for those.
+///
+/// The messages are written with plain `write(2)` calls, so that they can be
+/// sent from signal handlers. For the same reason, `fd` stays open for the
+/// rest of the process, even if a later call replaces it: a thread may still
+/// be writing its message to it.
+///
+/// [`panic::set_hook`]: crate::panic::set_hook
+#[unstable(feature = "unix_abort_message_fd", issue = "none")]
+pub fn set_abort_message_fd(fd: OwnedFd) {
+    sys::abort_output::set_abort_output_fd(fd)
+}
+
+/// Makes the runtime report the fatal signals that hit this process, the way
+/// it reports stack overflows.
+///
+/// By default, the standard library only catches SIGSEGV and SIGBUS to tell
+/// stack overflows apart, and leaves every other crash to the system, which
+/// usually just prints something like `Illegal instruction (core dumped)`.
+/// After calling this function, SIGILL, SIGFPE and SIGABRT, as well as the
+/// SIGSEGV and SIGBUS that aren't stack overflows, get a report naming the
+/// thread, the signal and the faulting address where there is one, followed
+/// by a backtrace if `RUST_BACKTRACE` asks for one (see
+/// [`panic::get_backtrace_style`]). The signal is then raised again with the
+/// disposition it had before, so the process still dies of it, with a core
+/// dump if those are enabled.
+///
+/// The report goes where [`set_abort_message_fd`] says. Signals that already
+/// have a handler when this is called are left alone. Printing the backtrace
+/// is best effort: the signal may have interrupted the allocator or another
+/// backtrace, in which case it can hang or crash.
+///
+/// Does nothing on the platforms where the standard library doesn't handle
+/// signals itself.
+///
+/// [`panic::get_backtrace_style`]: crate::panic::get_backtrace_style
+#[unstable(feature = "unix_crash_reporter", issue = "none")]
+pub fn install_crash_reporter() {
+    sys::stack_overflow::install_crash_reporter()
+}
@@ -0,0 +1,235 @@
+//! Unix-specific details of the standard library's stack overflow detection.
+//!
+//! The standard library catches stack overflows with a handler for SIGSEGV
+//! and SIGBUS that runs on an alternate signal stack, and tells them apart
+//! from other faults by the guard range below each thread's stack. Libraries
+//! that manipulate signals themselves, like async runtimes or FFI wrappers,
+//! can use [`status`] to find out what is in place and cooperate with it.
+//!
+//! What the handler prints before aborting can be chosen with the
+//! `RUST_STACK_OVERFLOW` environment variable or [`set_report_mode`], see
+//! [`ReportMode`].
+//!
+//! Setting the `RUST_STACK_OVERFLOW_MLOCK` environment variable to `1` locks
+//! the alternate signal stacks into memory as they are set up, so that the
+//! handler doesn't have to page them in when memory is tight, e.g. in a
+//! container at its memory limit. Where the stacks can't be locked, because
+//! `RLIMIT_MEMLOCK` is too small or the process lacks the privilege, they are
+//! only made resident up front. Either way, this makes each thread's signal
+//! stack count against the process' memory for as long as the thread lives.
+
+#![unstable(feature = "unix_stack_overflow_status", issue = "none")]
+
+use crate::fmt;
+use crate::ops::Range;
+
+/// The state of stack overflow detection, as seen from the current thread.
+///
+/// Returned by [`status`].
+#[derive(Clone)]
+pub struct Status {
+    handlers_installed: bool,
+    altstack_active: bool,
+    guard: Option<Range<usize>>,
+    fault_signals_unblocked: bool,
+}
+
+impl Status {
+    /// Whether the standard library's handler is currently installed for
+    /// SIGSEGV or SIGBUS.
+    ///
+    /// This is `false` if the signals had a handler already when the
+    /// standard library started, or if another one replaced it since.
+    #[must_use]
+    pub fn handlers_installed(&self) -> bool {
+        self.handlers_installed
+    }
+
+    /// Whether the current thread has an alternate signal stack, no matter
+    /// who installed it.
+    #[must_use]
+    pub fn altstack_active(&self) -> bool {
+        self.altstack_active
+    }
+
+    /// The range of addresses below the current thread's stack that the
+    /// handler treats as its guard: a fault in there is reported as a stack
+    /// overflow.
+    ///
+    /// Returns `None` if the guard isn't known, e.g. on a thread that the
+    /// standard library didn't spawn and that wasn't registered with
+    /// [`register_current_thread`]. It also isn't known for the main thread
+    /// where the system can't tell how far its stack can grow: with musl, or
+    /// on AIX when the stack limit is unlimited. Overflows there end in a
+    /// plain `SIGSEGV`.
+    ///
+    /// On Linux, where the main thread's stack can grow until it comes close
+    /// to the next mapping below it, e.g. with an unlimited stack limit, the
+    /// guard range is the gap the kernel keeps to that mapping.
+    ///
+    /// [`register_current_thread`]: crate::os::unix::thread::register_current_thread
+    #[must_use]
+    pub fn guard_range(&self) -> Option<Range<usize>> {
+        self.guard.clone()
+    }
+
+    /// Whether SIGSEGV or SIGBUS were blocked on a thread when stack overflow
+    /// detection was set up on it.
+    ///
+    /// A fault while these signals are blocked kills the process without
+    /// running any handler, so an overflow would end without a report. The
+    /// signal mask is inherited from the parent process and by spawned
+    /// threads, so a parent that blocks them, or a library that blocks them
+    /// while spawning a thread, affects every thread that comes after. The
+    /// standard library unblocks them on each thread it sets up, along with
+    /// its handler, and this tells whether it had to.
+    #[must_use]
+    pub fn fault_signals_unblocked(&self) -> bool {
+        self.fault_signals_unblocked
+    }
+}
+
+impl fmt::Debug for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Status")
+            .field("handlers_installed", &self.handlers_installed())
+            .field("altstack_active", &self.altstack_active())
+            .field("guard_range", &self.guard_range())
+            .field("fault_signals_unblocked", &self.fault_signals_unblocked())
+            .finish()
+    }
+}
+
+/// Returns the current state of stack overflow detection, see [`Status`].
+///
+/// On platforms where the standard library doesn't detect stack overflows,
+/// the status says that nothing is installed.
+#[must_use]
+pub fn status() -> Status {
+    let status = crate::sys::stack_overflow::status();
+    Status {
+        handlers_installed: status.handlers_installed,
+        altstack_active: status.altstack_active,
+        guard: status.guard,
+        fault_signals_unblocked: status.fault_signals_unblocked,
+    }
+}
+
+/// Returns whether a fault at `fault_addr` on the current thread would be
+/// reported as a stack overflow.
+///
+/// This applies the same test as the handler does when the thread faults:
+/// whether the address lies in the thread's guard range, including the main
+/// thread's guard moving after its stack limit changed. Tests, debugger
+/// scripts and crash analyzers can use it to check how a fault would be
+/// classified without actually faulting.
+///
+/// Always returns `false` on platforms where the standard library doesn't
+/// detect stack overflows, or for threads whose guard isn't known.
+#[must_use]
+pub fn is_stack_overflow(fault_addr: usize) -> bool {
+    crate::sys::stack_overflow::is_stack_overflow(fault_addr)
+}
+
+/// What the standard library prints when it catches a stack overflow, before
+/// aborting the process.
+///
+/// The mode is taken from the `RUST_STACK_OVERFLOW` environment variable at
+/// startup, which may be set to `abort`, `quiet` or `backtrace`. Other values
+/// are ignored. [`set_report_mode`] overrides it.
+#[unstable(feature = "unix_stack_overflow_report_mode", issue = "none")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ReportMode {
+    /// Prints a message naming the overflowing thread. This is the default.
+    Abort,
+    /// Prints nothing, e.g. for containers where writing to stderr from a
+    /// signal handler is undesirable.
+    Quiet,
+    /// Prints the message along with a backtrace of the overflowing thread.
+    ///
+    /// Threads set up while this mode is selected, or while `RUST_BACKTRACE`
+    /// is set, get a larger signal stack for the handler to print the
+    /// backtrace on. On other threads, taking the backtrace may itself run
+    /// out of stack. It can also hang if the overflow happened while the
+    /// thread held the backtrace lock. Where the overflow is detected on
+    /// another thread than the overflowing one, only the message is printed.
+    Backtrace,
+}
+
+/// Sets how stack overflows are reported from now on, see [`ReportMode`].
+///
+/// This applies to all threads and takes precedence over the
+/// `RUST_STACK_OVERFLOW` environment variable.
+#[unstable(feature = "unix_stack_overflow_report_mode", issue = "none")]
+pub fn set_report_mode(mode: ReportMode) {
+    use crate::sys::stack_overflow::ReportMode as Mode;
+
+    crate::sys::stack_overflow::set_report_mode(match mode {
+        ReportMode::Abort => Mode::Abort,
+        ReportMode::Quiet => Mode::Quiet,
+        ReportMode::Backtrace => Mode::Backtrace,
+    })
+}
+
+/// Returns how stack overflows are currently reported, see [`ReportMode`].
+#[unstable(feature = "unix_stack_overflow_report_mode", issue = "none")]
+#[must_use]
+pub fn report_mode() -> ReportMode {
+    use crate::sys::stack_overflow::ReportMode as Mode;
+
+    match crate::sys::stack_overflow::report_mode() {
+        Mode::Abort => ReportMode::Abort,
+        Mode::Quiet => ReportMode::Quiet,
+        Mode::Backtrace => ReportMode::Backtrace,
+    }
+}
+
+/// Sets up stack overflow detection in programs where the standard library
+/// didn't start the runtime, and registers the calling thread.
+///
+/// The standard library normally sets up its handler before `main` runs.
+/// That doesn't happen in `#![no_main]` programs, in programs with a custom
+/// entry point, or in a `cdylib` loaded into a host written in another
+/// language. Stack overflows then end in a plain `SIGSEGV`, and functions
+/// like [`status`] report that nothing is installed. Calling this function
+/// early, e.g. first thing in a `#![no_main]` program's `main`, or from a
+/// library's initialization routine, does the setup the runtime would have
+/// done.
+///
+/// As when the runtime does it, the handler is only installed for SIGSEGV
+/// and SIGBUS if those don't have a handler already. If the calling thread
+/// is the process' main thread, its guard is found as well. Other threads
+/// that std didn't spawn need to call [`register_current_thread`] to be
+/// covered, or this function, which does that too.
+///
+/// Calling this function again, or in a program whose runtime the standard
+/// library did start, only registers the calling thread.
+///
+/// [`register_current_thread`]: crate::os::unix::thread::register_current_thread
+#[unstable(feature = "unix_stack_overflow_init", issue = "none")]
+pub fn init() {
+    crate::sys::stack_overflow::init_external()
+}
+
+/// Returns the memory of the current thread's alternate signal stack to the
+/// operating system, keeping its address range reserved.
+///
+/// Each thread's handler runs on a signal stack of its own. Its pages only
+/// become resident once a signal is handled on them, e.g. by another handler
+/// that was installed with `SA_ONSTACK`, and stay resident for the life of
+/// the thread after that. Processes with many long-lived but mostly idle
+/// threads can call this from those threads, e.g. before they go back to
+/// waiting for work, to keep that memory from adding up. Where the system
+/// supports it, the pages are only reclaimed once memory gets short.
+///
+/// The pages are faulted back in whenever the next signal is handled on the
+/// stack. Signal stacks that the standard library didn't install are left
+/// alone, as is the current one if a signal handler is running on it, and
+/// all of them with `RUST_STACK_OVERFLOW_MLOCK` set.
+///
+/// Does nothing on platforms without signal stacks.
+#[unstable(feature = "unix_stack_overflow_trim", issue = "none")]
+pub fn trim_signal_stack() {
+    crate::sys::stack_overflow::trim_current_altstack()
+}
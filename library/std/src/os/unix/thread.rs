@@ -1,5 +1,17 @@
 //! Unix-specific extensions to primitives in the [`std::thread`] module.
 //!
+//! # Alternate signal stacks
+//!
+//! To report stack overflows, the standard library installs an alternate
+//! signal stack (see `sigaltstack(2)`) on the threads it sets up, unless the
+//! thread already has one. Applications are free to install their own in its
+//! place: when the thread exits, the standard library only disables the
+//! alternate signal stack if it is still the one it installed, and leaves
+//! the application's alone. The memory of the standard library's stack is
+//! released at that point regardless, so an application that saved it (as
+//! the previous stack returned by `sigaltstack`) must not reinstall it after
+//! the thread has started exiting.
+//!
 //! [`std::thread`]: crate::thread
 
 #![stable(feature = "thread_extensions", since = "1.9.0")]
@@ -39,3 +51,127 @@ fn into_pthread_t(self) -> RawPthread {
         self.into_inner().into_id() as RawPthread
     }
 }
+
+/// Sets up stack overflow detection on the current thread, if it wasn't
+/// spawned by the standard library.
+///
+/// Threads spawned through [`std::thread`] report running out of stack with a
+/// message naming the thread before aborting. Threads created by other means,
+/// e.g. by a C library calling back into Rust, don't get this set up until
+/// they first call [`thread::current`], which registers them automatically.
+/// Calling this function registers the thread right away: it records the
+/// bounds of the thread's guard page and installs an alternate signal stack
+/// to report the overflow on, which is released when the thread exits.
+///
+/// Calling this on a thread that is already registered does nothing. A
+/// thread that was created without a guard page can't be registered.
+///
+/// [`std::thread`]: crate::thread
+/// [`thread::current`]: crate::thread::current
+#[unstable(feature = "unix_register_current_thread", issue = "none")]
+pub fn register_current_thread() {
+    crate::sys::stack_overflow::register_current_thread()
+}
+
+/// Recomputes where the current thread's stack overflows, after something
+/// may have moved it.
+///
+/// The bounds of a thread's guard page are computed once, when the thread is
+/// set up. For the main thread they follow from the stack limit
+/// (`RLIMIT_STACK`) on most platforms, so if the program lowers or raises
+/// that limit later on, overflows may no longer be recognized as such. Call
+/// this on the main thread after changing the limit to bring them up to
+/// date. On Linux and AIX the signal handler also checks for a changed limit
+/// by itself when a fault doesn't hit the known guard page. On FreeBSD, this
+/// also picks up changes to the number of guard pages the kernel puts below
+/// the main thread's stack (the `security.bsd.stack_guard_page` sysctl).
+///
+/// Does nothing if stack overflow detection isn't set up on this thread.
+#[unstable(feature = "unix_refresh_stack_guard", issue = "none")]
+pub fn refresh_stack_guard() {
+    crate::sys::stack_overflow::refresh_current_guard()
+}
+
+/// Where the stack and the guard page of a thread lie, as recorded for stack
+/// overflow detection.
+///
+/// Crash handlers (e.g. minidump writers) can look these up to tell stack
+/// overflows apart from other faults: a fault in the guard range of a thread
+/// is an overflow of that thread's stack. The standard library records them
+/// for the main thread and the threads it spawns, as well as for other
+/// threads once they are registered (see [`register_current_thread`]), on
+/// the platforms where it detects stack overflows itself.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[unstable(feature = "unix_stack_guard_registry", issue = "none")]
+pub struct StackGuardInfo {
+    /// The system's id of the thread, e.g. what `gettid()` returns on Linux.
+    pub thread_id: u64,
+    /// The lowest address of the thread's stack.
+    pub stack_start: usize,
+    /// The address right above the highest one of the thread's stack.
+    pub stack_end: usize,
+    /// The lowest address of the guard range.
+    pub guard_start: usize,
+    /// The address right above the highest one of the guard range.
+    pub guard_end: usize,
+}
+
+/// Looks up the stack and guard range of the thread with the system id
+/// `thread_id`, writing them to `*info` if it is known.
+///
+/// Returns whether the thread was found. This function doesn't allocate or
+/// take any locks, so it can be called from signal handlers, and its C ABI
+/// lets crash handlers written in other languages call it through a function
+/// pointer.
+///
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[unstable(feature = "unix_stack_guard_registry", issue = "none")]
+pub unsafe extern "C" fn stack_guard_info(thread_id: u64, info: *mut StackGuardInfo) -> bool {
+    match crate::sys::stack_overflow::lookup_registered(thread_id) {
+        Some((stack, guard)) => {
+            // SAFETY: the caller guarantees `info` is valid for writes.
+            unsafe {
+                info.write(StackGuardInfo {
+                    thread_id,
+                    stack_start: stack.start,
+                    stack_end: stack.end,
+                    guard_start: guard.start,
+                    guard_end: guard.end,
+                })
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Calls `callback` with the stack and guard range of every thread that is
+/// currently known, passing `data` along.
+///
+/// Like [`stack_guard_info`], this doesn't allocate or take any locks, so it
+/// can be called from signal handlers. The pointer passed to `callback` is
+/// only valid for the duration of that call.
+///
+/// # Safety
+///
+/// `callback` must be safe to call with a valid `StackGuardInfo` and `data`.
+#[unstable(feature = "unix_stack_guard_registry", issue = "none")]
+pub unsafe extern "C" fn for_each_stack_guard(
+    callback: unsafe extern "C" fn(info: *const StackGuardInfo, data: *mut crate::ffi::c_void),
+    data: *mut crate::ffi::c_void,
+) {
+    crate::sys::stack_overflow::for_each_registered(|thread_id, stack, guard| {
+        let info = StackGuardInfo {
+            thread_id,
+            stack_start: stack.start,
+            stack_end: stack.end,
+            guard_start: guard.start,
+            guard_end: guard.end,
+        };
+        // SAFETY: the caller guarantees `callback` can be called like this.
+        unsafe { callback(&info, data) };
+    });
+}
@@ -29,9 +29,10 @@
 // - the standard error output
 // - some dedicated platform specific output
 // - nothing (so this macro is a no-op)
+// On Unix, it can be redirected with `std::os::unix::process::set_abort_message_fd`.
 macro_rules! rtprintpanic {
     ($($t:tt)*) => {
-        if let Some(mut out) = crate::sys::stdio::panic_output() {
+        if let Some(mut out) = crate::sys::abort_output::abort_output() {
             let _ = crate::io::Write::write_fmt(&mut out, format_args!($($t)*));
         }
     }
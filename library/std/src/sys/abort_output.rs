@@ -0,0 +1,11 @@
+//! Where the runtime writes its last words before aborting the process, see
+//! `rtabort!`. Unix allows redirecting them, elsewhere this is the same as the
+//! panic output.
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        pub use super::pal::stdio::{abort_output, set_abort_output_fd};
+    } else {
+        pub use super::pal::stdio::panic_output as abort_output;
+    }
+}
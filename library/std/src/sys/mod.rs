@@ -8,6 +8,7 @@
 mod alloc;
 mod personality;
 
+pub mod abort_output;
 pub mod anonymous_pipe;
 pub mod backtrace;
 pub mod cmath;
@@ -15,6 +16,9 @@
 pub mod exit_guard;
 pub mod os_str;
 pub mod path;
+pub mod stack_overflow;
+pub mod stack_region;
+pub mod stack_usage;
 pub mod sync;
 pub mod thread_local;
 
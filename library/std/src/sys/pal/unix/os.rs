@@ -13,6 +13,7 @@
 use crate::ffi::{CStr, CString, OsStr, OsString};
 use crate::os::unix::prelude::*;
 use crate::path::{self, PathBuf};
+use crate::sync::atomic::{AtomicUsize, Ordering};
 use crate::sync::{PoisonError, RwLock};
 use crate::sys::common::small_c_string::{run_path_with_cstr, run_with_cstr};
 #[cfg(all(target_env = "gnu", not(target_os = "vxworks")))]
@@ -693,7 +694,16 @@ pub unsafe fn unsetenv(n: &OsStr) -> io::Result<()> {
 
 #[cfg(not(target_os = "espidf"))]
 pub fn page_size() -> usize {
-    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    // It can't change while the process runs, so only ask once.
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+    match PAGE_SIZE.load(Ordering::Relaxed) {
+        0 => {
+            let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+            PAGE_SIZE.store(size, Ordering::Relaxed);
+            size
+        }
+        size => size,
+    }
 }
 
 pub fn temp_dir() -> PathBuf {
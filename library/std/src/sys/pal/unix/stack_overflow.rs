@@ -1,34 +1,250 @@
 #![cfg_attr(test, allow(dead_code))]
 
-pub use self::imp::{cleanup, init};
+pub use self::imp::{
+    cleanup, install_crash_reporter, is_stack_overflow, remaining_stack, run_on_new_stack, status,
+};
 use self::imp::{drop_handler, make_handler};
+pub use self::registry::{for_each_registered, lookup_registered};
+use crate::cell::Cell;
+use crate::ffi::CStr;
+use crate::sys::stack_overflow::report;
+
+mod registry;
+
+#[cfg(target_os = "espidf")]
+mod espidf;
+#[cfg(target_os = "espidf")]
+use self::espidf as imp;
+#[cfg(target_os = "fuchsia")]
+mod fuchsia;
+#[cfg(target_os = "fuchsia")]
+use self::fuchsia as imp;
+#[cfg(all(
+    feature = "apple_mach_stack_overflow",
+    any(target_os = "ios", target_os = "tvos", target_os = "watchos", target_os = "visionos"),
+))]
+mod mach;
+#[cfg(all(
+    feature = "apple_mach_stack_overflow",
+    any(target_os = "ios", target_os = "tvos", target_os = "watchos", target_os = "visionos"),
+))]
+use self::mach as imp;
+
+thread_local! {
+    /// Whether stack overflow detection has been set up on this thread, by
+    /// `init`, `register_spawned_thread` or `register_current_thread`.
+    static REGISTERED: Cell<bool> = const { Cell::new(false) };
+    /// The handler of this thread, dropped along with the rest of the
+    /// thread's TLS. It is set before any other TLS with a destructor, so
+    /// it is dropped after all of them and overflows in those destructors
+    /// are still caught on the alternate stack.
+    static HANDLER: Cell<Option<Handler>> = const { Cell::new(None) };
+    /// The name of this thread for the signal handler to report, null if it
+    /// has none. See `set_current_name`.
+    static NAME: Cell<*const libc::c_char> = const { Cell::new(crate::ptr::null()) };
+    /// The guard size this thread was spawned with, if one was requested.
+    static GUARD_SIZE: Cell<Option<usize>> = const { Cell::new(None) };
+    /// The stack size this thread was spawned with, if std spawned it.
+    static STACK_SIZE: Cell<Option<usize>> = const { Cell::new(None) };
+    /// The guard std set up itself, for threads spawned on a stack region
+    /// their builder was given.
+    // FIXME: use `Range` once that implements `Copy`.
+    static REGION_GUARD: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+    /// Whether this is the main thread, as far as `init` or `init_external`
+    /// could tell.
+    static ON_MAIN_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Guards `init_now`, which `ensure_init` and `init_external` can get to
+/// from any thread.
+static INIT: crate::sync::Once = crate::sync::Once::new();
+
+/// # Safety
+/// Must be called only once
+pub unsafe fn init() {
+    ON_MAIN_THREAD.set(true);
+    NAME.set(c"main".as_ptr());
+    // Short-lived tools and embedders may not want a process-wide signal
+    // handler, nor the syscalls to set it up, unless they spawn threads or
+    // ask for it. See `ensure_init`.
+    if !cfg!(feature = "lazy_stack_overflow") {
+        // SAFETY: nothing else can have run `init_now` before the runtime
+        // was started.
+        INIT.call_once(|| unsafe { init_now() });
+    }
+}
+
+/// Does the work `init` left for later with the `lazy_stack_overflow`
+/// feature, if it wasn't done yet: std calls this before spawning a thread,
+/// and when stack overflow detection is asked for explicitly.
+///
+/// If this happens on another thread than the main one, the main thread is
+/// left unprotected, since its guard can only be found from the main thread.
+pub fn ensure_init() {
+    if cfg!(feature = "lazy_stack_overflow") {
+        // SAFETY: the `Once` makes sure this only happens once.
+        INIT.call_once(|| unsafe { init_now() });
+    }
+}
+
+/// Sets up stack overflow detection for programs whose runtime std didn't
+/// start, i.e. where `init` never ran, and registers the current thread.
+/// Does only the latter if it did.
+pub fn init_external() {
+    INIT.call_once(|| {
+        if is_main_thread() {
+            ON_MAIN_THREAD.set(true);
+            if NAME.get().is_null() {
+                NAME.set(c"main".as_ptr());
+            }
+        }
+        // SAFETY: the `Once` makes sure this only happens once.
+        unsafe { init_now() }
+    });
+    register_current_thread();
+}
+
+/// Whether the current thread is the process' initial thread, where we can
+/// tell. Otherwise, it is set up like any other thread.
+fn is_main_thread() -> bool {
+    // SAFETY: none of these have any preconditions.
+    unsafe {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                // glibc only has a `gettid` wrapper since 2.30.
+                libc::syscall(libc::SYS_gettid) as libc::pid_t == libc::getpid()
+            } else if #[cfg(target_os = "android")] {
+                libc::gettid() == libc::getpid()
+            } else if #[cfg(any(
+                target_vendor = "apple",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd",
+            ))] {
+                libc::pthread_main_np() == 1
+            } else {
+                false
+            }
+        }
+    }
+}
+
+unsafe fn init_now() {
+    report::init_report_mode();
+    imp::init();
+    if ON_MAIN_THREAD.get() {
+        REGISTERED.set(true);
+        registry::register_current();
+    }
+}
+
+/// Stashes the name of the current thread for the signal handler, which
+/// can't go through `thread::current()`: that may allocate, or find the
+/// thread's TLS already torn down.
+///
+/// # Safety
+/// `name` must stay valid until it is replaced, e.g. by `None`.
+pub unsafe fn set_current_name(name: Option<&CStr>) {
+    NAME.set(name.map_or(crate::ptr::null(), CStr::as_ptr));
+}
+
+/// Sets up stack overflow detection on a thread that std didn't spawn, the
+/// way `register_spawned_thread` does for the threads it did. Does nothing on threads
+/// that are already set up.
+pub fn register_current_thread() {
+    ensure_init();
+    if REGISTERED.replace(true) {
+        return;
+    }
+    // SAFETY: the handler is dropped on this thread, as part of its TLS.
+    let handler = unsafe { make_handler(false) };
+    registry::register_current();
+    // If the thread's TLS is already being torn down, there is nothing left
+    // to protect and the handler is dropped right away.
+    let _ = HANDLER.try_with(|slot| slot.set(Some(handler)));
+}
+
+/// Sets up the current thread, which was spawned by std with a stack of
+/// `stack_size` bytes and a guard of `guard_size` bytes, or the platform's
+/// default if `None`. `guard_range` is where the guard is if std placed it
+/// itself, on a stack region the thread was given. Must be called before
+/// anything else touches the thread's TLS, see `HANDLER`.
+pub unsafe fn register_spawned_thread(
+    stack_size: usize,
+    guard_size: Option<usize>,
+    guard_range: Option<crate::ops::Range<usize>>,
+) {
+    REGISTERED.set(true);
+    STACK_SIZE.set(Some(stack_size));
+    GUARD_SIZE.set(guard_size);
+    REGION_GUARD.set(guard_range.map(|guard| (guard.start, guard.end)));
+    let handler = make_handler(false);
+    registry::register_current();
+    HANDLER.set(Some(handler));
+}
+
+/// Recomputes the current thread's guard range, for when it may have moved
+/// since the thread was set up, e.g. because `RLIMIT_STACK` was changed.
+pub fn refresh_current_guard() {
+    // SAFETY: not called from a signal handler.
+    unsafe { imp::refresh_guard() }
+}
+
+/// Lets the system reclaim the memory of the current thread's altstack until
+/// the next signal is handled on it.
+pub fn trim_current_altstack() {
+    imp::trim_altstack()
+}
+
+/// The bounds of the current thread's stack and of its guard, where stack
+/// overflow detection knows them.
+pub fn current_stack_bounds() -> Option<(crate::ops::Range<usize>, crate::ops::Range<usize>)> {
+    imp::registry_info().map(|(_, stack, guard)| (stack, guard))
+}
+
+/// The state of stack overflow detection, as seen from the current thread.
+#[derive(Clone, Debug, Default)]
+pub struct Status {
+    /// Whether our handler is installed for SIGSEGV or SIGBUS, or whatever
+    /// the platform reports overflows through.
+    pub handlers_installed: bool,
+    /// Whether the current thread has an alternate signal stack, ours or not.
+    pub altstack_active: bool,
+    /// The current thread's guard range, if it is known.
+    pub guard: Option<crate::ops::Range<usize>>,
+    /// Whether SIGSEGV or SIGBUS were blocked on a thread that was set up,
+    /// and had to be unblocked.
+    pub fault_signals_unblocked: bool,
+}
 
 pub struct Handler {
     data: *mut libc::c_void,
+    /// The size of the altstack at `data`, if the platform maps one.
+    size: usize,
 }
 
 impl Handler {
-    pub unsafe fn new() -> Handler {
-        make_handler(false)
-    }
-
     fn null() -> Handler {
-        Handler { data: crate::ptr::null_mut() }
+        Handler { data: crate::ptr::null_mut(), size: 0 }
     }
 }
 
 impl Drop for Handler {
     fn drop(&mut self) {
+        registry::unregister_current();
         unsafe {
-            drop_handler(self.data);
+            drop_handler(self.data, self.size);
         }
     }
 }
 
 #[cfg(any(
+    target_os = "aix",
     target_os = "linux",
     target_os = "freebsd",
+    target_os = "dragonfly",
     target_os = "hurd",
+    target_os = "haiku",
     target_os = "macos",
     target_os = "netbsd",
     target_os = "openbsd",
@@ -42,26 +258,37 @@ mod imp {
     use libc::{mmap64, mprotect, munmap};
     use libc::{
         sigaction, sigaltstack, sighandler_t, MAP_ANON, MAP_FAILED, MAP_FIXED, MAP_PRIVATE,
-        PROT_NONE, PROT_READ, PROT_WRITE, SA_ONSTACK, SA_SIGINFO, SIGBUS, SIGSEGV, SIG_DFL,
-        SS_DISABLE,
+        PROT_NONE, PROT_READ, PROT_WRITE, SA_NODEFER, SA_ONSTACK, SA_SIGINFO, SIGBUS, SIGSEGV,
+        SIG_DFL, SS_DISABLE, SS_ONSTACK,
     };
 
     use super::Handler;
     use crate::cell::Cell;
+    use crate::ffi::CStr;
     use crate::ops::Range;
+    use crate::panic::{self, AssertUnwindSafe};
     use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
     use crate::sync::OnceLock;
     use crate::sys::pal::unix::os;
-    use crate::{io, mem, ptr, thread};
+    use crate::sys::stack_overflow::report;
+    use crate::{io, mem, ptr};
 
     // We use a TLS variable to store the address of the guard page. While TLS
     // variables are not guaranteed to be signal-safe, this works out in practice
     // since we make sure to write to the variable before the signal stack is
     // installed, thereby ensuring that the variable is always allocated when
     // the signal handler is called.
+    //
+    // Where TLS is emulated with keys, these are freed along with the rest of
+    // the thread's TLS though, while its handler may still be in place (see
+    // `HANDLER`). The signal handler only uses `try_with` on them and falls
+    // back to the registry, which outlives the thread's TLS, for the guard.
     thread_local! {
         // FIXME: use `Range` once that implements `Copy`.
         static GUARD: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+        static MAIN_THREAD: Cell<bool> = const { Cell::new(false) };
+        // The guard page below the altstack we installed on this thread.
+        static ALTSTACK_GUARD: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
     }
 
     // Signal handler for the SIGSEGV and SIGBUS handlers. We've got guard pages
@@ -94,19 +321,52 @@ mod imp {
         info: *mut libc::siginfo_t,
         _data: *mut libc::c_void,
     ) {
-        let (start, end) = GUARD.get();
+        // SAFETY: `pthread_self` has no preconditions.
+        let thread = unsafe { libc::pthread_self() } as usize;
+        // The handler faulted itself, before it could get rid of itself.
+        // Nothing it relies on can be trusted anymore, TLS included.
+        if let Some(slot) =
+            IN_HANDLER.iter().position(|slot| slot.load(Ordering::Relaxed) == thread)
+        {
+            // SAFETY: passed on from the system.
+            unsafe { reentered(signum, info, slot) };
+            return;
+        }
+        let slot = IN_HANDLER.iter().position(|slot| {
+            slot.compare_exchange(0, thread, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+        });
+
         // SAFETY: this pointer is provided by the system and will always point to a valid `siginfo_t`.
         let addr = unsafe { (*info).si_addr().addr() };
 
+        // Another handler running on our altstack ran out of it, this one
+        // doing so ends up in `reentered`. Formatting needs stack we don't
+        // have, so go straight to write(2).
+        let (alt_start, alt_end) = ALTSTACK_GUARD.try_with(Cell::get).unwrap_or((0, 0));
+        if let Some(slot) = slot {
+            IN_HANDLER_ALTSTACK_GUARD[slot].store(alt_start, Ordering::Relaxed);
+        }
+        if alt_start <= addr && addr < alt_end {
+            const MSG: &[u8] = b"\nfatal runtime error: signal stack overflow (double fault)\n";
+            if let Some(mut out) = crate::sys::abort_output::abort_output() {
+                let _ = io::Write::write_all(&mut out, MSG);
+            }
+            crate::sys::abort_internal();
+        }
+
         // If the faulting address is within the guard page, then we print a
         // message saying so and abort.
-        if start <= addr && addr < end {
-            rtprintpanic!(
-                "\nthread '{}' has overflowed its stack\n",
-                thread::current().name().unwrap_or("<unknown>")
-            );
-            rtabort!("stack overflow");
+        if is_stack_overflow(addr) {
+            report::report_and_abort(&report::Overflow {
+                name: Some(current_name()),
+                id: Some(current_os_id()),
+                stack_size: super::STACK_SIZE.try_with(Cell::get).ok().flatten(),
+                can_backtrace: true,
+            });
         } else {
+            if REPORT_CRASHES.load(Ordering::Relaxed) {
+                report_crash(signum, info);
+            }
             // Unregister ourselves by reverting back to the default behavior.
             // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
             let mut action: sigaction = unsafe { mem::zeroed() };
@@ -114,39 +374,315 @@ mod imp {
             // SAFETY: pray this is a well-behaved POSIX implementation of fn sigaction
             unsafe { sigaction(signum, &action, ptr::null_mut()) };
 
+            if let Some(slot) = slot {
+                IN_HANDLER[slot].store(0, Ordering::Relaxed);
+            }
             // See comment above for why this function returns.
         }
     }
 
+    /// The threads running `signal_handler`, by `pthread_self`, so that it
+    /// can tell when it faulted itself: TLS may be what faulted. A thread
+    /// takes any free slot, and if more threads fault at once than there are
+    /// slots, the rest do without. Overflows abort with their slot taken.
+    static IN_HANDLER: [AtomicUsize; 8] = [const { AtomicUsize::new(0) }; 8];
+    /// The start of the guard below the altstack of the thread in the same
+    /// slot of `IN_HANDLER`, or zero. Its end is a page further up.
+    static IN_HANDLER_ALTSTACK_GUARD: [AtomicUsize; 8] = [const { AtomicUsize::new(0) }; 8];
+
+    /// Called when `signal_handler` faults while handling a fault on the
+    /// same thread, from `slot` of `IN_HANDLER`. Makes sure the process
+    /// dies of the fault instead of looping or hanging in the handler, and
+    /// with as few moving parts as possible: a fixed message straight to
+    /// write(2), and the default disposition for the signal raised again.
+    ///
+    /// # Safety
+    /// `info` must be the `siginfo_t` the system passed to the handler.
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe fn reentered(signum: libc::c_int, info: *mut libc::siginfo_t, slot: usize) {
+        // SAFETY: the kernel could only deliver the signal by writing `info`.
+        let addr = unsafe { (*info).si_addr().addr() };
+        let guard_start = IN_HANDLER_ALTSTACK_GUARD[slot].load(Ordering::Relaxed);
+        let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+        let msg: &[u8] =
+            if guard_start != 0 && (guard_start..guard_start + page_size).contains(&addr) {
+                b"\nfatal runtime error: signal stack overflow (double fault)\n"
+            } else {
+                b"\nfatal runtime error: fault in the stack overflow handler\n"
+            };
+        if let Some(mut out) = crate::sys::abort_output::abort_output() {
+            let _ = io::Write::write_all(&mut out, msg);
+        }
+
+        // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
+        let mut action: sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = SIG_DFL;
+        for signal in [SIGSEGV, SIGBUS] {
+            // SAFETY: resets the disposition to the default.
+            unsafe { sigaction(signal, &action, ptr::null_mut()) };
+        }
+        // A fault happens again once we return anyway, a signal that was sent
+        // has to be sent again.
+        // SAFETY: raising a signal has no preconditions.
+        unsafe { libc::raise(signum) };
+    }
+
+    /// Whether `install_crash_reporter` was called.
+    static REPORT_CRASHES: AtomicBool = AtomicBool::new(false);
+    /// Whether crash reports should come with a backtrace.
+    static CRASH_BACKTRACE: AtomicBool = AtomicBool::new(false);
+    /// The signals only the crash reporter handles, besides SIGSEGV and
+    /// SIGBUS, and the dispositions it replaced for them.
+    const FATAL_SIGNALS: [libc::c_int; 3] = [libc::SIGILL, libc::SIGFPE, libc::SIGABRT];
+    static FATAL_ACTIONS: [OnceLock<sigaction>; 3] =
+        [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+
+    /// Sets up reports for SIGILL, SIGFPE and SIGABRT, as well as for the
+    /// SIGSEGV and SIGBUS that aren't stack overflows. Signals that have a
+    /// handler already are left alone.
+    pub fn install_crash_reporter() {
+        let backtrace = crate::panic::get_backtrace_style()
+            .is_some_and(|style| style != crate::panic::BacktraceStyle::Off);
+        CRASH_BACKTRACE.store(backtrace, Ordering::Relaxed);
+        if REPORT_CRASHES.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
+        let mut action: sigaction = unsafe { mem::zeroed() };
+        for (&signal, original) in FATAL_SIGNALS.iter().zip(&FATAL_ACTIONS) {
+            // SAFETY: just fetches the current signal handler into action
+            unsafe { sigaction(signal, ptr::null_mut(), &mut action) };
+            if action.sa_sigaction == SIG_DFL {
+                let _ = original.set(action);
+                action.sa_flags = SA_SIGINFO | SA_ONSTACK | SA_NODEFER;
+                action.sa_sigaction = fatal_signal_handler as sighandler_t;
+                // SAFETY: only overriding signals if the default is set
+                unsafe { sigaction(signal, &action, ptr::null_mut()) };
+            }
+        }
+    }
+
+    /// SIGILL/SIGFPE/SIGABRT entry point, once the crash reporter is installed.
+    /// # Safety
+    /// Rust doesn't call this, it *gets called*.
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe extern "C" fn fatal_signal_handler(
+        signum: libc::c_int,
+        info: *mut libc::siginfo_t,
+        _data: *mut libc::c_void,
+    ) {
+        report_crash(signum, info);
+
+        let index = FATAL_SIGNALS.iter().position(|&signal| signal == signum);
+        // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
+        let mut action: sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = SIG_DFL;
+        let original = index.and_then(|index| FATAL_ACTIONS[index].get()).unwrap_or(&action);
+        // SAFETY: puts back what `install_crash_reporter` found.
+        unsafe { sigaction(signum, original, ptr::null_mut()) };
+
+        // A fault happens again once we return, and then goes to the original
+        // disposition. Signals sent by someone, like the one `abort` raises,
+        // have to be sent again.
+        // SAFETY: the system passes a valid `siginfo_t`.
+        if unsafe { (*info).si_code } <= 0 {
+            // SAFETY: raising a signal has no preconditions.
+            unsafe { libc::raise(signum) };
+        }
+    }
+
+    /// Prints what hit the current thread, and a backtrace if requested.
+    /// The backtrace is best effort: it takes the backtrace lock and may
+    /// allocate, so it can hang or crash if the signal interrupted the
+    /// allocator or another backtrace.
+    fn report_crash(signum: libc::c_int, info: *mut libc::siginfo_t) {
+        let name = match signum {
+            libc::SIGSEGV => "SIGSEGV",
+            libc::SIGBUS => "SIGBUS",
+            libc::SIGILL => "SIGILL",
+            libc::SIGFPE => "SIGFPE",
+            libc::SIGABRT => "SIGABRT",
+            _ => "unknown signal",
+        };
+        // SAFETY: the system passes a valid `siginfo_t`.
+        let (code, addr) = unsafe { ((*info).si_code, (*info).si_addr().addr()) };
+        // Only faults detected by the kernel have an address to blame.
+        if code > 0 && signum != libc::SIGABRT {
+            rtprintpanic!(
+                "\nthread '{}' (id {}) received fatal signal {} at address {:#x}\n",
+                current_name(),
+                current_os_id(),
+                name,
+                addr
+            );
+        } else {
+            rtprintpanic!(
+                "\nthread '{}' (id {}) received fatal signal {}\n",
+                current_name(),
+                current_os_id(),
+                name
+            );
+        }
+        if CRASH_BACKTRACE.load(Ordering::Relaxed) {
+            report::print_backtrace();
+        }
+    }
+
+    pub fn status() -> super::Status {
+        let handlers_installed = [SIGSEGV, SIGBUS].iter().any(|&signal| {
+            // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
+            let mut action: sigaction = unsafe { mem::zeroed() };
+            // SAFETY: just fetches the current signal handler into action
+            unsafe { sigaction(signal, ptr::null_mut(), &mut action) };
+            action.sa_sigaction == signal_handler as sighandler_t
+        });
+        // SAFETY: assuming stack_t is zero-initializable
+        let mut stack: libc::stack_t = unsafe { mem::zeroed() };
+        // SAFETY: reads current stack_t into stack
+        unsafe { sigaltstack(ptr::null(), &mut stack) };
+        let (start, end) = GUARD.try_with(Cell::get).unwrap_or((0, 0));
+        super::Status {
+            handlers_installed,
+            altstack_active: stack.ss_flags & SS_DISABLE == 0,
+            guard: (start < end).then_some(start..end),
+            fault_signals_unblocked: FAULT_SIGNALS_UNBLOCKED.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether a fault at `addr` on the current thread would be reported as
+    /// a stack overflow, i.e. lies within the thread's guard range.
+    pub fn is_stack_overflow(addr: usize) -> bool {
+        let (start, end) = current_guard();
+        if start <= addr && addr < end {
+            return true;
+        }
+        // The main thread's guard may have moved since we computed it.
+        match moved_main_guard() {
+            Some(guard) => {
+                let _ = GUARD.try_with(|cell| cell.set((guard.start, guard.end)));
+                guard.contains(&addr)
+            }
+            None => false,
+        }
+    }
+
+    /// The current thread's guard range, from the registry once the thread's
+    /// TLS is gone.
+    fn current_guard() -> (usize, usize) {
+        GUARD.try_with(Cell::get).unwrap_or_else(|_| {
+            super::lookup_registered(current_os_id())
+                .map_or((0, 0), |(_, guard)| (guard.start, guard.end))
+        })
+    }
+
+    /// The name stashed by `set_current_name`, or `<unknown>` if there is none.
+    /// Only reads the thread's `NAME`, so it is fine to call from the handler.
+    fn current_name() -> &'static str {
+        let name = super::NAME.try_with(Cell::get).unwrap_or(ptr::null());
+        if name.is_null() {
+            return "<unknown>";
+        }
+        // SAFETY: `set_current_name` requires the name to outlive the slot.
+        // Names of `Thread`s are UTF-8 already, this doesn't allocate.
+        unsafe { CStr::from_ptr(name) }.to_str().unwrap_or("<unknown>")
+    }
+
+    /// The system's id for the current thread, the one that shows up in
+    /// logs, core dumps and debuggers (and `/proc/<pid>/task` on Linux).
+    /// Only makes async-signal-safe calls.
+    fn current_os_id() -> u64 {
+        // SAFETY: none of these have any preconditions.
+        unsafe {
+            cfg_if::cfg_if! {
+                if #[cfg(target_os = "linux")] {
+                    // glibc only has a `gettid` wrapper since 2.30.
+                    libc::syscall(libc::SYS_gettid) as u64
+                } else if #[cfg(target_os = "macos")] {
+                    let mut id = 0;
+                    // A null thread means the current one.
+                    libc::pthread_threadid_np(0, &mut id);
+                    id
+                } else if #[cfg(target_os = "freebsd")] {
+                    libc::pthread_getthreadid_np() as u64
+                } else if #[cfg(target_os = "netbsd")] {
+                    libc::_lwp_self() as u64
+                } else if #[cfg(target_os = "openbsd")] {
+                    libc::getthrid() as u64
+                } else if #[cfg(any(
+                    target_os = "solaris",
+                    target_os = "illumos",
+                    target_os = "aix",
+                ))] {
+                    libc::thr_self() as u64
+                } else if #[cfg(target_os = "haiku")] {
+                    libc::find_thread(ptr::null_mut()) as u64
+                } else {
+                    // DragonFly and Hurd: the pthread is all we have to go by.
+                    libc::pthread_self() as usize as u64
+                }
+            }
+        }
+    }
+
     static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
     static MAIN_ALTSTACK: AtomicPtr<libc::c_void> = AtomicPtr::new(ptr::null_mut());
+    static MAIN_ALTSTACK_SIZE: AtomicUsize = AtomicUsize::new(0);
     static NEED_ALTSTACK: AtomicBool = AtomicBool::new(false);
+    /// Whether altstacks are locked into memory, see `lock_pages`.
+    static LOCK_ALTSTACKS: AtomicBool = AtomicBool::new(false);
+    /// The `RLIMIT_STACK` the main thread's guard was computed for, zero if
+    /// there was no finite one.
+    static MAIN_STACK_LIMIT: AtomicUsize = AtomicUsize::new(0);
+    /// The dispositions of SIGSEGV and SIGBUS that `init` replaced, for
+    /// `cleanup` to put back.
+    static ORIGINAL_ACTIONS: [OnceLock<sigaction>; 2] = [OnceLock::new(), OnceLock::new()];
 
     /// # Safety
     /// Must be called only once
     #[forbid(unsafe_op_in_unsafe_fn)]
     pub unsafe fn init() {
         PAGE_SIZE.store(os::page_size(), Ordering::Relaxed);
+        let lock = crate::env::var_os("RUST_STACK_OVERFLOW_MLOCK");
+        LOCK_ALTSTACKS.store(lock.is_some_and(|lock| lock == "1"), Ordering::Relaxed);
 
-        // Always write to GUARD to ensure the TLS variable is allocated.
-        let guard = unsafe { install_main_guard().unwrap_or(0..0) };
-        GUARD.set((guard.start, guard.end));
+        // With the `lazy_stack_overflow` feature, this may run on another
+        // thread, which is then left for `register_current_thread` to set up.
+        let main_thread = super::ON_MAIN_THREAD.get();
+        if main_thread {
+            // Always write to GUARD to ensure the TLS variable is allocated.
+            let guard = unsafe { install_main_guard().unwrap_or(0..0) };
+            GUARD.set((guard.start, guard.end));
+            MAIN_THREAD.set(true);
+            MAIN_STACK_LIMIT.store(stack_limit().unwrap_or(0), Ordering::Relaxed);
+        }
 
         // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
         let mut action: sigaction = unsafe { mem::zeroed() };
-        for &signal in &[SIGSEGV, SIGBUS] {
+        for (&signal, original) in [SIGSEGV, SIGBUS].iter().zip(&ORIGINAL_ACTIONS) {
             // SAFETY: just fetches the current signal handler into action
             unsafe { sigaction(signal, ptr::null_mut(), &mut action) };
             // Configure our signal handler if one is not already set.
             if action.sa_sigaction == SIG_DFL {
+                let _ = original.set(action);
                 if !NEED_ALTSTACK.load(Ordering::Relaxed) {
                     // haven't set up our sigaltstack yet
                     NEED_ALTSTACK.store(true, Ordering::Release);
-                    let handler = unsafe { make_handler(true) };
-                    MAIN_ALTSTACK.store(handler.data, Ordering::Relaxed);
-                    mem::forget(handler);
+                    if main_thread {
+                        let handler = unsafe { make_handler(true) };
+                        MAIN_ALTSTACK_SIZE.store(handler.size, Ordering::Relaxed);
+                        MAIN_ALTSTACK.store(handler.data, Ordering::Relaxed);
+                        mem::forget(handler);
+                    }
+                    // SAFETY: `fork_child` only makes async-signal-safe calls.
+                    unsafe { libc::pthread_atfork(None, None, Some(fork_child)) };
                 }
-                action.sa_flags = SA_SIGINFO | SA_ONSTACK;
+                // `SA_NODEFER` lets a fault in the handler itself come back
+                // here instead of killing the process outright. The kernel can
+                // only deliver it if there's room for the signal frame below
+                // the faulting stack pointer, so this catches some overflows
+                // of the altstack, not all of them.
+                action.sa_flags = SA_SIGINFO | SA_ONSTACK | SA_NODEFER;
                 action.sa_sigaction = signal_handler as sighandler_t;
                 // SAFETY: only overriding signals if the default is set
                 unsafe { sigaction(signal, &action, ptr::null_mut()) };
@@ -158,38 +694,316 @@ pub unsafe fn init() {
     /// Must be called only once
     #[forbid(unsafe_op_in_unsafe_fn)]
     pub unsafe fn cleanup() {
+        // Unregister first: once our handler is gone, no fault can end up on
+        // the altstack while we unmap it. Handlers that were installed over
+        // ours in the meantime are left alone.
+        for (&signal, original) in [SIGSEGV, SIGBUS].iter().zip(&ORIGINAL_ACTIONS) {
+            let Some(original) = original.get() else { continue };
+            // SAFETY: assuming all platforms define struct sigaction as "zero-initializable"
+            let mut action: sigaction = unsafe { mem::zeroed() };
+            // SAFETY: just fetches the current signal handler into action
+            unsafe { sigaction(signal, ptr::null_mut(), &mut action) };
+            if action.sa_sigaction == signal_handler as sighandler_t {
+                // SAFETY: restores the disposition we replaced in `init`
+                unsafe { sigaction(signal, original, ptr::null_mut()) };
+            }
+        }
+
         // FIXME: I probably cause more bugs than I'm worth!
         // see https://github.com/rust-lang/rust/issues/111272
-        unsafe { drop_handler(MAIN_ALTSTACK.load(Ordering::Relaxed)) };
+        // Forks from here on must not reinstall it, see `fork_child`.
+        let data = MAIN_ALTSTACK.swap(ptr::null_mut(), Ordering::Relaxed);
+        unsafe { drop_handler(data, MAIN_ALTSTACK_SIZE.load(Ordering::Relaxed)) };
     }
 
-    unsafe fn get_stack() -> libc::stack_t {
-        // OpenBSD requires this flag for stack mapping
-        // otherwise the said mapping will fail as a no-op on most systems
-        // and has a different meaning on FreeBSD
-        #[cfg(any(
-            target_os = "openbsd",
-            target_os = "netbsd",
+    /// Runs in the child after `fork`, where the forking thread is the only
+    /// one left, and under a new id. Like the child between `fork` and `exec`
+    /// in general, this may only make async-signal-safe calls.
+    extern "C" fn fork_child() {
+        super::registry::reset_after_fork(current_os_id());
+
+        // The forking thread may be without an altstack, e.g. a foreign
+        // thread that was never registered or the main thread forking from a
+        // signal handler while its altstack was disarmed. Unless the main
+        // thread forked, nothing uses the main thread's altstack anymore, so
+        // it can take that one. Its guard range stays as it is: it either
+        // came along with the thread, or was never known.
+        let data = MAIN_ALTSTACK.load(Ordering::Relaxed);
+        if data.is_null() {
+            return;
+        }
+        // SAFETY: assuming stack_t is zero-initializable
+        let mut current: libc::stack_t = unsafe { mem::zeroed() };
+        // SAFETY: reads current stack_t into current
+        unsafe { sigaltstack(ptr::null(), &mut current) };
+        if current.ss_flags & SS_DISABLE != 0 {
+            let size = MAIN_ALTSTACK_SIZE.load(Ordering::Relaxed);
+            let stack = libc::stack_t { ss_sp: data, ss_flags: 0, ss_size: size };
+            // SAFETY: `data` is the altstack `init` mapped, which no other
+            // thread uses anymore.
+            unsafe { set_altstack(&stack) };
+            let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+            let _ =
+                ALTSTACK_GUARD.try_with(|cell| cell.set((data.addr() - page_size, data.addr())));
+        } else if current.ss_sp != data && !LOCK_ALTSTACKS.load(Ordering::Relaxed) {
+            // Nothing in the child runs on it, but it stays mapped for the
+            // next fork to hand out.
+            // SAFETY: `data` is the altstack `init` mapped, which is unused.
+            unsafe { release_pages(data, MAIN_ALTSTACK_SIZE.load(Ordering::Relaxed)) };
+        }
+    }
+
+    /// Recomputes the current thread's guard range, e.g. after the main
+    /// thread's stack limit was changed.
+    ///
+    /// # Safety
+    /// Must not be called from a signal handler.
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    pub unsafe fn refresh_guard() {
+        if !NEED_ALTSTACK.load(Ordering::Acquire) {
+            return;
+        }
+        let guard = if MAIN_THREAD.get() {
+            MAIN_STACK_LIMIT.store(stack_limit().unwrap_or(0), Ordering::Relaxed);
+            unsafe { refresh_main_guard() }
+        } else {
+            unsafe { thread_guard() }
+        };
+        if let Some(guard) = guard {
+            GUARD.set((guard.start, guard.end));
+        }
+    }
+
+    /// How much of the current thread's stack is left below `sp`, going by
+    /// where its guard starts.
+    pub fn remaining_stack(sp: usize) -> Option<usize> {
+        let (_, end) = GUARD.get();
+        (end != 0).then(|| sp.saturating_sub(end))
+    }
+
+    /// Runs `f` on a new stack of at least `size` bytes, mapped with a guard
+    /// page below it like an altstack. While `f` runs, that page is the
+    /// current thread's guard, so `remaining_stack` measures the new stack and
+    /// overflowing it is reported like any other stack overflow. A panic in
+    /// `f` is propagated once the old stack is back.
+    pub fn run_on_new_stack(size: usize, f: &mut dyn FnMut()) -> io::Result<()> {
+        if !CAN_SWITCH_STACKS {
+            return Err(io::const_io_error!(
+                io::ErrorKind::Unsupported,
+                "switching stacks is not supported on this architecture",
+            ));
+        }
+        let page_size = os::page_size();
+        let Some(size) = size.max(1).checked_next_multiple_of(page_size) else {
+            return Err(io::const_io_error!(io::ErrorKind::InvalidInput, "stack size too large"));
+        };
+
+        // SAFETY: maps fresh memory, and only protects and unmaps that.
+        let base = unsafe {
+            let base = mmap64(
+                ptr::null_mut(),
+                size + page_size,
+                PROT_READ | PROT_WRITE,
+                STACK_MAP_FLAGS,
+                -1,
+                0,
+            );
+            if base == MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            if mprotect(base, page_size, PROT_NONE) != 0 {
+                let err = io::Error::last_os_error();
+                munmap(base, size + page_size);
+                return Err(err);
+            }
+            base
+        };
+
+        let guard = GUARD.replace((base.addr(), base.addr() + page_size));
+        let mut result = Ok(());
+        // SAFETY: the top of the mapping is page-aligned, and the mapping
+        // stays until `f` has returned. Nothing unwinds out of the closure.
+        unsafe {
+            call_on_stack(base.addr() + page_size + size, &mut || {
+                result = panic::catch_unwind(AssertUnwindSafe(&mut *f));
+            });
+        }
+        GUARD.set(guard);
+        // SAFETY: nothing runs on the new stack anymore.
+        unsafe { munmap(base, size + page_size) };
+
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+        Ok(())
+    }
+
+    /// Whether `call_on_stack` is implemented for this architecture.
+    const CAN_SWITCH_STACKS: bool = cfg!(any(
+        all(target_arch = "x86_64", target_pointer_width = "64"),
+        target_arch = "aarch64",
+    ));
+
+    /// Passed to `call_on_stack`'s assembly to call the closure it was given.
+    #[cfg(any(all(target_arch = "x86_64", target_pointer_width = "64"), target_arch = "aarch64"))]
+    unsafe extern "C" fn call_closure(f: *mut &mut dyn FnMut()) {
+        // SAFETY: `call_on_stack` passes a pointer to its closure.
+        unsafe { (*f)() }
+    }
+
+    /// Calls `f` with the stack pointer at `top`, and puts the stack pointer
+    /// back when it returns.
+    ///
+    /// # Safety
+    /// `top` must be 16-byte aligned, with enough writable stack below it for
+    /// `f`. `f` must not unwind.
+    #[cfg(all(target_arch = "x86_64", target_pointer_width = "64"))]
+    unsafe fn call_on_stack(top: usize, mut f: &mut dyn FnMut()) {
+        // SAFETY: `r12` is callee-saved, so it holds the old stack pointer
+        // across the call. The caller guarantees the new stack is fit for it.
+        unsafe {
+            core::arch::asm!(
+                "mov r12, rsp",
+                "mov rsp, {top}",
+                "call {call_closure}",
+                "mov rsp, r12",
+                top = in(reg) top,
+                call_closure = sym call_closure,
+                in("rdi") ptr::addr_of_mut!(f),
+                out("r12") _,
+                clobber_abi("C"),
+            );
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn call_on_stack(top: usize, mut f: &mut dyn FnMut()) {
+        // SAFETY: as on x86_64, with `x20` to hold the old stack pointer.
+        unsafe {
+            core::arch::asm!(
+                "mov x20, sp",
+                "mov sp, {top}",
+                "bl {call_closure}",
+                "mov sp, x20",
+                top = in(reg) top,
+                call_closure = sym call_closure,
+                in("x0") ptr::addr_of_mut!(f),
+                out("x20") _,
+                clobber_abi("C"),
+            );
+        }
+    }
+
+    #[cfg(not(any(
+        all(target_arch = "x86_64", target_pointer_width = "64"),
+        target_arch = "aarch64"
+    )))]
+    unsafe fn call_on_stack(_top: usize, _f: &mut dyn FnMut()) {
+        unreachable!("`run_on_new_stack` checks `CAN_SWITCH_STACKS`")
+    }
+
+    /// The current thread's id, stack bounds and guard range for the registry.
+    pub fn registry_info() -> Option<(u64, Range<usize>, Range<usize>)> {
+        let (start, end) = GUARD.try_with(Cell::get).unwrap_or((0, 0));
+        if start == end {
+            return None;
+        }
+        // SAFETY: only queries the current thread.
+        let (stack_start, stack_end) = unsafe { get_stack_bounds() }?;
+        Some((current_os_id(), stack_start.addr()..stack_end, start..end))
+    }
+
+    /// The soft `RLIMIT_STACK`, if it is finite.
+    fn stack_limit() -> Option<usize> {
+        // SAFETY: all-zero is a valid `rlimit`, and `getrlimit` is
+        // async-signal-safe.
+        let mut limit: libc::rlimit = unsafe { mem::zeroed() };
+        if unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut limit) } != 0
+            || limit.rlim_cur == libc::RLIM_INFINITY
+        {
+            return None;
+        }
+        Some(limit.rlim_cur as usize)
+    }
+
+    /// Where the main thread's guard is now, if it moved since we last
+    /// computed it because `RLIMIT_STACK` was changed.
+    ///
+    /// On Linux (except musl, where we don't know the guard to begin with)
+    /// and AIX, the kernel grows the main thread's stack on demand up to the
+    /// current limit, and the guard we compute sits right below the bottom
+    /// that the limit implies. When the limit changes, so does the address
+    /// the stack overflows at. The signal handler uses this on faults outside
+    /// the known guard, so it must stick to async-signal-safe calls.
+    fn moved_main_guard() -> Option<Range<usize>> {
+        if !cfg!(any(all(target_os = "linux", not(target_env = "musl")), target_os = "aix"))
+            || !MAIN_THREAD.get()
+        {
+            return None;
+        }
+        let old_limit = MAIN_STACK_LIMIT.load(Ordering::Relaxed);
+        let new_limit = stack_limit()?;
+        let (start, end) = GUARD.get();
+        let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+        // A larger guard is the kernel's guard gap, which the limit didn't
+        // decide, see `install_main_guard_linux`.
+        if old_limit == 0 || old_limit == new_limit || end == 0 || end - start != page_size {
+            return None;
+        }
+        // The top of the stack is page aligned and `end` is the bottom the
+        // old limit implied, rounded up to a page (see `stack_start_aligned`).
+        let top = (end + old_limit) & !(page_size - 1);
+        let bottom = top.checked_sub(new_limit)?.next_multiple_of(page_size);
+        MAIN_STACK_LIMIT.store(new_limit, Ordering::Relaxed);
+        Some(bottom - page_size..bottom)
+    }
+
+    /// Recomputes the main thread's guard on the platforms where it is
+    /// derived from the stack limit instead of being a mapping of our own.
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe fn refresh_main_guard() -> Option<Range<usize>> {
+        if cfg!(any(
             target_os = "linux",
-            target_os = "dragonfly",
-        ))]
-        let flags = MAP_PRIVATE | MAP_ANON | libc::MAP_STACK;
-        #[cfg(not(any(
-            target_os = "openbsd",
+            target_os = "freebsd",
+            target_os = "aix",
+            target_os = "haiku",
             target_os = "netbsd",
-            target_os = "linux",
-            target_os = "dragonfly",
-        )))]
-        let flags = MAP_PRIVATE | MAP_ANON;
+            target_os = "openbsd",
+            target_os = "dragonfly"
+        )) {
+            // None of these map or protect anything.
+            unsafe { install_main_guard() }
+        } else {
+            None
+        }
+    }
+
+    // OpenBSD requires this flag for stack mapping
+    // otherwise the said mapping will fail as a no-op on most systems
+    // and has a different meaning on FreeBSD
+    #[cfg(any(
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "linux",
+        target_os = "dragonfly",
+    ))]
+    const STACK_MAP_FLAGS: libc::c_int = MAP_PRIVATE | MAP_ANON | libc::MAP_STACK;
+    #[cfg(not(any(
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "linux",
+        target_os = "dragonfly",
+    )))]
+    const STACK_MAP_FLAGS: libc::c_int = MAP_PRIVATE | MAP_ANON;
 
-        let sigstack_size = sigstack_size();
+    unsafe fn get_stack(sigstack_size: usize) -> libc::stack_t {
         let page_size = PAGE_SIZE.load(Ordering::Relaxed);
 
         let stackp = mmap64(
             ptr::null_mut(),
             sigstack_size + page_size,
             PROT_READ | PROT_WRITE,
-            flags,
+            STACK_MAP_FLAGS,
             -1,
             0,
         );
@@ -201,10 +1015,35 @@ unsafe fn get_stack() -> libc::stack_t {
             panic!("failed to set up alternative stack guard page: {}", io::Error::last_os_error());
         }
         let stackp = stackp.add(page_size);
+        if LOCK_ALTSTACKS.load(Ordering::Relaxed) {
+            lock_pages(stackp, sigstack_size);
+        }
 
         libc::stack_t { ss_sp: stackp, ss_flags: 0, ss_size: sigstack_size }
     }
 
+    /// Keeps the pages of a new altstack resident, for `RUST_STACK_OVERFLOW_MLOCK`.
+    /// Otherwise, the handler needs memory to run at all, which it may not get
+    /// when memory is short: the first touch of a page can fail, or block on
+    /// reclaim in a cgroup at its limit. Without the privilege or the
+    /// `RLIMIT_MEMLOCK` to lock them, the pages are at least faulted in now,
+    /// while the system still has memory to give.
+    ///
+    /// The lock goes away with the mapping in `drop_handler`, and isn't
+    /// inherited by children after `fork`.
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe fn lock_pages(data: *mut libc::c_void, size: usize) {
+        // SAFETY: the range is the altstack `get_stack` just mapped.
+        if unsafe { libc::mlock(data, size) } == 0 {
+            return;
+        }
+        let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+        for offset in (0..size).step_by(page_size) {
+            // SAFETY: within the mapping, which nothing uses yet.
+            unsafe { data.cast::<u8>().add(offset).write_volatile(0) };
+        }
+    }
+
     /// # Safety
     /// Mutates the alternate signal stack
     #[forbid(unsafe_op_in_unsafe_fn)]
@@ -213,9 +1052,14 @@ pub unsafe fn make_handler(main_thread: bool) -> Handler {
             return Handler::null();
         }
 
+        // The handler is no use if the faults can't reach it.
+        if unblock_fault_signals() {
+            FAULT_SIGNALS_UNBLOCKED.store(true, Ordering::Relaxed);
+        }
+
         if !main_thread {
             // Always write to GUARD to ensure the TLS variable is allocated.
-            let guard = unsafe { current_guard() }.unwrap_or(0..0);
+            let guard = unsafe { thread_guard() }.unwrap_or(0..0);
             GUARD.set((guard.start, guard.end));
         }
 
@@ -227,42 +1071,181 @@ pub unsafe fn make_handler(main_thread: bool) -> Handler {
         if stack.ss_flags & SS_DISABLE != 0 {
             // SAFETY: We warned our caller this would happen!
             unsafe {
-                stack = get_stack();
-                sigaltstack(&stack, ptr::null_mut());
+                stack = get_stack(altstack_size());
+                set_altstack(&stack);
             }
-            Handler { data: stack.ss_sp as *mut libc::c_void }
+            // `get_stack` maps a guard page right below the altstack.
+            let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+            ALTSTACK_GUARD.set((stack.ss_sp.addr() - page_size, stack.ss_sp.addr()));
+            Handler { data: stack.ss_sp as *mut libc::c_void, size: stack.ss_size }
         } else {
             Handler::null()
         }
     }
 
+    /// Whether `unblock_fault_signals` found anything to unblock.
+    static FAULT_SIGNALS_UNBLOCKED: AtomicBool = AtomicBool::new(false);
+
+    /// Unblocks SIGSEGV and SIGBUS on the current thread, and returns whether
+    /// either of them was blocked. A fault while they are blocked kills the
+    /// process without running any handler. The mask can come from the parent
+    /// process or from whatever spawned the thread, and threads spawned from
+    /// here on inherit it.
+    fn unblock_fault_signals() -> bool {
+        // SAFETY: all-zero is a valid `sigset_t`, and these only fill in or
+        // read the sets and the current thread's mask.
+        unsafe {
+            let mut set: libc::sigset_t = mem::zeroed();
+            let mut old: libc::sigset_t = mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, SIGSEGV);
+            libc::sigaddset(&mut set, SIGBUS);
+            if libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, &mut old) != 0 {
+                return false;
+            }
+            libc::sigismember(&old, SIGSEGV) == 1 || libc::sigismember(&old, SIGBUS) == 1
+        }
+    }
+
+    /// Has the kernel disarm the altstack while a handler runs on it, and arm
+    /// it again when the handler returns. Without it, a handler that switches
+    /// to another context (`swapcontext`, fiber libraries) leaves the kernel
+    /// believing the altstack is free, and the next signal is delivered on top
+    /// of the frames still in use.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const SS_AUTODISARM: libc::c_int = 1 << 31;
+
+    /// Whether the kernel rejected `SS_AUTODISARM`, which it only supports
+    /// since Linux 4.7.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    static NO_AUTODISARM: AtomicBool = AtomicBool::new(false);
+
+    /// Installs `stack` as the current thread's altstack, with
+    /// `SS_AUTODISARM` where the kernel supports it.
+    unsafe fn set_altstack(stack: &libc::stack_t) {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if !NO_AUTODISARM.load(Ordering::Relaxed) {
+            let autodisarm = libc::stack_t { ss_flags: stack.ss_flags | SS_AUTODISARM, ..*stack };
+            // SAFETY: the caller guarantees `stack` is fit to be the altstack.
+            if unsafe { sigaltstack(&autodisarm, ptr::null_mut()) } == 0 {
+                return;
+            }
+            // Older kernels reject flags they don't know with EINVAL.
+            if io::Error::last_os_error().raw_os_error() != Some(libc::EINVAL) {
+                return;
+            }
+            NO_AUTODISARM.store(true, Ordering::Relaxed);
+        }
+        // SAFETY: the caller guarantees `stack` is fit to be the altstack.
+        unsafe { sigaltstack(stack, ptr::null_mut()) };
+    }
+
     /// # Safety
     /// Must be called
     /// - only with our handler or nullptr
     /// - only when done with our altstack
-    /// This disables the alternate signal stack!
+    /// This disables the alternate signal stack, if it is still ours!
     #[forbid(unsafe_op_in_unsafe_fn)]
-    pub unsafe fn drop_handler(data: *mut libc::c_void) {
+    pub unsafe fn drop_handler(data: *mut libc::c_void, sigstack_size: usize) {
         if !data.is_null() {
-            let sigstack_size = sigstack_size();
             let page_size = PAGE_SIZE.load(Ordering::Relaxed);
-            let disabling_stack = libc::stack_t {
-                ss_sp: ptr::null_mut(),
-                ss_flags: SS_DISABLE,
-                // Workaround for bug in macOS implementation of sigaltstack
-                // UNIX2003 which returns ENOMEM when disabling a stack while
-                // passing ss_size smaller than MINSIGSTKSZ. According to POSIX
-                // both ss_sp and ss_size should be ignored in this case.
-                ss_size: sigstack_size,
-            };
-            // SAFETY: we warned the caller this disables the alternate signal stack!
-            unsafe { sigaltstack(&disabling_stack, ptr::null_mut()) };
+            // The application may have replaced our altstack with its own in
+            // the meantime. That one is none of our business, so only disable
+            // the altstack if it is still the one we installed. Ours is unused
+            // either way and can be unmapped.
+            // SAFETY: assuming stack_t is zero-initializable
+            let mut current: libc::stack_t = unsafe { mem::zeroed() };
+            // SAFETY: reads current stack_t into current
+            unsafe { sigaltstack(ptr::null(), &mut current) };
+            if current.ss_flags & SS_DISABLE == 0 && current.ss_sp == data {
+                let disabling_stack = libc::stack_t {
+                    ss_sp: ptr::null_mut(),
+                    ss_flags: SS_DISABLE,
+                    // Workaround for bug in macOS implementation of sigaltstack
+                    // UNIX2003 which returns ENOMEM when disabling a stack while
+                    // passing ss_size smaller than MINSIGSTKSZ. According to POSIX
+                    // both ss_sp and ss_size should be ignored in this case.
+                    ss_size: sigstack_size,
+                };
+                // SAFETY: we warned the caller this disables the alternate signal stack!
+                unsafe { sigaltstack(&disabling_stack, ptr::null_mut()) };
+            }
+            let _ = ALTSTACK_GUARD.try_with(|cell| {
+                if cell.get().1 == data.addr() {
+                    cell.set((0, 0));
+                }
+            });
             // SAFETY: We know from `get_stackp` that the alternate stack we installed is part of
             // a mapping that started one page earlier, so walk back a page and unmap from there.
             unsafe { munmap(data.sub(page_size), sigstack_size + page_size) };
         }
     }
 
+    /// Lets the system reclaim the memory of the current thread's altstack,
+    /// if it is ours and no handler is running on it. The pages stay mapped
+    /// and are faulted back in when the next signal is handled on them.
+    pub fn trim_altstack() {
+        // SAFETY: assuming stack_t is zero-initializable
+        let mut current: libc::stack_t = unsafe { mem::zeroed() };
+        // SAFETY: reads current stack_t into current
+        unsafe { sigaltstack(ptr::null(), &mut current) };
+        // A handler running on the altstack shows up as `SS_ONSTACK`, or as
+        // `SS_DISABLE` with `SS_AUTODISARM`. Locked altstacks are meant to
+        // stay resident.
+        if current.ss_flags & (SS_DISABLE | SS_ONSTACK) != 0
+            || LOCK_ALTSTACKS.load(Ordering::Relaxed)
+        {
+            return;
+        }
+        let ours = ALTSTACK_GUARD
+            .try_with(Cell::get)
+            .is_ok_and(|(_, end)| end != 0 && end == current.ss_sp.addr());
+        if ours {
+            // SAFETY: the altstack is one `get_stack` mapped, and unused.
+            unsafe { release_pages(current.ss_sp, current.ss_size) };
+        }
+    }
+
+    /// Hands the pages of an unused altstack back to the system, keeping its
+    /// address range reserved. Only makes async-signal-safe calls.
+    ///
+    /// # Safety
+    /// `data` and `size` must describe an altstack from `get_stack` that
+    /// nothing is running on.
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe fn release_pages(data: *mut libc::c_void, size: usize) {
+        let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+        // `get_stack` mapped whole pages, so this stays within the mapping.
+        let len = size.next_multiple_of(page_size);
+        // `MADV_FREE` only has the pages reclaimed when memory gets short,
+        // which saves faulting them back in otherwise.
+        #[cfg(not(any(target_os = "aix", target_os = "hurd")))]
+        // SAFETY: the caller guarantees the range is ours and unused.
+        if unsafe { libc::madvise(data, len, libc::MADV_FREE) } == 0 {
+            return;
+        }
+        // Linux only supports `MADV_FREE` since 4.5.
+        // SAFETY: the caller guarantees the range is ours and unused.
+        unsafe { libc::madvise(data, len, libc::MADV_DONTNEED) };
+    }
+
+    /// The size of the altstack needed to print a backtrace from the handler.
+    /// Symbolization and formatting take far more than the `SIGSTKSZ` the
+    /// handler gets by with otherwise, and running out of altstack while
+    /// reporting an overflow loses the report.
+    const BACKTRACE_SIGSTACK_SIZE: usize = 256 * 1024;
+
+    /// The size of the altstack for a thread that is being set up now. It is
+    /// only as large as backtraces need if they are asked for at this point,
+    /// to keep the memory of the many threads that never fault small.
+    fn altstack_size() -> usize {
+        let backtraces = report::report_mode() == report::ReportMode::Backtrace
+            || CRASH_BACKTRACE.load(Ordering::Relaxed)
+            || crate::panic::get_backtrace_style()
+                .is_some_and(|style| style != crate::panic::BacktraceStyle::Off);
+        if backtraces { sigstack_size().max(BACKTRACE_SIGSTACK_SIZE) } else { sigstack_size() }
+    }
+
     /// Modern kernels on modern hardware can have dynamic signal stack sizes.
     #[cfg(any(target_os = "linux", target_os = "android"))]
     fn sigstack_size() -> usize {
@@ -275,28 +1258,92 @@ fn sigstack_size() -> usize {
         libc::SIGSTKSZ.max(dynamic_sigstksz as _)
     }
 
-    /// Not all OS support hardware where this is needed.
+    /// Elsewhere, `SIGSTKSZ` is a constant that may be too small for the
+    /// signal frames of newer hardware, so make room for those where we can
+    /// tell how large they get.
     #[cfg(not(any(target_os = "linux", target_os = "android")))]
     fn sigstack_size() -> usize {
-        libc::SIGSTKSZ
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "hurd")] {
+                // glibc's `SIGSTKSZ` is only a constant for compatibility, the
+                // size it actually recommends depends on the CPU.
+                // SAFETY: `sysconf` has no preconditions.
+                let size = unsafe { libc::sysconf(libc::_SC_SIGSTKSZ) };
+                libc::SIGSTKSZ.max(size.try_into().unwrap_or(0))
+            } else if #[cfg(target_arch = "x86_64")] {
+                libc::SIGSTKSZ + xsave_size().saturating_sub(FXSAVE_SIZE)
+            } else {
+                libc::SIGSTKSZ
+            }
+        }
+    }
+
+    /// The size of the legacy x87/SSE state in the signal frame, which is all
+    /// that the `SIGSTKSZ` of most systems was chosen for.
+    #[cfg(all(not(any(target_os = "linux", target_os = "android")), target_arch = "x86_64"))]
+    const FXSAVE_SIZE: usize = 512;
+
+    /// The size of the XSAVE area the kernel saves into the signal frame, for
+    /// the state components it enabled. AVX-512 takes it to about 2.5 KiB,
+    /// AMX to over 10 KiB.
+    #[cfg(all(not(any(target_os = "linux", target_os = "android")), target_arch = "x86_64"))]
+    fn xsave_size() -> usize {
+        use crate::arch::x86_64::{__cpuid, __cpuid_count};
+
+        // SAFETY: every x86_64 CPU has `cpuid`.
+        unsafe {
+            // Leaf 1 says whether the OS enabled XSAVE, leaf 0xd how large it
+            // made the area.
+            if __cpuid(0).eax < 0xd || __cpuid(1).ecx & (1 << 27) == 0 {
+                return 0;
+            }
+            __cpuid_count(0xd, 0).ebx as usize
+        }
     }
 
-    #[cfg(any(target_os = "solaris", target_os = "illumos"))]
     unsafe fn get_stack_start() -> Option<*mut libc::c_void> {
+        get_stack_bounds().map(|(start, _)| start)
+    }
+
+    /// The lowest address of the current thread's stack, and the address
+    /// right above its highest one.
+    #[cfg(any(target_os = "solaris", target_os = "illumos"))]
+    unsafe fn get_stack_bounds() -> Option<(*mut libc::c_void, usize)> {
         let mut current_stack: libc::stack_t = crate::mem::zeroed();
         assert_eq!(libc::stack_getbounds(&mut current_stack), 0);
-        Some(current_stack.ss_sp)
+        Some((current_stack.ss_sp, current_stack.ss_sp.addr() + current_stack.ss_size))
     }
 
     #[cfg(target_os = "macos")]
-    unsafe fn get_stack_start() -> Option<*mut libc::c_void> {
+    unsafe fn get_stack_bounds() -> Option<(*mut libc::c_void, usize)> {
         let th = libc::pthread_self();
         let stackptr = libc::pthread_get_stackaddr_np(th);
-        Some(stackptr.map_addr(|addr| addr - libc::pthread_get_stacksize_np(th)))
+        let mut size = libc::pthread_get_stacksize_np(th);
+        // The kernel reserves the main thread's stack at exec, as large as
+        // `RLIMIT_STACK` says. That can be set by `ulimit -s` or launchd to
+        // something else than what libpthread reports for the main thread,
+        // and then we would put the guard where the stack doesn't end. The
+        // program could have changed the limit since exec, but `init` runs
+        // before it normally gets the chance to.
+        if libc::pthread_main_np() == 1 {
+            if let Some(limit) = stack_limit() {
+                size = limit;
+            }
+        }
+        Some((stackptr.map_addr(|addr| addr - size), stackptr.addr()))
+    }
+
+    #[cfg(target_os = "haiku")]
+    unsafe fn get_stack_bounds() -> Option<(*mut libc::c_void, usize)> {
+        let mut info: libc::thread_info = crate::mem::zeroed();
+        if libc::get_thread_info(libc::find_thread(ptr::null_mut()), &mut info) != libc::B_OK {
+            return None;
+        }
+        Some((info.stack_base, info.stack_end.addr()))
     }
 
     #[cfg(target_os = "openbsd")]
-    unsafe fn get_stack_start() -> Option<*mut libc::c_void> {
+    unsafe fn get_stack_bounds() -> Option<(*mut libc::c_void, usize)> {
         let mut current_stack: libc::stack_t = crate::mem::zeroed();
         assert_eq!(libc::pthread_stackseg_np(libc::pthread_self(), &mut current_stack), 0);
 
@@ -308,38 +1355,120 @@ unsafe fn get_stack_start() -> Option<*mut libc::c_void> {
             // new thread
             stack_ptr.addr() - current_stack.ss_size
         };
-        Some(stack_ptr.with_addr(stackaddr))
+        Some((stack_ptr.with_addr(stackaddr), stack_ptr.addr()))
     }
 
     #[cfg(any(
+        target_os = "aix",
         target_os = "android",
         target_os = "freebsd",
+        target_os = "dragonfly",
         target_os = "netbsd",
         target_os = "hurd",
         target_os = "linux",
         target_os = "l4re"
     ))]
-    unsafe fn get_stack_start() -> Option<*mut libc::c_void> {
+    unsafe fn get_stack_bounds() -> Option<(*mut libc::c_void, usize)> {
         let mut ret = None;
         let mut attr: libc::pthread_attr_t = crate::mem::zeroed();
-        #[cfg(target_os = "freebsd")]
+        #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
         assert_eq!(libc::pthread_attr_init(&mut attr), 0);
-        #[cfg(target_os = "freebsd")]
+        #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
         let e = libc::pthread_attr_get_np(libc::pthread_self(), &mut attr);
-        #[cfg(not(target_os = "freebsd"))]
+        #[cfg(not(any(target_os = "freebsd", target_os = "dragonfly")))]
         let e = libc::pthread_getattr_np(libc::pthread_self(), &mut attr);
         if e == 0 {
-            let mut stackaddr = crate::ptr::null_mut();
+            let mut stackaddr = crate::ptr::null_mut::<libc::c_void>();
             let mut stacksize = 0;
             assert_eq!(libc::pthread_attr_getstack(&attr, &mut stackaddr, &mut stacksize), 0);
-            ret = Some(stackaddr);
+            ret = Some((stackaddr, stackaddr.addr() + stacksize));
         }
-        if e == 0 || cfg!(target_os = "freebsd") {
+        if e == 0 || cfg!(any(target_os = "freebsd", target_os = "dragonfly")) {
             assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
         }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if e != 0 {
+            ret = stack_bounds_from_maps();
+        }
         ret
     }
 
+    /// The bounds of the current thread's stack according to
+    /// `/proc/self/maps`, for when `pthread_getattr_np` fails, as it does with
+    /// some alternative libcs and in sandboxes that hide parts of `/proc`
+    /// from it. This is the mapping the stack pointer is in, or for the main
+    /// thread, the part of it `RLIMIT_STACK` and the mapping below let it grow
+    /// to, as glibc would report it.
+    ///
+    /// Parses the file in a fixed buffer, without allocating, and only makes
+    /// async-signal-safe calls.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unsafe fn stack_bounds_from_maps() -> Option<(*mut libc::c_void, usize)> {
+        /// The start and end of the mapping described by `line`, and whether
+        /// it is the main thread's stack.
+        fn parse(line: &[u8]) -> Option<(usize, usize, bool)> {
+            let range = line.split(|&b| b == b' ').next()?;
+            let dash = range.iter().position(|&b| b == b'-')?;
+            let hex = |digits| usize::from_str_radix(crate::str::from_utf8(digits).ok()?, 16).ok();
+            Some((hex(&range[..dash])?, hex(&range[dash + 1..])?, line.ends_with(b"[stack]")))
+        }
+
+        let local = 0u8;
+        let sp = ptr::addr_of!(local).addr();
+        let fd = libc::open(c"/proc/self/maps".as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return None;
+        }
+
+        // Long enough for any line: paths are at most `PATH_MAX` bytes.
+        let mut buf = [0u8; 8192];
+        let mut len = 0;
+        let mut found = None;
+        let mut prev_end = 0;
+        'read: loop {
+            let n = libc::read(fd, buf[len..].as_mut_ptr().cast(), buf.len() - len);
+            if n < 0 && io::Error::last_os_error().is_interrupted() {
+                continue;
+            }
+            if n <= 0 {
+                break;
+            }
+            len += n as usize;
+
+            let mut consumed = 0;
+            while let Some(end) = buf[consumed..len].iter().position(|&b| b == b'\n') {
+                let line = &buf[consumed..consumed + end];
+                consumed += end + 1;
+                match parse(line) {
+                    Some((start, end, main)) if start <= sp && sp < end => {
+                        found = Some((start, end, main));
+                        break 'read;
+                    }
+                    Some((_, end, _)) => prev_end = end,
+                    None => {}
+                }
+            }
+            if consumed == 0 && len == buf.len() {
+                // Not a line we could make sense of anyway.
+                break;
+            }
+            buf.copy_within(consumed..len, 0);
+            len -= consumed;
+        }
+        libc::close(fd);
+
+        let (mut start, end, main) = found?;
+        if main {
+            // The mapping grows down as the stack is used, up to the limit,
+            // but not into the mapping below.
+            start = match stack_limit() {
+                Some(limit) => end.saturating_sub(limit).max(prev_end),
+                None => prev_end,
+            };
+        }
+        Some((ptr::without_provenance_mut(start), end))
+    }
+
     fn stack_start_aligned(page_size: usize) -> Option<*mut libc::c_void> {
         let stackptr = unsafe { get_stack_start()? };
         let stackaddr = stackptr.addr();
@@ -370,7 +1499,15 @@ unsafe fn install_main_guard() -> Option<Range<usize>> {
                 install_main_guard_linux_musl(page_size)
             } else if cfg!(target_os = "freebsd") {
                 install_main_guard_freebsd(page_size)
-            } else if cfg!(any(target_os = "netbsd", target_os = "openbsd")) {
+            } else if cfg!(target_os = "aix") {
+                install_main_guard_aix(page_size)
+            } else if cfg!(target_os = "haiku") {
+                install_main_guard_haiku(page_size)
+            } else if cfg!(any(
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly"
+            )) {
                 install_main_guard_bsds(page_size)
             } else {
                 install_main_guard_default(page_size)
@@ -392,9 +1529,24 @@ unsafe fn install_main_guard_linux(page_size: usize) -> Option<Range<usize>> {
         // trust that the kernel's own stack guard will work.
         let stackptr = stack_start_aligned(page_size)?;
         let stackaddr = stackptr.addr();
+
+        // Where the limit is unlimited, or reaches further down than the next
+        // mapping below the stack, glibc reports the stack as ending where
+        // that mapping does. It's the kernel's guard gap that stops the stack
+        // from growing then, so that's where it faults instead.
+        // Short by less than a page is just the limit being rounded.
+        let (_, end) = unsafe { get_stack_bounds()? };
+        if stack_limit().is_none_or(|limit| end - stackaddr + page_size <= limit) {
+            return Some(stackaddr..stackaddr + STACK_GUARD_GAP_PAGES * page_size);
+        }
         Some(stackaddr - page_size..stackaddr)
     }
 
+    /// The kernel's default `stack_guard_gap`, the distance it keeps between
+    /// a growing stack and the mapping below it. It can only be changed on
+    /// the kernel command line, which we don't look at.
+    const STACK_GUARD_GAP_PAGES: usize = 256;
+
     #[forbid(unsafe_op_in_unsafe_fn)]
     unsafe fn install_main_guard_linux_musl(_page_size: usize) -> Option<Range<usize>> {
         // For the main thread, the musl's pthread_attr_getstack
@@ -412,37 +1564,46 @@ unsafe fn install_main_guard_freebsd(page_size: usize) -> Option<Range<usize>> {
         // the builtin guard page.
         let stackptr = stack_start_aligned(page_size)?;
         let guardaddr = stackptr.addr();
-        // Technically the number of guard pages is tunable and controlled
-        // by the security.bsd.stack_guard_page sysctl.
-        // By default it is 1, checking once is enough since it is
-        // a boot time config value.
-        static PAGES: OnceLock<usize> = OnceLock::new();
-
-        let pages = PAGES.get_or_init(|| {
-            use crate::sys::weak::dlsym;
-            dlsym!(fn sysctlbyname(*const libc::c_char, *mut libc::c_void, *mut libc::size_t, *const libc::c_void, libc::size_t) -> libc::c_int);
-            let mut guard: usize = 0;
-            let mut size = mem::size_of_val(&guard);
-            let oid = c"security.bsd.stack_guard_page";
-            match sysctlbyname.get() {
-                Some(fcn) if unsafe {
-                    fcn(oid.as_ptr(),
-                        ptr::addr_of_mut!(guard).cast(),
-                        ptr::addr_of_mut!(size),
-                        ptr::null_mut(),
-                        0) == 0
-                } => guard,
-                _ => 1,
-            }
-        });
+        // Technically the number of guard pages is tunable, see
+        // `freebsd_guard_pages`.
+        let pages = freebsd_guard_pages();
         Some(guardaddr..guardaddr + pages * page_size)
     }
 
+    /// The number of guard pages FreeBSD puts below the main thread's stack,
+    /// as controlled by the security.bsd.stack_guard_page sysctl. By default
+    /// it is 1. Administrators can change it at runtime, so it is looked up
+    /// again whenever the guard is computed rather than cached.
+    fn freebsd_guard_pages() -> usize {
+        use crate::sys::weak::dlsym;
+        dlsym!(
+            fn sysctlbyname(
+                *const libc::c_char,
+                *mut libc::c_void,
+                *mut libc::size_t,
+                *const libc::c_void,
+                libc::size_t
+            ) -> libc::c_int
+        );
+        let Some(sysctlbyname) = sysctlbyname.get() else { return 1 };
+        let mut pages: usize = 0;
+        let mut size = mem::size_of_val(&pages);
+        let oid = c"security.bsd.stack_guard_page";
+        // SAFETY: `pages` is valid for writes of `size` bytes.
+        let ret = unsafe {
+            sysctlbyname(oid.as_ptr(), ptr::addr_of_mut!(pages).cast(), &mut size, ptr::null(), 0)
+        };
+        if ret == 0 { pages } else { 1 }
+    }
+
     #[forbid(unsafe_op_in_unsafe_fn)]
     unsafe fn install_main_guard_bsds(page_size: usize) -> Option<Range<usize>> {
         // OpenBSD stack already includes a guard page, and stack is
         // immutable.
         // NetBSD stack includes the guard page.
+        // DragonFly's stack autogrows like FreeBSD's, but the kernel keeps
+        // its own guard below the growth area, so remapping the bottom of
+        // the stack ourselves would only move it.
         //
         // We'll just note where we expect rlimit to start
         // faulting, so our handler can report "stack overflow", and
@@ -452,6 +1613,37 @@ unsafe fn install_main_guard_bsds(page_size: usize) -> Option<Range<usize>> {
         Some(stackaddr - page_size..stackaddr)
     }
 
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe fn install_main_guard_aix(page_size: usize) -> Option<Range<usize>> {
+        // AIX grows the main thread's stack on demand up to RLIMIT_STACK and,
+        // like glibc, reports the initial thread's stack as extending all the
+        // way down to that limit. The pages there aren't reserved yet, so we
+        // can't map our own guard; just note where the kernel will refuse to
+        // grow the stack any further. With an unlimited rlimit the reported
+        // bounds are meaningless, so don't guess.
+        let mut limit: libc::rlimit = unsafe { mem::zeroed() };
+        if unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut limit) } != 0
+            || limit.rlim_cur == libc::RLIM_INFINITY
+        {
+            return None;
+        }
+        let stackptr = stack_start_aligned(page_size)?;
+        let stackaddr = stackptr.addr();
+        Some(stackaddr - page_size..stackaddr)
+    }
+
+    #[forbid(unsafe_op_in_unsafe_fn)]
+    unsafe fn install_main_guard_haiku(page_size: usize) -> Option<Range<usize>> {
+        // Haiku's kernel reserves a guard area of `USER_STACK_GUARD_SIZE`
+        // (four pages) below every userland stack, the main thread's
+        // included. Mapping our own guard on top of it would only shrink the
+        // usable stack, so just note where the kernel's guard lies.
+        const USER_STACK_GUARD_PAGES: usize = 4;
+        let stackptr = stack_start_aligned(page_size)?;
+        let stackaddr = stackptr.addr();
+        Some(stackaddr - USER_STACK_GUARD_PAGES * page_size..stackaddr)
+    }
+
     #[forbid(unsafe_op_in_unsafe_fn)]
     unsafe fn install_main_guard_default(page_size: usize) -> Option<Range<usize>> {
         // Reallocate the last page of the stack.
@@ -487,22 +1679,84 @@ unsafe fn install_main_guard_default(page_size: usize) -> Option<Range<usize>> {
         Some(guardaddr..guardaddr + page_size)
     }
 
-    #[cfg(any(
-        target_os = "macos",
-        target_os = "openbsd",
-        target_os = "solaris",
-        target_os = "illumos",
-    ))]
+    /// The guard range of the current thread, which isn't the main one.
+    unsafe fn thread_guard() -> Option<Range<usize>> {
+        // Threads spawned with `Builder::no_guard` have none, don't let the
+        // platform specific code guess one (e.g. musl's fallback below).
+        if super::GUARD_SIZE.get() == Some(0) {
+            return None;
+        }
+        // Nor those spawned on a stack region, their guard is known exactly.
+        if let Some((start, end)) = super::REGION_GUARD.get() {
+            return Some(start..end);
+        }
+        unsafe { current_guard() }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "openbsd", target_os = "solaris"))]
     // FIXME: I am probably not unsafe.
     unsafe fn current_guard() -> Option<Range<usize>> {
+        // There's no way to ask these for the guard size of a running thread,
+        // so use the one the thread was spawned with. The default is a page.
+        let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+        let guardsize = super::GUARD_SIZE.get().map_or(page_size, |size| {
+            // The implementation rounds it up to whole pages.
+            size.next_multiple_of(page_size)
+        });
+        if guardsize == 0 {
+            return None;
+        }
         let stackptr = get_stack_start()?;
         let stackaddr = stackptr.addr();
-        Some(stackaddr - PAGE_SIZE.load(Ordering::Relaxed)..stackaddr)
+        Some(stackaddr - guardsize..stackaddr)
+    }
+
+    #[cfg(target_os = "illumos")]
+    // FIXME: I am probably not unsafe.
+    unsafe fn current_guard() -> Option<Range<usize>> {
+        // Unlike Solaris, illumos has no `pthread_getattr_np`, but its
+        // `pthread_attr_get_np` reports the attributes of a running thread,
+        // including the guard size it was created with. The libc crate does
+        // not bind `pthread_attr_getguardsize` for illumos, so declare it here.
+        extern "C" {
+            fn pthread_attr_getguardsize(
+                attr: *const libc::pthread_attr_t,
+                guardsize: *mut libc::size_t,
+            ) -> libc::c_int;
+        }
+
+        let mut ret = None;
+        let mut attr: libc::pthread_attr_t = crate::mem::zeroed();
+        assert_eq!(libc::pthread_attr_init(&mut attr), 0);
+        if libc::pthread_attr_get_np(libc::pthread_self(), &mut attr) == 0 {
+            let mut guardsize = 0;
+            assert_eq!(pthread_attr_getguardsize(&attr, &mut guardsize), 0);
+            if guardsize == 0 {
+                // Threads created without a guard still fault right below the
+                // stack once the redzone is exhausted, so keep the old
+                // single-page assumption rather than disabling detection.
+                guardsize = PAGE_SIZE.load(Ordering::Relaxed);
+            }
+            let mut stackptr = crate::ptr::null_mut::<libc::c_void>();
+            let mut size = 0;
+            assert_eq!(libc::pthread_attr_getstack(&attr, &mut stackptr, &mut size), 0);
+
+            let stackaddr = stackptr.addr();
+            ret = Some(stackaddr - guardsize..stackaddr);
+        } else if let Some(stackptr) = get_stack_start() {
+            let stackaddr = stackptr.addr();
+            ret = Some(stackaddr - PAGE_SIZE.load(Ordering::Relaxed)..stackaddr);
+        }
+        assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+        ret
     }
 
     #[cfg(any(
+        target_os = "aix",
         target_os = "android",
         target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "haiku",
         target_os = "hurd",
         target_os = "linux",
         target_os = "netbsd",
@@ -512,11 +1766,11 @@ unsafe fn current_guard() -> Option<Range<usize>> {
     unsafe fn current_guard() -> Option<Range<usize>> {
         let mut ret = None;
         let mut attr: libc::pthread_attr_t = crate::mem::zeroed();
-        #[cfg(target_os = "freebsd")]
+        #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
         assert_eq!(libc::pthread_attr_init(&mut attr), 0);
-        #[cfg(target_os = "freebsd")]
+        #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
         let e = libc::pthread_attr_get_np(libc::pthread_self(), &mut attr);
-        #[cfg(not(target_os = "freebsd"))]
+        #[cfg(not(any(target_os = "freebsd", target_os = "dragonfly")))]
         let e = libc::pthread_getattr_np(libc::pthread_self(), &mut attr);
         if e == 0 {
             let mut guardsize = 0;
@@ -528,7 +1782,10 @@ unsafe fn current_guard() -> Option<Range<usize>> {
                     // Use page size as a fallback.
                     guardsize = PAGE_SIZE.load(Ordering::Relaxed);
                 } else {
-                    panic!("there is no guard page");
+                    // Threads created by foreign code may have been spawned
+                    // without a guard page, there is nothing to watch then.
+                    assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+                    return None;
                 }
             }
             let mut stackptr = crate::ptr::null_mut::<libc::c_void>();
@@ -536,7 +1793,14 @@ unsafe fn current_guard() -> Option<Range<usize>> {
             assert_eq!(libc::pthread_attr_getstack(&attr, &mut stackptr, &mut size), 0);
 
             let stackaddr = stackptr.addr();
-            ret = if cfg!(any(target_os = "freebsd", target_os = "netbsd", target_os = "hurd")) {
+            ret = if cfg!(any(
+                target_os = "aix",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "haiku",
+                target_os = "netbsd",
+                target_os = "hurd"
+            )) {
                 Some(stackaddr - guardsize..stackaddr)
             } else if cfg!(all(target_os = "linux", target_env = "musl")) {
                 Some(stackaddr - guardsize..stackaddr)
@@ -553,9 +1817,19 @@ unsafe fn current_guard() -> Option<Range<usize>> {
                 Some(stackaddr..stackaddr + guardsize)
             };
         }
-        if e == 0 || cfg!(target_os = "freebsd") {
+        if e == 0 || cfg!(any(target_os = "freebsd", target_os = "dragonfly")) {
             assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
         }
+        // Without the attributes, assume the usual single page right below
+        // the stack mapping.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if e != 0 {
+            let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+            ret = stack_bounds_from_maps().map(|(stackptr, _)| {
+                let stackaddr = stackptr.addr();
+                stackaddr - page_size..stackaddr
+            });
+        }
         ret
     }
 }
@@ -568,10 +1842,25 @@ unsafe fn current_guard() -> Option<Range<usize>> {
 // usually have fewer qualms about forwards compatibility, since the runtime
 // is shipped with the OS):
 // <https://github.com/apple/swift/blob/swift-5.10-RELEASE/stdlib/public/runtime/CrashHandlerMacOS.cpp>
+//
+// Applications that can live with Mach exception ports instead can opt into
+// the `apple_mach_stack_overflow` feature of std, see `mach.rs`.
+//
+// Fuchsia and ESP-IDF don't use signals for faults at all, see `fuchsia.rs`
+// and `espidf.rs` instead.
 #[cfg(not(any(
+    all(
+        feature = "apple_mach_stack_overflow",
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos", target_os = "visionos"),
+    ),
+    target_os = "espidf",
+    target_os = "fuchsia",
+    target_os = "aix",
     target_os = "linux",
     target_os = "freebsd",
+    target_os = "dragonfly",
     target_os = "hurd",
+    target_os = "haiku",
     target_os = "macos",
     target_os = "netbsd",
     target_os = "openbsd",
@@ -587,5 +1876,34 @@ pub unsafe fn make_handler(_main_thread: bool) -> super::Handler {
         super::Handler::null()
     }
 
-    pub unsafe fn drop_handler(_data: *mut libc::c_void) {}
+    pub unsafe fn drop_handler(_data: *mut libc::c_void, _size: usize) {}
+
+    pub unsafe fn refresh_guard() {}
+
+    pub fn install_crash_reporter() {}
+
+    pub fn is_stack_overflow(_addr: usize) -> bool {
+        false
+    }
+
+    pub fn status() -> super::Status {
+        super::Status::default()
+    }
+
+    pub fn trim_altstack() {}
+
+    pub fn remaining_stack(_sp: usize) -> Option<usize> {
+        None
+    }
+
+    pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+        Err(crate::io::const_io_error!(
+            crate::io::ErrorKind::Unsupported,
+            "switching stacks is not supported on this platform",
+        ))
+    }
+
+    pub fn registry_info() -> Option<(u64, crate::ops::Range<usize>, crate::ops::Range<usize>)> {
+        None
+    }
 }
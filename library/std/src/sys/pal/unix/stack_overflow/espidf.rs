@@ -0,0 +1,96 @@
+//! Stack overflow reporting for ESP-IDF.
+//!
+//! FreeRTOS tasks have no guard pages and there is no `SIGSEGV` to catch.
+//! Instead, when the IDF is built with `CONFIG_FREERTOS_CHECK_STACKOVERFLOW`,
+//! the scheduler checks the stack of every task it switches out (by watermark
+//! or canary) and calls `vApplicationStackOverflowHook` if it was exceeded.
+//! The IDF ships a weak default of that hook which prints its own message.
+//! With the `espidf_stack_overflow_hook` feature of std, we override it so
+//! the report reads the same as on the other platforms, and then abort
+//! through the IDF's panic handler as the default does. The feature is off by
+//! default, as applications that define the hook themselves would get a
+//! duplicate symbol error, since the IDF's definition is the only weak one.
+
+#[cfg(feature = "espidf_stack_overflow_hook")]
+use libc::c_char;
+use libc::c_void;
+
+use super::Handler;
+#[cfg(feature = "espidf_stack_overflow_hook")]
+use crate::ffi::CStr;
+
+pub unsafe fn init() {}
+
+pub unsafe fn cleanup() {}
+
+pub unsafe fn make_handler(_main_thread: bool) -> Handler {
+    Handler::null()
+}
+
+pub unsafe fn drop_handler(_data: *mut c_void, _size: usize) {}
+
+pub unsafe fn refresh_guard() {}
+
+pub fn install_crash_reporter() {}
+
+/// Overflows are caught by the scheduler's checks, not by faulting.
+pub fn is_stack_overflow(_addr: usize) -> bool {
+    false
+}
+
+pub fn status() -> super::Status {
+    super::Status::default()
+}
+
+pub fn trim_altstack() {}
+
+pub fn remaining_stack(_sp: usize) -> Option<usize> {
+    None
+}
+
+pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+    Err(crate::io::const_io_error!(
+        crate::io::ErrorKind::Unsupported,
+        "switching stacks is not supported on this platform",
+    ))
+}
+
+pub fn registry_info() -> Option<(u64, crate::ops::Range<usize>, crate::ops::Range<usize>)> {
+    None
+}
+
+#[cfg(feature = "espidf_stack_overflow_hook")]
+extern "C" {
+    fn esp_system_abort(details: *const c_char) -> !;
+}
+
+// The default `CONFIG_FREERTOS_MAX_TASK_NAME_LEN`, names are truncated to it.
+#[cfg(feature = "espidf_stack_overflow_hook")]
+const MAX_TASK_NAME_LEN: usize = 16;
+
+#[cfg(feature = "espidf_stack_overflow_hook")]
+#[no_mangle]
+unsafe extern "C" fn vApplicationStackOverflowHook(_task: *mut c_void, name: *const c_char) {
+    // This is called from the scheduler with the offending task's stack
+    // already trashed, so we can neither allocate nor go through stderr.
+    // Assemble the message in a fixed buffer and let the IDF print it.
+    const PREFIX: &[u8] = b"thread '";
+    const SUFFIX: &[u8] = b"' has overflowed its stack\0";
+
+    let name = if name.is_null() {
+        b"<unknown>".as_slice()
+    } else {
+        // SAFETY: FreeRTOS passes the nul-terminated name of the task.
+        unsafe { CStr::from_ptr(name) }.to_bytes()
+    };
+    let name = &name[..name.len().min(MAX_TASK_NAME_LEN)];
+
+    let mut buf = [0u8; PREFIX.len() + MAX_TASK_NAME_LEN + SUFFIX.len()];
+    let mut len = 0;
+    for part in [PREFIX, name, SUFFIX] {
+        buf[len..len + part.len()].copy_from_slice(part);
+        len += part.len();
+    }
+    // SAFETY: `buf` holds a nul-terminated message.
+    unsafe { esp_system_abort(buf.as_ptr().cast()) }
+}
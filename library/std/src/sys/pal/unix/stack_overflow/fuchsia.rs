@@ -0,0 +1,390 @@
+//! Stack overflow reporting for Fuchsia.
+//!
+//! Zircon doesn't deliver faults as POSIX signals. Instead, a fault suspends
+//! the faulting thread and sends an exception message over the exception
+//! channels bound to the thread, its process and its jobs, in that order.
+//! We bind the process-level channel and service it from a dedicated thread:
+//! page faults that land in the guard region of a thread we know about are
+//! reported as stack overflows, and everything else is passed on to the next
+//! handler (usually the system crash service) untouched.
+//!
+//! Only one process-level exception channel can exist at a time. If one is
+//! already bound (e.g. a debugger is attached), we stay out of the way.
+
+#![allow(non_camel_case_types, dead_code)]
+
+use libc::{c_void, size_t};
+
+use super::Handler;
+use crate::ops::Range;
+use crate::sync::atomic::{AtomicBool, Ordering};
+use crate::sync::Mutex;
+use crate::sys::pal::unix::thread::{Thread, DEFAULT_MIN_STACK_SIZE};
+use crate::sys::stack_overflow::report;
+use crate::{mem, ptr, str};
+
+type zx_handle_t = u32;
+type zx_status_t = i32;
+type zx_koid_t = u64;
+type zx_signals_t = u32;
+type zx_time_t = i64;
+
+const ZX_OK: zx_status_t = 0;
+const ZX_HANDLE_INVALID: zx_handle_t = 0;
+const ZX_TIME_INFINITE: zx_time_t = i64::MAX;
+const ZX_CHANNEL_READABLE: zx_signals_t = 1 << 0;
+const ZX_CHANNEL_PEER_CLOSED: zx_signals_t = 1 << 2;
+const ZX_INFO_HANDLE_BASIC: u32 = 2;
+const ZX_INFO_THREAD_EXCEPTION_REPORT: u32 = 11;
+const ZX_PROP_NAME: u32 = 3;
+const ZX_MAX_NAME_LEN: usize = 32;
+const ZX_EXCP_FATAL_PAGE_FAULT: u32 = 0x108;
+
+#[repr(C)]
+struct zx_exception_info_t {
+    pid: zx_koid_t,
+    tid: zx_koid_t,
+    type_: u32,
+    padding1: [u8; 4],
+}
+
+#[repr(C)]
+struct zx_info_handle_basic_t {
+    koid: zx_koid_t,
+    rights: u32,
+    type_: u32,
+    related_koid: zx_koid_t,
+    reserved: u32,
+    padding1: [u8; 4],
+}
+
+#[repr(C)]
+struct zx_exception_report_t {
+    size: u32,
+    type_: u32,
+    // The architecture-specific part of the context is a union whose largest
+    // member (x86-64's `vector`, `err_code`, `cr2`) is three words long.
+    arch: [u64; 3],
+    synth_code: u32,
+    synth_data: u32,
+}
+
+impl zx_exception_report_t {
+    fn fault_address(&self) -> usize {
+        // x86-64 reports the address in `cr2`, the third word; arm64 (`far`)
+        // and riscv64 (`tval`) both report it in the second.
+        (if cfg!(target_arch = "x86_64") { self.arch[2] } else { self.arch[1] }) as usize
+    }
+}
+
+extern "C" {
+    fn zx_process_self() -> zx_handle_t;
+    fn zx_thread_self() -> zx_handle_t;
+    fn zx_handle_close(handle: zx_handle_t) -> zx_status_t;
+    fn zx_task_create_exception_channel(
+        handle: zx_handle_t,
+        options: u32,
+        out: *mut zx_handle_t,
+    ) -> zx_status_t;
+    fn zx_object_wait_one(
+        handle: zx_handle_t,
+        signals: zx_signals_t,
+        deadline: zx_time_t,
+        observed: *mut zx_signals_t,
+    ) -> zx_status_t;
+    fn zx_channel_read(
+        handle: zx_handle_t,
+        options: u32,
+        bytes: *mut c_void,
+        handles: *mut zx_handle_t,
+        num_bytes: u32,
+        num_handles: u32,
+        actual_bytes: *mut u32,
+        actual_handles: *mut u32,
+    ) -> zx_status_t;
+    fn zx_exception_get_thread(handle: zx_handle_t, out: *mut zx_handle_t) -> zx_status_t;
+    fn zx_object_get_info(
+        handle: zx_handle_t,
+        topic: u32,
+        buffer: *mut c_void,
+        buffer_size: size_t,
+        actual: *mut size_t,
+        avail: *mut size_t,
+    ) -> zx_status_t;
+    fn zx_object_get_property(
+        handle: zx_handle_t,
+        property: u32,
+        value: *mut c_void,
+        value_size: size_t,
+    ) -> zx_status_t;
+}
+
+/// Guard ranges of the threads std knows about, keyed by thread koid.
+///
+/// Unlike the signal-based implementation, the fault is classified on another
+/// thread, so the faulting thread's TLS is out of reach.
+static GUARDS: Mutex<Vec<(zx_koid_t, Range<usize>)>> = Mutex::new(Vec::new());
+static NEED_ALTSTACK: AtomicBool = AtomicBool::new(false);
+
+/// # Safety
+/// Must be called only once
+#[forbid(unsafe_op_in_unsafe_fn)]
+pub unsafe fn init() {
+    let mut channel = ZX_HANDLE_INVALID;
+    // SAFETY: `zx_process_self` returns a handle that stays valid for the
+    // lifetime of the process.
+    if unsafe { zx_task_create_exception_channel(zx_process_self(), 0, &mut channel) } != ZX_OK {
+        return;
+    }
+    NEED_ALTSTACK.store(true, Ordering::Release);
+    register_current_thread();
+
+    // SAFETY: the closure only owns the channel handle.
+    let thread =
+        unsafe { Thread::new(DEFAULT_MIN_STACK_SIZE, None, Box::new(move || serve(channel))) };
+    match thread {
+        // Dropping the thread detaches it.
+        Ok(thread) => drop(thread),
+        Err(_) => {
+            NEED_ALTSTACK.store(false, Ordering::Release);
+            // SAFETY: nobody else has seen the channel.
+            unsafe { zx_handle_close(channel) };
+        }
+    }
+}
+
+/// # Safety
+/// Must be called only once
+pub unsafe fn cleanup() {}
+
+/// # Safety
+/// Must be called on the thread the returned handler will be dropped on.
+pub unsafe fn make_handler(_main_thread: bool) -> Handler {
+    if !NEED_ALTSTACK.load(Ordering::Acquire) || !register_current_thread() {
+        return Handler::null();
+    }
+    // There is no altstack to keep track of, the handler only needs to
+    // remember to unregister the thread again.
+    Handler { data: ptr::dangling_mut(), size: 0 }
+}
+
+/// # Safety
+/// Must be called on the thread that created the handler.
+pub unsafe fn drop_handler(data: *mut c_void, _size: usize) {
+    if !data.is_null() {
+        if let Some(koid) = current_koid() {
+            GUARDS.lock().unwrap_or_else(|e| e.into_inner()).retain(|&(k, _)| k != koid);
+        }
+    }
+}
+
+/// # Safety
+/// Must be called on a thread that isn't faulting.
+pub unsafe fn refresh_guard() {
+    let (Some(koid), Some(guard)) = (current_koid(), current_guard()) else {
+        return;
+    };
+    for (k, g) in GUARDS.lock().unwrap_or_else(|e| e.into_inner()).iter_mut() {
+        if *k == koid {
+            *g = guard.clone();
+        }
+    }
+}
+
+pub fn install_crash_reporter() {}
+
+pub fn is_stack_overflow(addr: usize) -> bool {
+    let Some(koid) = current_koid() else {
+        return false;
+    };
+    let guards = GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+    guards.iter().any(|(k, guard)| *k == koid && guard.contains(&addr))
+}
+
+/// There is no altstack on Fuchsia, the exception channel takes its place.
+pub fn status() -> super::Status {
+    let guard = current_koid().and_then(|koid| {
+        let guards = GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+        guards.iter().find(|&&(k, _)| k == koid).map(|(_, guard)| guard.clone())
+    });
+    super::Status {
+        handlers_installed: NEED_ALTSTACK.load(Ordering::Relaxed),
+        altstack_active: false,
+        guard,
+        fault_signals_unblocked: false,
+    }
+}
+
+pub fn trim_altstack() {}
+
+pub fn remaining_stack(sp: usize) -> Option<usize> {
+    let koid = current_koid()?;
+    let guards = GUARDS.lock().unwrap_or_else(|e| e.into_inner());
+    let (_, guard) = guards.iter().find(|&&(k, _)| k == koid)?;
+    Some(sp.saturating_sub(guard.end))
+}
+
+pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+    Err(crate::io::const_io_error!(
+        crate::io::ErrorKind::Unsupported,
+        "switching stacks is not supported on this platform",
+    ))
+}
+
+/// Crash handlers on Fuchsia get the faulting thread's details from the
+/// exception report instead.
+pub fn registry_info() -> Option<(u64, Range<usize>, Range<usize>)> {
+    None
+}
+
+fn register_current_thread() -> bool {
+    let (Some(koid), Some(guard)) = (current_koid(), current_guard()) else {
+        return false;
+    };
+    GUARDS.lock().unwrap_or_else(|e| e.into_inner()).push((koid, guard));
+    true
+}
+
+fn current_koid() -> Option<zx_koid_t> {
+    // SAFETY: `zx_thread_self` returns a handle that stays valid for the
+    // lifetime of the thread.
+    unsafe { koid(zx_thread_self()) }
+}
+
+unsafe fn koid(handle: zx_handle_t) -> Option<zx_koid_t> {
+    let mut info: zx_info_handle_basic_t = unsafe { mem::zeroed() };
+    let status = unsafe {
+        zx_object_get_info(
+            handle,
+            ZX_INFO_HANDLE_BASIC,
+            ptr::addr_of_mut!(info).cast(),
+            mem::size_of_val(&info),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    (status == ZX_OK).then_some(info.koid)
+}
+
+fn current_guard() -> Option<Range<usize>> {
+    if let Some((start, end)) = super::REGION_GUARD.get() {
+        return Some(start..end);
+    }
+    // Fuchsia's libc places a guard region of the thread's guard size right
+    // below the stack of every thread it creates, the initial one included.
+    unsafe {
+        let mut attr: libc::pthread_attr_t = mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut guardsize = 0;
+        let mut stackptr = ptr::null_mut::<c_void>();
+        let mut size = 0;
+        let ok = libc::pthread_attr_getguardsize(&attr, &mut guardsize) == 0
+            && libc::pthread_attr_getstack(&attr, &mut stackptr, &mut size) == 0;
+        assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+        if !ok || guardsize == 0 {
+            return None;
+        }
+        let stackaddr = stackptr.addr();
+        Some(stackaddr - guardsize..stackaddr)
+    }
+}
+
+/// Services the process exception channel until it is closed.
+fn serve(channel: zx_handle_t) {
+    loop {
+        let mut observed = 0;
+        // SAFETY: `channel` is owned by this thread.
+        let status = unsafe {
+            zx_object_wait_one(
+                channel,
+                ZX_CHANNEL_READABLE | ZX_CHANNEL_PEER_CLOSED,
+                ZX_TIME_INFINITE,
+                &mut observed,
+            )
+        };
+        if status != ZX_OK || observed & ZX_CHANNEL_READABLE == 0 {
+            break;
+        }
+
+        let mut info: zx_exception_info_t = unsafe { mem::zeroed() };
+        let mut exception = ZX_HANDLE_INVALID;
+        let (mut actual_bytes, mut actual_handles) = (0, 0);
+        // SAFETY: the buffers are large enough for one exception message.
+        let status = unsafe {
+            zx_channel_read(
+                channel,
+                0,
+                ptr::addr_of_mut!(info).cast(),
+                &mut exception,
+                mem::size_of_val(&info) as u32,
+                1,
+                &mut actual_bytes,
+                &mut actual_handles,
+            )
+        };
+        if status != ZX_OK {
+            continue;
+        }
+
+        if info.type_ == ZX_EXCP_FATAL_PAGE_FAULT {
+            // SAFETY: `exception` was just received from the channel.
+            unsafe { report_if_overflow(exception, info.tid) };
+        }
+
+        // Closing the exception without resolving it hands it on to the next
+        // handler in line, which terminates the process as usual.
+        // SAFETY: `exception` is owned by us.
+        unsafe { zx_handle_close(exception) };
+    }
+    // SAFETY: `channel` is owned by this thread.
+    unsafe { zx_handle_close(channel) };
+}
+
+unsafe fn report_if_overflow(exception: zx_handle_t, tid: zx_koid_t) {
+    let Some(guard) = GUARDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|&&(koid, _)| koid == tid)
+        .map(|(_, guard)| guard.clone())
+    else {
+        return;
+    };
+
+    let mut thread = ZX_HANDLE_INVALID;
+    if unsafe { zx_exception_get_thread(exception, &mut thread) } != ZX_OK {
+        return;
+    }
+
+    let mut report: zx_exception_report_t = unsafe { mem::zeroed() };
+    let status = unsafe {
+        zx_object_get_info(
+            thread,
+            ZX_INFO_THREAD_EXCEPTION_REPORT,
+            ptr::addr_of_mut!(report).cast(),
+            mem::size_of_val(&report),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    if status == ZX_OK && guard.contains(&report.fault_address()) {
+        // `Thread::set_name` stores the name in the thread object, so we can
+        // read it from here without touching the faulting thread's state.
+        let mut name = [0u8; ZX_MAX_NAME_LEN];
+        let name = match unsafe {
+            zx_object_get_property(thread, ZX_PROP_NAME, name.as_mut_ptr().cast(), name.len())
+        } {
+            ZX_OK => {
+                let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                str::from_utf8(&name[..len]).ok().filter(|name| !name.is_empty())
+            }
+            _ => None,
+        };
+        // A backtrace from here would be of this thread, not the faulting
+        // one, so `ReportMode::Backtrace` only gets the message.
+        report::report_and_abort(&report::Overflow { name, id: Some(tid), ..Default::default() });
+    }
+    unsafe { zx_handle_close(thread) };
+}
@@ -0,0 +1,309 @@
+//! Stack overflow reporting for iOS, tvOS, watchOS and visionOS.
+//!
+//! The signal-based handler is disabled on these targets because of the
+//! symbols it uses (see the comment on the fallback `imp` in the parent
+//! module). As an opt-in alternative for applications that are fine with it,
+//! the `apple_mach_stack_overflow` feature of std installs a task-level Mach
+//! exception port for `EXC_BAD_ACCESS` instead, serviced by a dedicated
+//! thread. The kernel raises Mach exceptions before translating them into
+//! signals, so faults that turn out not to be stack overflows are answered
+//! with `KERN_FAILURE`, which makes the kernel carry on delivering them as
+//! the usual `SIGSEGV`/`SIGBUS`.
+//!
+//! Since the exception message names the faulting thread, its guard range can
+//! be computed on the spot from its pthread, no per-thread state is needed.
+
+#![allow(non_camel_case_types, dead_code)]
+
+use libc::{c_char, c_int, c_void, mach_port_t};
+
+use super::Handler;
+use crate::sys::pal::unix::os;
+use crate::sys::pal::unix::thread::{Thread, DEFAULT_MIN_STACK_SIZE};
+use crate::sys::stack_overflow::report;
+use crate::{mem, ptr, str};
+
+type kern_return_t = c_int;
+type exception_mask_t = u32;
+type exception_behavior_t = c_int;
+type thread_state_flavor_t = c_int;
+type mach_msg_option_t = c_int;
+type mach_msg_return_t = kern_return_t;
+
+const KERN_SUCCESS: kern_return_t = 0;
+const KERN_FAILURE: kern_return_t = 5;
+const MACH_PORT_NULL: mach_port_t = 0;
+const MACH_PORT_RIGHT_RECEIVE: u32 = 1;
+const MACH_MSG_TYPE_MAKE_SEND: u32 = 20;
+const MACH_MSGH_BITS_REMOTE_MASK: u32 = 0x1f;
+const MACH_SEND_MSG: mach_msg_option_t = 0x1;
+const MACH_RCV_MSG: mach_msg_option_t = 0x2;
+const MACH_MSG_TIMEOUT_NONE: u32 = 0;
+const EXC_BAD_ACCESS: c_int = 1;
+const EXC_MASK_BAD_ACCESS: exception_mask_t = 1 << EXC_BAD_ACCESS;
+const EXCEPTION_DEFAULT: exception_behavior_t = 1;
+const MACH_EXCEPTION_CODES: exception_behavior_t = 0x80000000_u32 as c_int;
+#[cfg(target_arch = "aarch64")]
+const THREAD_STATE_NONE: thread_state_flavor_t = 5;
+#[cfg(not(target_arch = "aarch64"))]
+const THREAD_STATE_NONE: thread_state_flavor_t = 13;
+/// The MIG id of `mach_exception_raise`, replies use the id plus 100.
+const MACH_EXCEPTION_RAISE_ID: i32 = 2405;
+
+#[repr(C)]
+struct mach_msg_header_t {
+    msgh_bits: u32,
+    msgh_size: u32,
+    msgh_remote_port: mach_port_t,
+    msgh_local_port: mach_port_t,
+    msgh_voucher_port: mach_port_t,
+    msgh_id: i32,
+}
+
+#[repr(C)]
+struct mach_msg_port_descriptor_t {
+    name: mach_port_t,
+    pad1: u32,
+    pad2: u16,
+    disposition: u8,
+    type_: u8,
+}
+
+#[repr(C)]
+struct NDR_record_t {
+    bytes: [u8; 8],
+}
+
+/// `__Request__mach_exception_raise_t`, plus room for the trailer.
+#[repr(C, packed(4))]
+struct exception_request_t {
+    head: mach_msg_header_t,
+    descriptor_count: u32,
+    thread: mach_msg_port_descriptor_t,
+    task: mach_msg_port_descriptor_t,
+    ndr: NDR_record_t,
+    exception: c_int,
+    code_count: u32,
+    code: [i64; 2],
+    trailer: [u8; 128],
+}
+
+/// `__Reply__mach_exception_raise_t`.
+#[repr(C, packed(4))]
+struct exception_reply_t {
+    head: mach_msg_header_t,
+    ndr: NDR_record_t,
+    ret_code: kern_return_t,
+}
+
+extern "C" {
+    static NDR_record: NDR_record_t;
+
+    fn mach_port_allocate(task: mach_port_t, right: u32, name: *mut mach_port_t) -> kern_return_t;
+    fn mach_port_insert_right(
+        task: mach_port_t,
+        name: mach_port_t,
+        poly: mach_port_t,
+        poly_poly: u32,
+    ) -> kern_return_t;
+    fn mach_port_deallocate(task: mach_port_t, name: mach_port_t) -> kern_return_t;
+    fn task_set_exception_ports(
+        task: mach_port_t,
+        exception_mask: exception_mask_t,
+        new_port: mach_port_t,
+        behavior: exception_behavior_t,
+        new_flavor: thread_state_flavor_t,
+    ) -> kern_return_t;
+    fn mach_msg(
+        msg: *mut mach_msg_header_t,
+        option: mach_msg_option_t,
+        send_size: u32,
+        rcv_size: u32,
+        rcv_name: mach_port_t,
+        timeout: u32,
+        notify: mach_port_t,
+    ) -> mach_msg_return_t;
+}
+
+/// # Safety
+/// Must be called only once
+#[forbid(unsafe_op_in_unsafe_fn)]
+pub unsafe fn init() {
+    // SAFETY: plain Mach calls on our own task with valid out pointers.
+    unsafe {
+        let task = libc::mach_task_self();
+        let mut port = MACH_PORT_NULL;
+        if mach_port_allocate(task, MACH_PORT_RIGHT_RECEIVE, &mut port) != KERN_SUCCESS {
+            return;
+        }
+        if mach_port_insert_right(task, port, port, MACH_MSG_TYPE_MAKE_SEND) != KERN_SUCCESS
+            || task_set_exception_ports(
+                task,
+                EXC_MASK_BAD_ACCESS,
+                port,
+                EXCEPTION_DEFAULT | MACH_EXCEPTION_CODES,
+                THREAD_STATE_NONE,
+            ) != KERN_SUCCESS
+        {
+            mach_port_deallocate(task, port);
+            return;
+        }
+
+        match Thread::new(DEFAULT_MIN_STACK_SIZE, None, Box::new(move || serve(port))) {
+            // Dropping the thread detaches it.
+            Ok(thread) => drop(thread),
+            // Nobody would answer the exception messages, so put the port
+            // back the way it was before any fault can get stuck on it.
+            Err(_) => {
+                task_set_exception_ports(
+                    task,
+                    EXC_MASK_BAD_ACCESS,
+                    MACH_PORT_NULL,
+                    EXCEPTION_DEFAULT,
+                    THREAD_STATE_NONE,
+                );
+                mach_port_deallocate(task, port);
+            }
+        }
+    }
+}
+
+/// # Safety
+/// Must be called only once
+pub unsafe fn cleanup() {}
+
+pub unsafe fn make_handler(_main_thread: bool) -> Handler {
+    Handler::null()
+}
+
+pub unsafe fn drop_handler(_data: *mut c_void, _size: usize) {}
+
+pub unsafe fn refresh_guard() {}
+
+pub fn install_crash_reporter() {}
+
+pub fn is_stack_overflow(addr: usize) -> bool {
+    // SAFETY: the current thread is a valid pthread.
+    unsafe { guard_range(libc::pthread_self()) }.contains(&addr)
+}
+
+pub fn status() -> super::Status {
+    super::Status::default()
+}
+
+pub fn trim_altstack() {}
+
+pub fn remaining_stack(_sp: usize) -> Option<usize> {
+    None
+}
+
+pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+    Err(crate::io::const_io_error!(
+        crate::io::ErrorKind::Unsupported,
+        "switching stacks is not supported on this platform",
+    ))
+}
+
+pub fn registry_info() -> Option<(u64, crate::ops::Range<usize>, crate::ops::Range<usize>)> {
+    None
+}
+
+/// Answers exception messages on `port` for the rest of the process' life.
+fn serve(port: mach_port_t) {
+    loop {
+        // SAFETY: all-zero is a valid bit pattern for the message buffers.
+        let mut request: exception_request_t = unsafe { mem::zeroed() };
+        // SAFETY: the buffer is as large as we claim.
+        let ret = unsafe {
+            mach_msg(
+                ptr::addr_of_mut!(request.head),
+                MACH_RCV_MSG,
+                0,
+                mem::size_of::<exception_request_t>() as u32,
+                port,
+                MACH_MSG_TIMEOUT_NONE,
+                MACH_PORT_NULL,
+            )
+        };
+        if ret != KERN_SUCCESS || request.head.msgh_id != MACH_EXCEPTION_RAISE_ID {
+            continue;
+        }
+
+        let thread = request.thread.name;
+        let (exception, code_count, addr) =
+            (request.exception, request.code_count, request.code[1]);
+        if exception == EXC_BAD_ACCESS && code_count >= 2 {
+            // SAFETY: `thread` is a send right to a thread of this task.
+            unsafe { report_if_overflow(thread, addr as usize) };
+        }
+
+        // SAFETY: the message carried send rights we are done with.
+        unsafe {
+            let task = libc::mach_task_self();
+            mach_port_deallocate(task, thread);
+            mach_port_deallocate(task, request.task.name);
+        }
+
+        let mut reply = exception_reply_t {
+            head: mach_msg_header_t {
+                msgh_bits: request.head.msgh_bits & MACH_MSGH_BITS_REMOTE_MASK,
+                msgh_size: mem::size_of::<exception_reply_t>() as u32,
+                msgh_remote_port: request.head.msgh_remote_port,
+                msgh_local_port: MACH_PORT_NULL,
+                msgh_voucher_port: MACH_PORT_NULL,
+                msgh_id: request.head.msgh_id + 100,
+            },
+            // SAFETY: `NDR_record` is an immutable global of the system library.
+            ndr: NDR_record_t { bytes: unsafe { NDR_record.bytes } },
+            // Not handled: let the kernel carry on with the next handler and
+            // eventually the BSD signal.
+            ret_code: KERN_FAILURE,
+        };
+        // SAFETY: the reply is a well-formed MIG reply to `request`.
+        unsafe {
+            mach_msg(
+                ptr::addr_of_mut!(reply.head),
+                MACH_SEND_MSG,
+                mem::size_of::<exception_reply_t>() as u32,
+                0,
+                MACH_PORT_NULL,
+                MACH_MSG_TIMEOUT_NONE,
+                MACH_PORT_NULL,
+            )
+        };
+    }
+}
+
+/// Same guard computation as the signal-based handler uses on macOS: the
+/// guard page sits right below the lowest address of the stack.
+unsafe fn guard_range(pthread: libc::pthread_t) -> crate::ops::Range<usize> {
+    let page_size = os::page_size();
+    let stackaddr = unsafe { libc::pthread_get_stackaddr_np(pthread) }.addr()
+        - unsafe { libc::pthread_get_stacksize_np(pthread) };
+    stackaddr - page_size..stackaddr
+}
+
+unsafe fn report_if_overflow(thread: mach_port_t, addr: usize) {
+    let pthread = unsafe { libc::pthread_from_mach_thread_np(thread) };
+    if pthread == 0 {
+        return;
+    }
+    if !unsafe { guard_range(pthread) }.contains(&addr) {
+        return;
+    }
+    let mut name = [0 as c_char; 64];
+    let name = match unsafe { libc::pthread_getname_np(pthread, name.as_mut_ptr(), name.len()) } {
+        0 => {
+            // SAFETY: `c_char` and `u8` have the same layout.
+            let name = unsafe { &*(ptr::addr_of!(name) as *const [u8; 64]) };
+            let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            str::from_utf8(&name[..len]).ok().filter(|name| !name.is_empty())
+        }
+        _ => None,
+    };
+    let mut id = 0;
+    let id = (unsafe { libc::pthread_threadid_np(pthread, &mut id) } == 0).then_some(id);
+    // A backtrace from here would be of the exception thread, not the
+    // faulting one, so `ReportMode::Backtrace` only gets the message.
+    report::report_and_abort(&report::Overflow { name, id, ..Default::default() });
+}
@@ -0,0 +1,161 @@
+//! A process-wide registry of the stacks and guard ranges of the threads we
+//! watch for stack overflows, for crash handlers to classify faults with
+//! (see `std::os::unix::thread::StackGuardInfo`).
+//!
+//! Crash handlers run in signal handlers, or on another thread while the
+//! process is in an unknown state, so reading the registry must neither lock
+//! nor allocate. Entries live in a list that only ever grows, the entries of
+//! threads that exited are marked free and reused by later ones.
+
+use crate::cell::Cell;
+use crate::ops::Range;
+use crate::ptr;
+use crate::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+struct Entry {
+    /// The next entry in the list, fixed before the entry is published.
+    next: *const Entry,
+    in_use: AtomicBool,
+    /// The id of the thread this entry describes, zero while it is being
+    /// written to or free.
+    thread_id: AtomicU64,
+    stack_start: AtomicUsize,
+    stack_end: AtomicUsize,
+    guard_start: AtomicUsize,
+    guard_end: AtomicUsize,
+}
+
+static HEAD: AtomicPtr<Entry> = AtomicPtr::new(ptr::null_mut());
+
+thread_local! {
+    static ENTRY: Cell<*const Entry> = const { Cell::new(ptr::null()) };
+}
+
+/// Records the current thread, if its guard range is known.
+pub fn register_current() {
+    let Some((thread_id, stack, guard)) = super::imp::registry_info() else {
+        return;
+    };
+    if !ENTRY.get().is_null() {
+        unregister_current();
+    }
+
+    let entry = claim();
+    entry.stack_start.store(stack.start, Ordering::Relaxed);
+    entry.stack_end.store(stack.end, Ordering::Relaxed);
+    entry.guard_start.store(guard.start, Ordering::Relaxed);
+    entry.guard_end.store(guard.end, Ordering::Relaxed);
+    entry.thread_id.store(thread_id, Ordering::Release);
+    ENTRY.set(entry);
+}
+
+/// Removes the current thread's entry, if it has one.
+pub fn unregister_current() {
+    // This runs as the thread's very last TLS destructor, and where TLS is
+    // emulated with keys `ENTRY` may be gone already. Find the entry by the
+    // thread's id then, lest it stay in use with an id that can be reused.
+    let entry = ENTRY.try_with(|cell| cell.replace(ptr::null())).unwrap_or_else(|_| {
+        let Some((thread_id, ..)) = super::imp::registry_info() else {
+            return ptr::null();
+        };
+        let mut current = HEAD.load(Ordering::Acquire).cast_const();
+        // SAFETY: entries are never deallocated.
+        while let Some(entry) = unsafe { current.as_ref() } {
+            if entry.thread_id.load(Ordering::Relaxed) == thread_id {
+                return entry;
+            }
+            current = entry.next;
+        }
+        ptr::null()
+    });
+    // SAFETY: entries are never deallocated.
+    if let Some(entry) = unsafe { entry.as_ref() } {
+        entry.thread_id.store(0, Ordering::Release);
+        entry.in_use.store(false, Ordering::Release);
+    }
+}
+
+/// Forgets all threads but the current one, which goes by `thread_id` now.
+/// For the child after `fork`, where no other thread is left.
+pub fn reset_after_fork(thread_id: u64) {
+    let own = ENTRY.try_with(Cell::get).unwrap_or(ptr::null());
+    let mut current = HEAD.load(Ordering::Acquire).cast_const();
+    // SAFETY: entries are never deallocated.
+    while let Some(entry) = unsafe { current.as_ref() } {
+        if current == own {
+            entry.thread_id.store(thread_id, Ordering::Release);
+        } else {
+            entry.thread_id.store(0, Ordering::Release);
+            entry.in_use.store(false, Ordering::Release);
+        }
+        current = entry.next;
+    }
+}
+
+/// Finds a free entry, or adds a new one to the list.
+fn claim() -> &'static Entry {
+    let mut current = HEAD.load(Ordering::Acquire).cast_const();
+    // SAFETY: entries are never deallocated.
+    while let Some(entry) = unsafe { current.as_ref() } {
+        if entry.in_use.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        {
+            return entry;
+        }
+        current = entry.next;
+    }
+
+    let entry = Box::leak(Box::new(Entry {
+        next: ptr::null(),
+        in_use: AtomicBool::new(true),
+        thread_id: AtomicU64::new(0),
+        stack_start: AtomicUsize::new(0),
+        stack_end: AtomicUsize::new(0),
+        guard_start: AtomicUsize::new(0),
+        guard_end: AtomicUsize::new(0),
+    }));
+    let mut head = HEAD.load(Ordering::Relaxed);
+    loop {
+        entry.next = head;
+        match HEAD.compare_exchange_weak(head, entry, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => return entry,
+            Err(new) => head = new,
+        }
+    }
+}
+
+/// Reads an entry, unless it is free or changed while being read.
+fn read(entry: &Entry) -> Option<(u64, Range<usize>, Range<usize>)> {
+    let thread_id = entry.thread_id.load(Ordering::Acquire);
+    if thread_id == 0 {
+        return None;
+    }
+    let stack = entry.stack_start.load(Ordering::Relaxed)..entry.stack_end.load(Ordering::Relaxed);
+    let guard = entry.guard_start.load(Ordering::Relaxed)..entry.guard_end.load(Ordering::Relaxed);
+    fence(Ordering::Acquire);
+    (entry.thread_id.load(Ordering::Relaxed) == thread_id).then_some((thread_id, stack, guard))
+}
+
+/// Calls `f` with the id, stack bounds and guard range of every registered
+/// thread. Async-signal-safe.
+pub fn for_each_registered(mut f: impl FnMut(u64, Range<usize>, Range<usize>)) {
+    let mut current = HEAD.load(Ordering::Acquire).cast_const();
+    // SAFETY: entries are never deallocated.
+    while let Some(entry) = unsafe { current.as_ref() } {
+        if let Some((thread_id, stack, guard)) = read(entry) {
+            f(thread_id, stack, guard);
+        }
+        current = entry.next;
+    }
+}
+
+/// The stack bounds and guard range of the registered thread with the given
+/// id. Async-signal-safe.
+pub fn lookup_registered(thread_id: u64) -> Option<(Range<usize>, Range<usize>)> {
+    let mut found = None;
+    for_each_registered(|id, stack, guard| {
+        if id == thread_id {
+            found = Some((stack, guard));
+        }
+    });
+    found
+}
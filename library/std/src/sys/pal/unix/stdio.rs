@@ -1,6 +1,7 @@
 use crate::io::{self, BorrowedCursor, IoSlice, IoSliceMut};
 use crate::mem::ManuallyDrop;
-use crate::os::unix::io::FromRawFd;
+use crate::os::unix::io::{FromRawFd, IntoRawFd, OwnedFd};
+use crate::sync::atomic::{AtomicI32, Ordering};
 use crate::sys::fd::FileDesc;
 
 pub struct Stdin(());
@@ -97,3 +98,34 @@ pub fn is_ebadf(err: &io::Error) -> bool {
 pub fn panic_output() -> Option<impl io::Write> {
     Some(Stderr::new())
 }
+
+/// Where `abort_output` writes to, stderr unless `set_abort_output_fd` was
+/// called.
+static ABORT_FD: AtomicI32 = AtomicI32::new(libc::STDERR_FILENO);
+
+struct AbortOutput(libc::c_int);
+
+impl io::Write for AbortOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        unsafe { ManuallyDrop::new(FileDesc::from_raw_fd(self.0)).write(buf) }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The output for the messages of `rtabort!` and the stack overflow handler.
+/// Writing to it is async-signal-safe.
+pub fn abort_output() -> Option<impl io::Write> {
+    Some(AbortOutput(ABORT_FD.load(Ordering::Relaxed)))
+}
+
+/// Makes `abort_output` write to `fd` from now on. `fd` is never closed: a
+/// signal handler on another thread may still be writing to it after a later
+/// call replaces it, and closing it then would let the number be reused for
+/// some other file.
+pub fn set_abort_output_fd(fd: OwnedFd) {
+    ABORT_FD.store(fd.into_raw_fd(), Ordering::Relaxed);
+}
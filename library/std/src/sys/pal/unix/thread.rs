@@ -1,6 +1,7 @@
 use crate::ffi::CStr;
 use crate::mem::{self, ManuallyDrop};
 use crate::num::NonZero;
+use crate::ops::Range;
 #[cfg(all(target_os = "linux", target_env = "gnu"))]
 use crate::sys::weak::dlsym;
 #[cfg(any(target_os = "solaris", target_os = "illumos", target_os = "nto",))]
@@ -38,6 +39,10 @@ pub struct Thread {
     id: libc::pthread_t,
 }
 
+/// What `thread_start` gets: the stack size, the guard size, the guard range
+/// if std set it up itself, and the closure to run.
+type ThreadStart = (usize, Option<usize>, Option<Range<usize>>, Box<dyn FnOnce()>);
+
 // Some platforms may have pthread_t as a pointer in which case we still want
 // a thread to be Send/Sync
 unsafe impl Send for Thread {}
@@ -45,9 +50,11 @@ unsafe impl Sync for Thread {}
 
 impl Thread {
     // unsafe: see thread::Builder::spawn_unchecked for safety requirements
-    pub unsafe fn new(stack: usize, p: Box<dyn FnOnce()>) -> io::Result<Thread> {
-        let p = Box::into_raw(Box::new(p));
-        let mut native: libc::pthread_t = mem::zeroed();
+    pub unsafe fn new(
+        stack: usize,
+        guard: Option<usize>,
+        p: Box<dyn FnOnce()>,
+    ) -> io::Result<Thread> {
         let mut attr: libc::pthread_attr_t = mem::zeroed();
         assert_eq!(libc::pthread_attr_init(&mut attr), 0);
 
@@ -63,7 +70,7 @@ pub unsafe fn new(stack: usize, p: Box<dyn FnOnce()>) -> io::Result<Thread> {
 
         #[cfg(not(target_os = "espidf"))]
         {
-            let stack_size = cmp::max(stack, min_stack_size(&attr));
+            let stack_size = cmp::max(stack, min_stack_size(&attr) + STD_SETUP_STACK_SIZE);
 
             match libc::pthread_attr_setstacksize(&mut attr, stack_size) {
                 0 => {}
@@ -81,6 +88,103 @@ pub unsafe fn new(stack: usize, p: Box<dyn FnOnce()>) -> io::Result<Thread> {
             };
         }
 
+        if let Some(guard) = guard {
+            set_guard_size(&mut attr, guard);
+        }
+
+        Thread::spawn(attr, Box::new((stack, guard, None, p)))
+    }
+
+    /// Spawns a thread that runs on the `len` bytes at `region`, instead of
+    /// a stack the system allocates. The lowest pages of the region become
+    /// its guard, `guard` bytes of them rounded up to whole pages, or one
+    /// page if `None`. They stay inaccessible after the thread exits.
+    ///
+    /// unsafe: see thread::Builder::stack_region for safety requirements
+    #[cfg(not(target_os = "espidf"))]
+    pub unsafe fn new_on_stack(
+        region: *mut u8,
+        len: usize,
+        guard: Option<usize>,
+        p: Box<dyn FnOnce()>,
+    ) -> io::Result<Thread> {
+        // FIXME(libc): the libc crate only binds it for illumos yet.
+        extern "C" {
+            fn pthread_attr_setstack(
+                attr: *mut libc::pthread_attr_t,
+                stackaddr: *mut libc::c_void,
+                stacksize: libc::size_t,
+            ) -> libc::c_int;
+        }
+
+        let page_size = os::page_size();
+        let guard_size = guard.map_or(page_size, |size| size.next_multiple_of(page_size));
+        // Whole pages only, some implementations insist on it.
+        let stack_size = len.saturating_sub(guard_size) / page_size * page_size;
+        let mut attr: libc::pthread_attr_t = mem::zeroed();
+        assert_eq!(libc::pthread_attr_init(&mut attr), 0);
+        if region.addr() % page_size != 0 {
+            assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+            return Err(io::const_io_error!(
+                io::ErrorKind::InvalidInput,
+                "stack region is not page-aligned",
+            ));
+        }
+        // Unlike with `Thread::new`, there is no rounding up: tell the caller
+        // how much it takes.
+        let min_stack = (min_stack_size(&attr) + STD_SETUP_STACK_SIZE).next_multiple_of(page_size);
+        if stack_size < min_stack {
+            assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "stack region is too small: the stack needs at least {min_stack} bytes, \
+                     in addition to {guard_size} bytes of guard"
+                ),
+            ));
+        }
+
+        let stack = region.add(guard_size);
+        let ret = pthread_attr_setstack(&mut attr, stack.cast(), stack_size);
+        if ret != 0 {
+            assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+            return Err(io::Error::from_raw_os_error(ret));
+        }
+        if guard_size != 0 && libc::mprotect(region.cast(), guard_size, libc::PROT_NONE) != 0 {
+            let err = io::Error::last_os_error();
+            assert_eq!(libc::pthread_attr_destroy(&mut attr), 0);
+            return Err(err);
+        }
+
+        let guard_range = (guard_size != 0).then(|| region.addr()..stack.addr());
+        let thread = Thread::spawn(attr, Box::new((stack_size, Some(guard_size), guard_range, p)));
+        if thread.is_err() && guard_size != 0 {
+            // Give the caller their memory back the way it was.
+            libc::mprotect(region.cast(), guard_size, libc::PROT_READ | libc::PROT_WRITE);
+        }
+        thread
+    }
+
+    #[cfg(target_os = "espidf")]
+    pub unsafe fn new_on_stack(
+        _region: *mut u8,
+        _len: usize,
+        _guard: Option<usize>,
+        _p: Box<dyn FnOnce()>,
+    ) -> io::Result<Thread> {
+        Err(io::const_io_error!(
+            io::ErrorKind::Unsupported,
+            "threads can't be spawned on a given stack region on this platform",
+        ))
+    }
+
+    /// Creates the thread with `attr`, which is destroyed. The stack overflow
+    /// handler needs to know the guard size on some platforms, and reports
+    /// the stack size, so both travel along with the closure, as does the
+    /// guard range if std set it up itself.
+    unsafe fn spawn(mut attr: libc::pthread_attr_t, p: Box<ThreadStart>) -> io::Result<Thread> {
+        let p = Box::into_raw(p);
+        let mut native: libc::pthread_t = mem::zeroed();
         let ret = libc::pthread_create(&mut native, &attr, thread_start, p as *mut _);
         // Note: if the thread creation fails and this assert fails, then p will
         // be leaked. However, an alternative design could cause double-free
@@ -98,11 +202,12 @@ pub unsafe fn new(stack: usize, p: Box<dyn FnOnce()>) -> io::Result<Thread> {
 
         extern "C" fn thread_start(main: *mut libc::c_void) -> *mut libc::c_void {
             unsafe {
+                let (stack, guard, guard_range, main) = *Box::from_raw(main as *mut ThreadStart);
                 // Next, set up our stack overflow handler which may get triggered if we run
-                // out of stack.
-                let _handler = stack_overflow::Handler::new();
+                // out of stack. It stays in place until the thread's TLS destructors have run.
+                stack_overflow::register_spawned_thread(stack, guard, guard_range);
                 // Finally, let's run some code.
-                Box::from_raw(main as *mut Box<dyn FnOnce()>)();
+                main();
             }
             ptr::null_mut()
         }
@@ -728,6 +833,14 @@ fn find_mountpoint(group_path: &Path) -> Option<(Cow<'static, str>, &Path)> {
     }
 }
 
+/// What std itself uses of a new thread's stack before the thread's closure
+/// runs, on top of what the platform needs: setting up stack overflow
+/// detection, the thread's name and its output capture. With less, the
+/// thread would overflow before running any of the program's code, and the
+/// report would make little sense.
+#[cfg(not(target_os = "espidf"))]
+const STD_SETUP_STACK_SIZE: usize = 16 * 1024;
+
 // glibc >= 2.15 has a __pthread_get_minstack() function that returns
 // PTHREAD_STACK_MIN plus bytes needed for thread-local storage.
 // We need that information to avoid blowing up when a small stack
@@ -746,6 +859,58 @@ unsafe fn min_stack_size(attr: *const libc::pthread_attr_t) -> usize {
     }
 }
 
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "hurd",
+    target_os = "aix",
+    target_os = "haiku",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "nto",
+    target_vendor = "apple",
+    target_os = "solaris",
+    target_os = "illumos",
+))]
+unsafe fn set_guard_size(attr: *mut libc::pthread_attr_t, guard: usize) {
+    // FIXME(libc): the libc crate doesn't bind it for these yet.
+    #[cfg(any(target_vendor = "apple", target_os = "solaris", target_os = "illumos"))]
+    extern "C" {
+        fn pthread_attr_setguardsize(
+            attr: *mut libc::pthread_attr_t,
+            guardsize: libc::size_t,
+        ) -> libc::c_int;
+    }
+    #[cfg(not(any(target_vendor = "apple", target_os = "solaris", target_os = "illumos")))]
+    use libc::pthread_attr_setguardsize;
+
+    // The only possible error is an invalid `attr`, and the size is rounded
+    // up to whole pages by the implementation.
+    assert_eq!(pthread_attr_setguardsize(attr, guard), 0);
+}
+
+// The remaining platforms don't let us choose the guard size.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "hurd",
+    target_os = "aix",
+    target_os = "haiku",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "nto",
+    target_vendor = "apple",
+    target_os = "solaris",
+    target_os = "illumos",
+)))]
+unsafe fn set_guard_size(_: *mut libc::pthread_attr_t, _: usize) {}
+
 // No point in looking up __pthread_get_minstack() on non-glibc platforms.
 #[cfg(all(not(all(target_os = "linux", target_env = "gnu")), not(target_os = "netbsd")))]
 unsafe fn min_stack_size(_: *const libc::pthread_attr_t) -> usize {
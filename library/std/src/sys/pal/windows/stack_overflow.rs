@@ -1,6 +1,7 @@
 #![cfg_attr(test, allow(dead_code))]
 
 use crate::sys::c;
+use crate::sys::stack_overflow::report;
 use crate::thread;
 
 /// Reserve stack space for use in stack overflow exceptions.
@@ -11,6 +12,31 @@ pub fn reserve_stack() {
     debug_assert_ne!(result, 0, "failed to reserve stack space for exception handling");
 }
 
+/// The exception handler is always installed by `init`.
+pub fn ensure_init() {}
+
+/// Reserves the stack space for a thread that std didn't spawn. The exception
+/// handler itself is process-wide already.
+pub fn register_current_thread() {
+    reserve_stack();
+}
+
+/// The exception handler runs on the faulting thread's own (reserved) stack
+/// and looks the name up itself.
+pub unsafe fn set_current_name(_name: Option<&crate::ffi::CStr>) {}
+
+// FIXME: `GetCurrentThreadStackLimits` would tell, but it isn't in the bindings.
+pub fn remaining_stack(_sp: usize) -> Option<usize> {
+    None
+}
+
+pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+    Err(crate::io::const_io_error!(
+        crate::io::ErrorKind::Unsupported,
+        "switching stacks is not supported on this platform",
+    ))
+}
+
 unsafe extern "system" fn vectored_handler(ExceptionInfo: *mut c::EXCEPTION_POINTERS) -> i32 {
     // SAFETY: It's up to the caller (which in this case is the OS) to ensure that `ExceptionInfo` is valid.
     unsafe {
@@ -18,16 +44,19 @@ pub fn reserve_stack() {
         let code = rec.ExceptionCode;
 
         if code == c::EXCEPTION_STACK_OVERFLOW {
-            rtprintpanic!(
-                "\nthread '{}' has overflowed its stack\n",
-                thread::current().name().unwrap_or("<unknown>")
-            );
+            // The reserved stack isn't enough for a backtrace. The process
+            // is terminated by the OS once we're done.
+            report::report(&report::Overflow {
+                name: thread::current().name(),
+                ..Default::default()
+            });
         }
         c::EXCEPTION_CONTINUE_SEARCH
     }
 }
 
 pub fn init() {
+    report::init_report_mode();
     // SAFETY: `vectored_handler` has the correct ABI and is safe to call during exception handling.
     unsafe {
         let result = c::AddVectoredExceptionHandler(0, Some(vectored_handler));
@@ -2,3 +2,15 @@
 
 pub unsafe fn reserve_stack() {}
 pub unsafe fn init() {}
+pub fn ensure_init() {}
+pub fn register_current_thread() {}
+pub unsafe fn set_current_name(_name: Option<&crate::ffi::CStr>) {}
+pub fn remaining_stack(_sp: usize) -> Option<usize> {
+    None
+}
+pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+    Err(crate::io::const_io_error!(
+        crate::io::ErrorKind::Unsupported,
+        "switching stacks is not supported on this platform",
+    ))
+}
@@ -0,0 +1,28 @@
+//! Stack overflow detection.
+//!
+//! Only the Unix and Windows platform layers detect stack overflows, the
+//! other platforms only get what is used outside of their platform layer.
+//! How overflows are reported is shared between them, see `report`.
+
+cfg_if::cfg_if! {
+    if #[cfg(any(unix, windows))] {
+        pub mod report;
+
+        pub use self::report::{report_mode, set_report_mode, ReportMode};
+        pub use super::pal::stack_overflow::*;
+    } else {
+        /// There is no stack overflow detection to set up on this platform.
+        pub fn ensure_init() {}
+        pub fn register_current_thread() {}
+        pub unsafe fn set_current_name(_name: Option<&crate::ffi::CStr>) {}
+        pub fn remaining_stack(_sp: usize) -> Option<usize> {
+            None
+        }
+        pub fn run_on_new_stack(_size: usize, _f: &mut dyn FnMut()) -> crate::io::Result<()> {
+            Err(crate::io::const_io_error!(
+                crate::io::ErrorKind::Unsupported,
+                "switching stacks is not supported on this platform",
+            ))
+        }
+    }
+}
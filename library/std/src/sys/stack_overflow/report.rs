@@ -0,0 +1,150 @@
+//! Reporting of stack overflows, shared by all platform layers that detect
+//! them. Those only find out that a thread overflowed and what they can tell
+//! about it, what gets printed and how is up to this module, so that the
+//! report reads the same everywhere.
+//!
+//! All of this runs in a signal handler or its equivalent, on whatever little
+//! stack is left there, so it must neither allocate nor take locks (except
+//! for the backtrace, which is best effort).
+
+use crate::ffi::OsStr;
+use crate::fmt;
+use crate::sync::atomic::{AtomicU8, Ordering};
+
+/// What is printed before aborting on a stack overflow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportMode {
+    /// A message naming the thread, the default.
+    Abort,
+    /// Nothing at all.
+    Quiet,
+    /// The message and a backtrace of the overflowing thread.
+    Backtrace,
+}
+
+/// The `ReportMode`, or `REPORT_MODE_UNSET` until `set_report_mode` or
+/// `init_report_mode` sets it. An atomic, since the handler reads it.
+static REPORT_MODE: AtomicU8 = AtomicU8::new(REPORT_MODE_UNSET);
+const REPORT_MODE_UNSET: u8 = u8::MAX;
+
+pub fn set_report_mode(mode: ReportMode) {
+    REPORT_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn report_mode() -> ReportMode {
+    match REPORT_MODE.load(Ordering::Relaxed) {
+        mode if mode == ReportMode::Quiet as u8 => ReportMode::Quiet,
+        mode if mode == ReportMode::Backtrace as u8 => ReportMode::Backtrace,
+        _ => ReportMode::Abort,
+    }
+}
+
+/// Takes the mode from `RUST_STACK_OVERFLOW`, unless it was set already:
+/// with the `lazy_stack_overflow` feature, the program may have done that
+/// before we get here, and then it takes precedence.
+pub fn init_report_mode() {
+    let var = crate::env::var_os("RUST_STACK_OVERFLOW");
+    let mode = match var.as_deref().and_then(OsStr::to_str) {
+        Some("abort") => ReportMode::Abort,
+        Some("quiet") => ReportMode::Quiet,
+        Some("backtrace") => ReportMode::Backtrace,
+        _ => return,
+    };
+    let _ = REPORT_MODE.compare_exchange(
+        REPORT_MODE_UNSET,
+        mode as u8,
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    );
+}
+
+/// What the platform layer could tell about an overflow.
+#[derive(Default)]
+pub struct Overflow<'a> {
+    /// The name of the overflowing thread, if it has one.
+    pub name: Option<&'a str>,
+    /// The system's id for the overflowing thread.
+    pub id: Option<u64>,
+    /// The size of the stack std spawned the thread with, if it did. Zero
+    /// asks for the platform's default, which we don't know.
+    pub stack_size: Option<usize>,
+    /// Whether the report is made on the overflowing thread itself, with
+    /// enough stack to take a backtrace of it.
+    pub can_backtrace: bool,
+}
+
+/// Prints the report for `overflow`, as the `ReportMode` asks. Harnesses
+/// that capture the thread's output (see `io::set_output_capture`) get it
+/// too, where adding it to their buffer is possible from here, i.e. without
+/// blocking or allocating. The process is about to die and takes the buffer
+/// with it though, so the report always goes to the abort output as well.
+pub fn report(overflow: &Overflow<'_>) {
+    let mode = report_mode();
+    if mode == ReportMode::Quiet {
+        return;
+    }
+    let mut msg = Message { buf: [0; 512], len: 0 };
+    let _ = fmt::Write::write_fmt(&mut msg, format_args!("{overflow}"));
+    let msg = &msg.buf[..msg.len];
+    crate::io::try_append_to_capture(msg);
+    if let Some(mut out) = crate::sys::abort_output::abort_output() {
+        let _ = crate::io::Write::write_all(&mut out, msg);
+    }
+    if mode == ReportMode::Backtrace && overflow.can_backtrace {
+        print_backtrace();
+    }
+}
+
+/// Reports `overflow` and aborts the process, for the platforms where
+/// nothing else would end it.
+pub fn report_and_abort(overflow: &Overflow<'_>) -> ! {
+    report(overflow);
+    if report_mode() == ReportMode::Quiet {
+        crate::sys::abort_internal();
+    }
+    rtabort!("stack overflow");
+}
+
+/// Prints a backtrace of the current thread to the abort output. This is
+/// best effort: it takes the backtrace lock and may allocate, so it can hang
+/// or crash if the thread was interrupted in the allocator or in another
+/// backtrace. After an overflow, it can also run out of stack.
+pub fn print_backtrace() {
+    if let Some(mut out) = crate::sys::abort_output::abort_output() {
+        let mut lock = crate::sys::backtrace::lock();
+        let _ = lock.print(&mut out, crate::backtrace_rs::PrintFmt::Short);
+    }
+}
+
+impl fmt::Display for Overflow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\nthread '{}'", self.name.unwrap_or("<unknown>"))?;
+        if let Some(id) = self.id {
+            write!(f, " (id {id})")?;
+        }
+        match self.stack_size {
+            Some(size) if size != 0 => write!(
+                f,
+                " has overflowed its stack (stack size: {size} bytes)\n\
+                 note: use `std::thread::Builder::stack_size` or the `RUST_MIN_STACK` \
+                 environment variable to spawn threads with a larger stack\n"
+            ),
+            _ => writeln!(f, " has overflowed its stack"),
+        }
+    }
+}
+
+/// Formats into a fixed buffer, cutting off what doesn't fit.
+struct Message {
+    buf: [u8; 512],
+    len: usize,
+}
+
+impl fmt::Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        if n == s.len() { Ok(()) } else { Err(fmt::Error) }
+    }
+}
@@ -0,0 +1,30 @@
+//! Spawning threads on a stack region the caller allocated, see
+//! `thread::Builder::stack_region`. Only Unix supports this so far.
+
+use super::thread::Thread;
+use crate::io;
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        pub unsafe fn spawn(
+            region: *mut u8,
+            len: usize,
+            guard: Option<usize>,
+            p: Box<dyn FnOnce()>,
+        ) -> io::Result<Thread> {
+            unsafe { Thread::new_on_stack(region, len, guard, p) }
+        }
+    } else {
+        pub unsafe fn spawn(
+            _region: *mut u8,
+            _len: usize,
+            _guard: Option<usize>,
+            _p: Box<dyn FnOnce()>,
+        ) -> io::Result<Thread> {
+            Err(io::const_io_error!(
+                io::ErrorKind::Unsupported,
+                "threads can't be spawned on a given stack region on this platform",
+            ))
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Measuring how much of its stack a thread used, see
+//! `thread::Builder::measure_stack_usage`. Only Unix supports this so far,
+//! since it relies on the stack bounds that stack overflow detection keeps.
+//!
+//! The unused part of the stack is painted with a pattern when the thread
+//! starts, and the deepest point the thread got to is then the lowest word
+//! that no longer holds it.
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        use crate::cell::Cell;
+        use crate::{mem, ptr};
+
+        const PATTERN_BYTE: u8 = 0xa5;
+        const PATTERN: usize = usize::from_ne_bytes([PATTERN_BYTE; mem::size_of::<usize>()]);
+        /// How far below its own frame `paint` stops, to stay clear of the
+        /// frames of what it calls.
+        const MARGIN: usize = 4096;
+
+        thread_local! {
+            /// The painted part of this thread's stack, and the top of the
+            /// stack.
+            static PAINTED: Cell<Option<(usize, usize, usize)>> = const { Cell::new(None) };
+        }
+
+        /// Paints the unused part of the current thread's stack, if its
+        /// bounds are known.
+        #[inline(never)]
+        pub fn paint() {
+            let Some((stack, guard)) = crate::sys::stack_overflow::current_stack_bounds() else {
+                return;
+            };
+            let marker = 0u8;
+            let sp = ptr::addr_of!(marker).addr();
+            let low = stack.start.max(guard.end).next_multiple_of(mem::size_of::<usize>());
+            let high = sp.saturating_sub(MARGIN) & !(mem::size_of::<usize>() - 1);
+            if low >= high || sp >= stack.end {
+                return;
+            }
+            // SAFETY: this part of the thread's stack lies between its guard
+            // and the frames in use, nothing lives there.
+            unsafe { ptr::write_bytes(low as *mut u8, PATTERN_BYTE, high - low) };
+            PAINTED.set(Some((low, high, stack.end)));
+        }
+
+        /// The most stack the current thread has used so far, in bytes, if
+        /// `paint` could paint it.
+        pub fn high_water_mark() -> Option<usize> {
+            let (low, high, top) = PAINTED.try_with(Cell::get).ok().flatten()?;
+            let mut addr = low;
+            // SAFETY: the painted range is part of our stack and word
+            // aligned. The reads are volatile since the compiler doesn't know
+            // about the writes of the frames that have come and gone since.
+            while addr < high && unsafe { ptr::read_volatile(addr as *const usize) } == PATTERN {
+                addr += mem::size_of::<usize>();
+            }
+            Some(top - addr)
+        }
+    } else {
+        pub fn paint() {}
+
+        pub fn high_water_mark() -> Option<usize> {
+            None
+        }
+    }
+}
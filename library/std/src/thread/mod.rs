@@ -165,7 +165,7 @@
 use crate::mem::{self, forget, ManuallyDrop};
 use crate::num::NonZero;
 use crate::pin::Pin;
-use crate::ptr::addr_of_mut;
+use crate::ptr::{self, addr_of, addr_of_mut};
 use crate::sync::atomic::{AtomicUsize, Ordering};
 use crate::sync::Arc;
 use crate::sys::sync::Parker;
@@ -251,8 +251,26 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned thread in bytes
     stack_size: Option<usize>,
+    // The size of the guard area below the stack in bytes
+    guard_size: Option<usize>,
+    // Memory for the stack that the caller allocated
+    stack_region: Option<StackRegion>,
+    // Whether to record how much of its stack the thread uses
+    measure_stack_usage: bool,
 }
 
+/// The memory given to [`Builder::stack_region`].
+#[derive(Clone, Copy, Debug)]
+struct StackRegion {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// SAFETY: the caller of `Builder::stack_region` hands the memory over to the
+// thread, whichever thread spawns it.
+unsafe impl Send for StackRegion {}
+unsafe impl Sync for StackRegion {}
+
 impl Builder {
     /// Generates the base configuration for spawning a thread, from which
     /// configuration methods can be chained.
@@ -274,7 +292,13 @@ impl Builder {
     /// ```
     #[stable(feature = "rust1", since = "1.0.0")]
     pub fn new() -> Builder {
-        Builder { name: None, stack_size: None }
+        Builder {
+            name: None,
+            stack_size: None,
+            guard_size: None,
+            stack_region: None,
+            measure_stack_usage: false,
+        }
     }
 
     /// Names the thread-to-be. Currently the name is used for identification
@@ -310,7 +334,8 @@ pub fn name(mut self, name: String) -> Builder {
     /// Sets the size of the stack (in bytes) for the new thread.
     ///
     /// The actual stack size may be greater than this value if
-    /// the platform specifies a minimal stack size.
+    /// the platform specifies a minimal stack size, or if it would not leave
+    /// room for the standard library to set up the new thread.
     ///
     /// For more information about the stack size for threads, see
     /// [this module-level documentation][stack-size].
@@ -330,6 +355,158 @@ pub fn stack_size(mut self, size: usize) -> Builder {
         self
     }
 
+    /// Sets the size of the guard area (in bytes) for the new thread.
+    ///
+    /// The guard area is a region of inaccessible memory right below the
+    /// thread's stack, so that running out of stack faults instead of
+    /// corrupting other memory. The platform's default is usually a single
+    /// page. Programs running many threads may want to use less address
+    /// space on guards, while functions with large stack frames may need a
+    /// larger guard to be sure not to skip over it. The size is rounded up to
+    /// a multiple of the page size, and the stack overflow handler takes it
+    /// into account when reporting stack overflows.
+    ///
+    /// This is currently only supported on Unix platforms that implement
+    /// `pthread_attr_setguardsize`, and ignored elsewhere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(thread_guard_size)]
+    ///
+    /// use std::thread;
+    ///
+    /// let builder = thread::Builder::new().guard_size(64 * 1024);
+    /// ```
+    #[unstable(feature = "thread_guard_size", issue = "none")]
+    pub fn guard_size(mut self, size: usize) -> Builder {
+        self.guard_size = Some(size);
+        self
+    }
+
+    /// Spawns the new thread without a guard area below its stack.
+    ///
+    /// This saves the address space of the guard, which adds up on 32-bit
+    /// targets running thousands of threads. The price is that running out
+    /// of stack is no longer guaranteed to fault: the thread may silently
+    /// overwrite whatever memory lies below its stack instead. Such threads
+    /// are not covered by the stack overflow handler either.
+    ///
+    /// This is the same as `guard_size(0)`, with the same platform support.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(thread_no_guard)]
+    ///
+    /// use std::thread;
+    ///
+    /// let builder = thread::Builder::new().stack_size(64 * 1024).no_guard();
+    /// ```
+    #[unstable(feature = "thread_no_guard", issue = "none")]
+    pub fn no_guard(mut self) -> Builder {
+        self.guard_size = Some(0);
+        self
+    }
+
+    /// Runs the new thread on the `len` bytes of memory at `ptr`, instead of
+    /// a stack that the system allocates.
+    ///
+    /// This lets the stack live in memory with special properties, e.g. on
+    /// huge pages or pinned to a NUMA node. The lowest pages of the region
+    /// are made inaccessible to serve as the thread's guard area, of the size
+    /// set with [`guard_size`] or a single page by default, and the stack
+    /// overflow handler knows about them like about any other guard. The
+    /// rest of the region is the stack, [`stack_size`] is ignored.
+    ///
+    /// The guard pages stay inaccessible after the thread has exited, make
+    /// them accessible again (e.g. with `mprotect`) before reusing the
+    /// memory for anything but another thread's stack.
+    ///
+    /// This is currently only supported on Unix platforms, spawning fails
+    /// with [`io::ErrorKind::Unsupported`] elsewhere. It also fails if `ptr`
+    /// isn't aligned to the page size or the region is too small.
+    ///
+    /// # Safety
+    ///
+    /// The region must be valid for reads and writes, and must not be used
+    /// for anything else until the thread has been joined. A thread that is
+    /// never joined keeps it forever.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// #![feature(thread_stack_region)]
+    ///
+    /// use std::alloc::{alloc, Layout};
+    /// use std::thread;
+    ///
+    /// let layout = Layout::from_size_align(1 << 20, 1 << 16).unwrap();
+    /// let stack = unsafe { alloc(layout) };
+    /// assert!(!stack.is_null());
+    ///
+    /// let handler = unsafe { thread::Builder::new().stack_region(stack, layout.size()) }
+    ///     .spawn(|| {
+    ///         // thread code
+    ///     })
+    ///     .unwrap();
+    /// handler.join().unwrap();
+    /// // The guard pages at the start of the region are still inaccessible,
+    /// // so the memory is kept for good here.
+    /// ```
+    ///
+    /// [`guard_size`]: Builder::guard_size
+    /// [`stack_size`]: Builder::stack_size
+    #[unstable(feature = "thread_stack_region", issue = "none")]
+    pub unsafe fn stack_region(mut self, ptr: *mut u8, len: usize) -> Builder {
+        self.stack_region = Some(StackRegion { ptr, len });
+        self
+    }
+
+    /// Records how much stack the new thread uses, for
+    /// [`Thread::stack_high_water_mark`] to report.
+    ///
+    /// When the thread starts, the unused part of its stack is filled with a
+    /// pattern, and the deepest point the thread got to is then found by
+    /// looking for the lowest address that no longer holds it. This takes
+    /// time proportional to the stack size, and makes the system back all of
+    /// the stack with memory right away instead of as the thread grows into
+    /// it, so it is meant for tuning [`stack_size`] during development rather
+    /// than for every thread of a production program.
+    ///
+    /// This is currently only supported on Unix platforms, for threads whose
+    /// stack bounds the standard library knows. Elsewhere, the thread is
+    /// spawned as usual and its high water mark is unknown.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #![feature(thread_stack_high_water_mark)]
+    ///
+    /// use std::thread;
+    ///
+    /// let handler = thread::Builder::new()
+    ///     .stack_size(256 * 1024)
+    ///     .measure_stack_usage()
+    ///     .spawn(|| {
+    ///         // thread code
+    ///     })
+    ///     .unwrap();
+    /// let thread = handler.thread().clone();
+    /// handler.join().unwrap();
+    ///
+    /// if let Some(used) = thread.stack_high_water_mark() {
+    ///     println!("the thread used {used} bytes of its stack");
+    /// }
+    /// ```
+    ///
+    /// [`stack_size`]: Builder::stack_size
+    #[unstable(feature = "thread_stack_high_water_mark", issue = "none")]
+    pub fn measure_stack_usage(mut self) -> Builder {
+        self.measure_stack_usage = true;
+        self
+    }
+
     /// Spawns a new thread by taking ownership of the `Builder`, and returns an
     /// [`io::Result`] to its [`JoinHandle`].
     ///
@@ -452,7 +629,7 @@ unsafe fn spawn_unchecked_<'scope, F, T>(
         F: Send,
         T: Send,
     {
-        let Builder { name, stack_size } = self;
+        let Builder { name, stack_size, guard_size, stack_region, measure_stack_usage } = self;
 
         let stack_size = stack_size.unwrap_or_else(|| {
             static MIN: AtomicUsize = AtomicUsize::new(0);
@@ -516,11 +693,31 @@ fn drop(&mut self) {
 
             crate::io::set_output_capture(output_capture);
 
+            // The stack overflow handler can't look the name up through
+            // `current()`, so it gets a pointer to it, kept alive by this
+            // handle until it is taken back below.
+            let overflow_name = their_thread.clone();
+            // SAFETY: the name is cleared before `overflow_name` is dropped.
+            unsafe { crate::sys::stack_overflow::set_current_name(overflow_name.cname()) };
+
+            // Where the high water mark goes once `f` is done.
+            let measured_thread = measure_stack_usage.then(|| their_thread.clone());
+            if measure_stack_usage {
+                crate::sys::stack_usage::paint();
+            }
+
             let f = f.into_inner();
             set_current(their_thread);
             let try_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
                 crate::sys::backtrace::__rust_begin_short_backtrace(f)
             }));
+            // Recorded before the result is handed over, so that it is there
+            // once the thread has been joined.
+            if let Some(thread) = measured_thread {
+                if let Some(used) = crate::sys::stack_usage::high_water_mark() {
+                    thread.inner.stack_high_water_mark.store(used, Ordering::Release);
+                }
+            }
             // SAFETY: `their_packet` as been built just above and moved by the
             // closure (it is an Arc<...>) and `my_packet` will be stored in the
             // same `JoinInner` as this closure meaning the mutation will be
@@ -530,6 +727,9 @@ fn drop(&mut self) {
             // will call `decrement_num_running_threads` and therefore signal that this thread is
             // done.
             drop(their_packet);
+            // SAFETY: clearing the name is always fine.
+            unsafe { crate::sys::stack_overflow::set_current_name(None) };
+            drop(overflow_name);
             // Here, the lifetime `'scope` can end. `main` keeps running for a bit
             // after that before returning itself.
         };
@@ -538,6 +738,10 @@ fn drop(&mut self) {
             scope_data.increment_num_running_threads();
         }
 
+        // The new thread sets up its stack overflow handler on its own, but
+        // the process-wide part may have been left for the first spawn.
+        crate::sys::stack_overflow::ensure_init();
+
         let main = Box::new(main);
         // SAFETY: dynamic size and alignment of the Box remain the same. See below for why the
         // lifetime change is justified.
@@ -558,7 +762,17 @@ fn drop(&mut self) {
             // Similarly, the `sys` implementation must guarantee that no references to the closure
             // exist after the thread has terminated, which is signaled by `Thread::join`
             // returning.
-            native: unsafe { imp::Thread::new(stack_size, main)? },
+            //
+            // For a stack region, `Builder::stack_region`'s caller guarantees it
+            // outlives the thread.
+            native: unsafe {
+                match stack_region {
+                    Some(StackRegion { ptr, len }) => {
+                        crate::sys::stack_region::spawn(ptr, len, guard_size, main)?
+                    }
+                    None => imp::Thread::new(stack_size, guard_size, main)?,
+                }
+            },
             thread: my_thread,
             packet: my_packet,
         })
@@ -708,6 +922,9 @@ pub(crate) fn try_current() -> Option<Thread> {
         .try_with(|current| {
             current
                 .get_or_init(|| {
+                    // Only threads that std didn't spawn get here, so this is
+                    // the first chance to watch their stack for overflows.
+                    crate::sys::stack_overflow::register_current_thread();
                     let thread = Thread::new_unnamed();
                     CURRENT_ID.set(Some(thread.id()));
                     thread
@@ -842,6 +1059,110 @@ pub fn panicking() -> bool {
     panicking::panicking()
 }
 
+/// Runs `f` with at least `red_zone` bytes of stack to spare, moving it to a
+/// new stack of `grow_size` bytes if the current one is running low.
+///
+/// Deeply recursive code, such as a parser or an interpreter working on
+/// untrusted input, can wrap its recursive calls in this function to keep
+/// going where it would otherwise overflow the stack. The amount of stack
+/// left is determined from the same bounds the stack overflow handler uses.
+/// If those aren't known for the current thread, e.g. because the platform
+/// doesn't detect stack overflows, `f` is simply run in place.
+///
+/// When the current stack is running low, a new stack of `grow_size` bytes is
+/// mapped, and `f` runs on it on the current thread, so it sees the same
+/// thread-locals and [`ThreadId`]. The new stack has a guard page of its own,
+/// and is unmapped when `f` returns. Overflowing it is reported like any
+/// other stack overflow, and a panic in `f` is propagated to the caller.
+/// Switching stacks is implemented on Unix for x86_64 and AArch64. Elsewhere,
+/// `f` runs in place.
+///
+/// # Panics
+///
+/// Panics if a new stack is needed and can't be mapped.
+///
+/// # Examples
+///
+/// ```
+/// #![feature(thread_stack_guarantee)]
+///
+/// use std::thread;
+///
+/// fn depth(n: u64) -> u64 {
+///     if n == 0 {
+///         return 0;
+///     }
+///     thread::with_stack_guarantee(64 * 1024, 1024 * 1024, || depth(n - 1) + 1)
+/// }
+///
+/// assert_eq!(depth(1000), 1000);
+/// ```
+#[unstable(feature = "thread_stack_guarantee", issue = "none")]
+pub fn with_stack_guarantee<F, R>(red_zone: usize, grow_size: usize, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    // The address of a local is as good a stack pointer as any.
+    let marker = 0u8;
+    let sp = crate::hint::black_box(addr_of!(marker)).addr();
+    match crate::sys::stack_overflow::remaining_stack(sp) {
+        Some(remaining) if remaining < red_zone => {}
+        _ => return f(),
+    }
+
+    let mut f = Some(f);
+    let mut result = None;
+    let run = &mut || result = f.take().map(|f| f());
+    match crate::sys::stack_overflow::run_on_new_stack(grow_size, run) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => return f.take().unwrap()(),
+        Err(e) => panic!("failed to map a new stack: {e}"),
+    }
+    result.unwrap()
+}
+
+/// Overflows the stack of the current thread.
+///
+/// This recurses until the thread runs out of stack, the way a program with
+/// unbounded recursion would, but without the optimizer getting a chance to
+/// turn the recursion into a loop or to leave it out. It is meant for tests
+/// of what happens on a stack overflow, whether of the standard library's
+/// own handler, of a crash reporter that sits on top of it, or of a new
+/// platform's.
+///
+/// Where the standard library detects stack overflows, this ends the process
+/// with the usual report. Elsewhere, what happens is up to the platform.
+///
+/// # Examples
+///
+/// ```no_run
+/// #![feature(thread_force_stack_overflow)]
+///
+/// use std::thread;
+///
+/// // The process aborts with "thread '<unnamed>' has overflowed its stack".
+/// thread::spawn(|| thread::force_stack_overflow()).join().unwrap();
+/// ```
+#[unstable(feature = "thread_force_stack_overflow", issue = "none")]
+pub fn force_stack_overflow() -> ! {
+    #[allow(unconditional_recursion)]
+    #[inline(never)]
+    fn recurse(depth: usize) -> usize {
+        let mut frame = [0u8; 1024];
+        // Volatile accesses can't be optimized out, so every call needs a
+        // frame of its own, and using the result after the call keeps it
+        // from being a tail call.
+        // SAFETY: the index is in bounds.
+        unsafe { ptr::write_volatile(&mut frame[depth % frame.len()], depth as u8) };
+        let below = recurse(depth + 1);
+        // SAFETY: the index is in bounds.
+        below + unsafe { ptr::read_volatile(&frame[depth % frame.len()]) } as usize
+    }
+
+    recurse(0);
+    unreachable!("the stack can't be infinite")
+}
+
 /// Uses [`sleep`].
 ///
 /// Puts the current thread to sleep for at least the specified amount of time.
@@ -1330,6 +1651,9 @@ struct Inner {
     name: ThreadName, // Guaranteed to be UTF-8
     id: ThreadId,
     parker: Parker,
+    // Set when a thread spawned with `Builder::measure_stack_usage` is done,
+    // zero until then.
+    stack_high_water_mark: AtomicUsize,
 }
 
 impl Inner {
@@ -1388,6 +1712,7 @@ fn new_inner(name: ThreadName) -> Thread {
             let ptr = Arc::get_mut_unchecked(&mut arc).as_mut_ptr();
             addr_of_mut!((*ptr).name).write(name);
             addr_of_mut!((*ptr).id).write(ThreadId::new());
+            addr_of_mut!((*ptr).stack_high_water_mark).write(AtomicUsize::new(0));
             Parker::new_in_place(addr_of_mut!((*ptr).parker));
             Pin::new_unchecked(arc.assume_init())
         };
@@ -1503,6 +1828,34 @@ pub fn name(&self) -> Option<&str> {
         self.inner.name.as_str()
     }
 
+    /// Returns the most stack the thread has used, in bytes, if it was
+    /// spawned with [`Builder::measure_stack_usage`].
+    ///
+    /// Called on the handle of the current thread, this measures how deep
+    /// the thread has got so far. For any other thread, the measurement is
+    /// taken when it has finished running its closure, so this returns
+    /// `None` until then, e.g. until the thread has been joined.
+    ///
+    /// The measurement includes what the platform and the standard library
+    /// use of the stack to start the thread. It is exact to the word, but
+    /// only counts stack that was written to: a large buffer on the stack
+    /// that was never filled is missed. Leave some headroom when choosing
+    /// a [`Builder::stack_size`] from it.
+    ///
+    /// Also returns `None` where measuring isn't supported, see
+    /// [`Builder::measure_stack_usage`].
+    #[unstable(feature = "thread_stack_high_water_mark", issue = "none")]
+    #[must_use]
+    pub fn stack_high_water_mark(&self) -> Option<usize> {
+        if self.id() == current_id() {
+            return crate::sys::stack_usage::high_water_mark();
+        }
+        match self.inner.stack_high_water_mark.load(Ordering::Acquire) {
+            0 => None,
+            used => Some(used),
+        }
+    }
+
     fn cname(&self) -> Option<&CStr> {
         self.inner.name.as_cstr()
     }
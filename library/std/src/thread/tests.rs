@@ -410,3 +410,118 @@ fn test_minimal_thread_stack() {
     assert_eq!(before, 0);
     assert_eq!(COUNT.load(Ordering::Relaxed), 1);
 }
+
+#[test]
+fn test_with_stack_guarantee() {
+    // Enough to spare: runs in place.
+    let id = thread::current().id();
+    assert_eq!(thread::with_stack_guarantee(0, 1024 * 1024, || thread::current().id()), id);
+    // Never enough to spare: runs on a new stack if the bounds are known, and
+    // either way on the same thread.
+    assert_eq!(
+        thread::with_stack_guarantee(usize::MAX, 1024 * 1024, || thread::current().id()),
+        id
+    );
+    // Panics come through.
+    let result = crate::panic::catch_unwind(|| {
+        thread::with_stack_guarantee(usize::MAX, 1024 * 1024, || panic_any(42u8))
+    });
+    assert_eq!(result.unwrap_err().downcast_ref::<u8>(), Some(&42));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "espidf")))]
+fn test_stack_region() {
+    use crate::alloc::{alloc, Layout};
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let layout = Layout::from_size_align(64 * page_size, page_size).unwrap();
+    let stack = unsafe { alloc(layout) };
+    assert!(!stack.is_null());
+    let region = stack.addr()..stack.addr() + layout.size();
+
+    let local = unsafe { Builder::new().stack_region(stack, layout.size()) }
+        .spawn(|| {
+            let local = 0u8;
+            crate::ptr::addr_of!(local).addr()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    // Above the guard page, which stays inaccessible, so the memory is leaked.
+    assert!((region.start + page_size..region.end).contains(&local));
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "espidf")))]
+fn test_stack_region_too_small() {
+    use crate::alloc::{alloc, dealloc, Layout};
+
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let layout = Layout::from_size_align(2 * page_size, page_size).unwrap();
+    let stack = unsafe { alloc(layout) };
+    assert!(!stack.is_null());
+
+    let err = unsafe { Builder::new().stack_region(stack, layout.size()) }
+        .spawn(|| unreachable!())
+        .unwrap_err();
+    assert_eq!(err.kind(), crate::io::ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("needs at least"), "{err}");
+    // Nothing was protected, the memory can be freed.
+    unsafe { dealloc(stack, layout) };
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+fn test_stack_high_water_mark() {
+    fn use_stack(depth: usize) {
+        let buf = [depth as u8; 1024];
+        crate::hint::black_box(&buf);
+        if depth > 0 {
+            use_stack(depth - 1);
+        }
+        // Keeps `buf` alive across the call, so it isn't a tail call.
+        crate::hint::black_box(&buf);
+    }
+
+    let handle = Builder::new()
+        .stack_size(1 << 20)
+        .measure_stack_usage()
+        .spawn(|| {
+            let before = thread::current().stack_high_water_mark().unwrap();
+            use_stack(64);
+            let after = thread::current().stack_high_water_mark().unwrap();
+            assert!(after >= before + 64 * 1024, "{before} -> {after}");
+            after
+        })
+        .unwrap();
+    let thread = handle.thread().clone();
+    let after = handle.join().unwrap();
+    let used = thread.stack_high_water_mark().unwrap();
+    assert!(after <= used && used < 1 << 20, "{after} -> {used}");
+
+    // Not measured unless asked for.
+    let handle = thread::spawn(|| use_stack(1));
+    let thread = handle.thread().clone();
+    handle.join().unwrap();
+    assert_eq!(thread.stack_high_water_mark(), None);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_is_stack_overflow() {
+    use crate::os::unix::stack_overflow::{is_stack_overflow, status};
+
+    thread::spawn(|| {
+        let local = 0u8;
+        assert!(!is_stack_overflow(crate::ptr::addr_of!(local).addr()));
+        // Only known where std detects stack overflows.
+        if let Some(guard) = status().guard_range() {
+            assert!(is_stack_overflow(guard.start));
+            assert!(is_stack_overflow(guard.end - 1));
+            assert!(!is_stack_overflow(guard.end));
+        }
+    })
+    .join()
+    .unwrap();
+}
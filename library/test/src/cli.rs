@@ -13,6 +13,8 @@ pub struct TestOpts {
     pub filters: Vec<String>,
     pub filter_exact: bool,
     pub force_run_in_process: bool,
+    /// Run every test in its own subprocess, even when panic=unwind.
+    pub run_in_subprocess: bool,
     pub exclude_should_panic: bool,
     pub run_ignored: RunIgnored,
     pub run_tests: bool,
@@ -53,6 +55,13 @@ fn optgroups() -> getopts::Options {
     opts.optflag("", "include-ignored", "Run ignored and not ignored tests")
         .optflag("", "ignored", "Run only ignored tests")
         .optflag("", "force-run-in-process", "Forces tests to run in-process when panic=abort")
+        .optflag(
+            "",
+            "run-in-subprocess",
+            "Runs each test in its own subprocess, so that a test \
+             aborting the process (e.g. by overflowing its stack) \
+             does not stop the others from running",
+        )
         .optflag("", "exclude-should-panic", "Excludes tests marked as should_panic")
         .optflag("", "test", "Run tests and not benchmarks")
         .optflag("", "bench", "Run benchmarks instead of tests")
@@ -257,7 +266,14 @@ fn parse_opts_impl(matches: getopts::Matches) -> OptRes {
 
     // Unstable flags
     let force_run_in_process = unstable_optflag!(matches, allow_unstable, "force-run-in-process");
+    let run_in_subprocess = unstable_optflag!(matches, allow_unstable, "run-in-subprocess");
     let exclude_should_panic = unstable_optflag!(matches, allow_unstable, "exclude-should-panic");
+    if force_run_in_process && run_in_subprocess {
+        return Err(
+            "the options --force-run-in-process and --run-in-subprocess are mutually exclusive"
+                .into(),
+        );
+    }
     let time_options = get_time_options(&matches, allow_unstable)?;
     let shuffle = get_shuffle(&matches, allow_unstable)?;
     let shuffle_seed = get_shuffle_seed(&matches, allow_unstable)?;
@@ -286,6 +302,7 @@ fn parse_opts_impl(matches: getopts::Matches) -> OptRes {
         filters,
         filter_exact: exact,
         force_run_in_process,
+        run_in_subprocess,
         exclude_should_panic,
         run_ignored,
         run_tests,
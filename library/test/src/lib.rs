@@ -154,6 +154,10 @@ pub fn test_main(args: &[String], tests: Vec<TestDescAndFn>, options: Option<Opt
 /// This is the entry point for the main function generated by `rustc --test`
 /// when panic=unwind.
 pub fn test_main_static(tests: &[&TestDescAndFn]) {
+    // Tests are only run in subprocesses with `--run-in-subprocess` here, in
+    // which case we may be one of them.
+    run_secondary_test_if_requested(tests);
+
     let args = env::args().collect::<Vec<_>>();
     let owned_tests: Vec<_> = tests.iter().map(make_owned_test).collect();
     test_main(&args, owned_tests, None)
@@ -168,42 +172,47 @@ pub fn test_main_static(tests: &[&TestDescAndFn]) {
 /// This is the entry point for the main function generated by `rustc --test`
 /// when panic=abort.
 pub fn test_main_static_abort(tests: &[&TestDescAndFn]) {
-    // If we're being run in SpawnedSecondary mode, run the test here. run_test
-    // will then exit the process.
-    if let Ok(name) = env::var(SECONDARY_TEST_INVOKER_VAR) {
-        env::remove_var(SECONDARY_TEST_INVOKER_VAR);
-
-        // Convert benchmarks to tests if we're not benchmarking.
-        let mut tests = tests.iter().map(make_owned_test).collect::<Vec<_>>();
-        if env::var(SECONDARY_TEST_BENCH_BENCHMARKS_VAR).is_ok() {
-            env::remove_var(SECONDARY_TEST_BENCH_BENCHMARKS_VAR);
-        } else {
-            tests = convert_benchmarks_to_tests(tests);
-        };
-
-        let test = tests
-            .into_iter()
-            .find(|test| test.desc.name.as_slice() == name)
-            .unwrap_or_else(|| panic!("couldn't find a test with the provided name '{name}'"));
-        let TestDescAndFn { desc, testfn } = test;
-        match testfn.into_runnable() {
-            Runnable::Test(runnable_test) => {
-                if runnable_test.is_dynamic() {
-                    panic!("only static tests are supported");
-                }
-                run_test_in_spawned_subprocess(desc, runnable_test);
-            }
-            Runnable::Bench(_) => {
-                panic!("benchmarks should not be executed into child processes")
-            }
-        }
-    }
+    run_secondary_test_if_requested(tests);
 
     let args = env::args().collect::<Vec<_>>();
     let owned_tests: Vec<_> = tests.iter().map(make_owned_test).collect();
     test_main(&args, owned_tests, Some(Options::new().panic_abort(true)))
 }
 
+/// If we're being run in SpawnedSecondary mode, runs the requested test here.
+/// run_test will then exit the process.
+fn run_secondary_test_if_requested(tests: &[&TestDescAndFn]) {
+    let Ok(name) = env::var(SECONDARY_TEST_INVOKER_VAR) else {
+        return;
+    };
+    env::remove_var(SECONDARY_TEST_INVOKER_VAR);
+
+    // Convert benchmarks to tests if we're not benchmarking.
+    let mut tests = tests.iter().map(make_owned_test).collect::<Vec<_>>();
+    if env::var(SECONDARY_TEST_BENCH_BENCHMARKS_VAR).is_ok() {
+        env::remove_var(SECONDARY_TEST_BENCH_BENCHMARKS_VAR);
+    } else {
+        tests = convert_benchmarks_to_tests(tests);
+    };
+
+    let test = tests
+        .into_iter()
+        .find(|test| test.desc.name.as_slice() == name)
+        .unwrap_or_else(|| panic!("couldn't find a test with the provided name '{name}'"));
+    let TestDescAndFn { desc, testfn } = test;
+    match testfn.into_runnable() {
+        Runnable::Test(runnable_test) => {
+            if runnable_test.is_dynamic() {
+                panic!("only static tests are supported");
+            }
+            run_test_in_spawned_subprocess(desc, runnable_test);
+        }
+        Runnable::Bench(_) => {
+            panic!("benchmarks should not be executed into child processes")
+        }
+    }
+}
+
 /// Clones static values for putting into a dynamic vector, which test_main()
 /// needs to hand out ownership of tests to parallel test runners.
 ///
@@ -335,11 +344,12 @@ struct TimeoutEntry {
     let mut pending = 0;
 
     let (tx, rx) = channel::<CompletedTest>();
-    let run_strategy = if opts.options.panic_abort && !opts.force_run_in_process {
-        RunStrategy::SpawnPrimary
-    } else {
-        RunStrategy::InProcess
-    };
+    let run_strategy =
+        if (opts.options.panic_abort && !opts.force_run_in_process) || opts.run_in_subprocess {
+            RunStrategy::SpawnPrimary
+        } else {
+            RunStrategy::InProcess
+        };
 
     let mut running_tests: TestMap = HashMap::default();
     let mut timeout_queue: VecDeque<TimeoutEntry> = VecDeque::new();
@@ -711,7 +721,15 @@ fn spawn_test_subprocess(
         formatters::write_stderr_delimiter(&mut test_output, &desc.name);
         test_output.extend_from_slice(&stderr);
 
-        let result = get_result_from_exit_code(&desc, status, &time_opts, &exec_time);
+        let result = match get_result_from_exit_code(&desc, status, &time_opts, &exec_time) {
+            // The test binary's stack overflow handler reports the overflow
+            // on stderr before aborting, point it out rather than leaving a
+            // bare failure.
+            TrFailed if is_stack_overflow_report(&stderr) => {
+                TrFailedMsg("test overflowed its stack".to_string())
+            }
+            result => result,
+        };
         (result, test_output, exec_time)
     })();
 
@@ -719,6 +737,12 @@ fn spawn_test_subprocess(
     monitor_ch.send(message).unwrap();
 }
 
+/// Whether `stderr` of a test subprocess holds std's stack overflow report.
+fn is_stack_overflow_report(stderr: &[u8]) -> bool {
+    let contains = |needle: &[u8]| stderr.windows(needle.len()).any(|w| w == needle);
+    contains(b"has overflowed its stack") && contains(b"fatal runtime error: stack overflow")
+}
+
 fn run_test_in_spawned_subprocess(desc: TestDesc, runnable_test: RunnableTest) -> ! {
     let builtin_panic_hook = panic::take_hook();
     let record_result = Arc::new(move |panic_info: Option<&'_ PanicHookInfo<'_>>| {
@@ -19,6 +19,7 @@ fn new() -> TestOpts {
             filters: vec![],
             filter_exact: false,
             force_run_in_process: false,
+            run_in_subprocess: false,
             exclude_should_panic: false,
             run_ignored: RunIgnored::No,
             run_tests: false,
@@ -478,6 +479,34 @@ fn parse_include_ignored_flag() {
     assert_eq!(opts.run_ignored, RunIgnored::Yes);
 }
 
+#[test]
+fn parse_run_in_subprocess_flag() {
+    let args = vec![
+        "progname".to_string(),
+        "--run-in-subprocess".to_string(),
+        "-Zunstable-options".to_string(),
+    ];
+    let opts = parse_opts(&args).unwrap().unwrap();
+    assert!(opts.run_in_subprocess);
+
+    let args = vec![
+        "progname".to_string(),
+        "--run-in-subprocess".to_string(),
+        "--force-run-in-process".to_string(),
+        "-Zunstable-options".to_string(),
+    ];
+    assert!(parse_opts(&args).unwrap().is_err());
+}
+
+#[test]
+fn stack_overflow_report() {
+    let stderr = b"\nthread 'tests::recurse' (id 2) has overflowed its stack\n\
+                   fatal runtime error: stack overflow\n";
+    assert!(is_stack_overflow_report(stderr));
+    assert!(!is_stack_overflow_report(b"thread 'main' has overflowed its stack\n"));
+    assert!(!is_stack_overflow_report(b"fatal runtime error: stack overflow\n"));
+}
+
 #[test]
 pub fn filter_for_ignored_option() {
     // When we run ignored tests the test filter should filter out all the
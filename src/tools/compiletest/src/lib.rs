@@ -579,6 +579,7 @@ pub fn test_opts(config: &Config) -> test::TestOpts {
         options: test::Options::new(),
         time_options: None,
         force_run_in_process: false,
+        run_in_subprocess: false,
         fail_fast: std::env::var_os("RUSTC_TEST_FAIL_FAST").is_some(),
     }
 }
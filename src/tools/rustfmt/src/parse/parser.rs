@@ -5,7 +5,7 @@
 use rustc_ast::{ast, attr, ptr};
 use rustc_errors::Diag;
 use rustc_parse::parser::Parser as RawParser;
-use rustc_parse::{new_parser_from_file, new_parser_from_source_str, unwrap_or_emit_fatal};
+use rustc_parse::{new_parser_from_crate_source_str, new_parser_from_file, unwrap_or_emit_fatal};
 use rustc_span::{sym, Span};
 use thin_vec::ThinVec;
 
@@ -66,7 +66,7 @@ fn parser(
     ) -> Result<RawParser<'a>, Vec<Diag<'a>>> {
         match input {
             Input::File(ref file) => new_parser_from_file(psess, file, None),
-            Input::Text(text) => new_parser_from_source_str(
+            Input::Text(text) => new_parser_from_crate_source_str(
                 psess,
                 rustc_span::FileName::Custom("stdin".to_owned()),
                 text,
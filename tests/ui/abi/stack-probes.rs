@@ -84,5 +84,5 @@ fn assert_overflow(cmd: &mut Command) {
     println!("stdout: {}", stdout);
     println!("stderr: {}", stderr);
     assert!(stdout.is_empty());
-    assert!(stderr.contains("has overflowed its stack\n"));
+    assert!(stderr.contains("has overflowed its stack"));
 }
@@ -0,0 +1,6 @@
+---
+[dependencies]
+---
+//~^^^ ERROR frontmatters are experimental
+
+fn main() {}
@@ -0,0 +1,10 @@
+#!/usr/bin/env -S cargo +nightly -Zscript
+---cargo
+[dependencies]
+---
+
+//@ run-pass
+
+#![feature(frontmatter)]
+
+pub fn main() {}
@@ -0,0 +1,11 @@
+// A custom inner attribute on the crate is given the crate pretty-printed, which the error for a
+// panic in it points out.
+
+//@ aux-crate:test_macros=test-macros.rs
+
+#![feature(custom_inner_attributes)]
+#![feature(prelude_import)]
+
+#![test_macros::panic_attr] //~ ERROR custom attribute panicked
+
+fn main() {}
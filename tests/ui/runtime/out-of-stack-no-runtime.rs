@@ -0,0 +1,84 @@
+//@ run-pass
+//@ only-unix
+//@ ignore-android: FIXME (#20004)
+//@ ignore-sgx no processes
+//@ ignore-fuchsia must translate zircon signal to SIGABRT, FIXME (#58590)
+//@ ignore-nto no stack overflow handler used (QNX has no sigaltstack to run it on)
+//@ ignore-ios stack overflow handlers aren't enabled
+//@ ignore-tvos stack overflow handlers aren't enabled
+//@ ignore-watchos stack overflow handlers aren't enabled
+//@ ignore-visionos stack overflow handlers aren't enabled
+
+// Programs that bypass the runtime's startup can still opt into stack
+// overflow detection with `std::os::unix::stack_overflow::init`.
+
+#![feature(start, rustc_private, unix_stack_overflow_init, unix_stack_overflow_status)]
+#![feature(unix_stack_overflow_trim)]
+#![allow(unconditional_recursion)]
+
+extern crate libc;
+
+use std::ffi::CStr;
+use std::hint::black_box;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::os::unix::stack_overflow;
+use std::process::Command;
+use std::thread;
+
+fn recurse() {
+    let buf = [0u8; 1000];
+    black_box(buf);
+    recurse();
+}
+
+#[start]
+fn start(argc: isize, argv: *const *const u8) -> isize {
+    let args = unsafe {
+        (0..argc as usize)
+            .map(|i| CStr::from_ptr(*argv.add(i) as *const _).to_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    if args.len() > 1 && args[1] == "overflow" {
+        stack_overflow::init();
+        thread::spawn(|| {
+            // A trimmed signal stack still has room for the handler.
+            stack_overflow::trim_signal_stack();
+            recurse()
+        })
+        .join()
+        .unwrap();
+        return 0;
+    }
+
+    // Nothing is set up until the program asks for it.
+    assert!(!stack_overflow::status().handlers_installed());
+    stack_overflow::init();
+    assert!(stack_overflow::status().handlers_installed());
+    // Doing it again is fine.
+    stack_overflow::init();
+
+    let output = Command::new(&args[0]).arg("overflow").output().unwrap();
+    assert_eq!(output.status.signal(), Some(libc::SIGABRT));
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("has overflowed its stack"), "missing overflow message: {}", error);
+
+    // Fault signals blocked by the parent are unblocked again.
+    let mut command = Command::new(&args[0]);
+    unsafe {
+        command.arg("overflow").pre_exec(|| {
+            let mut set = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, libc::SIGSEGV);
+            libc::sigaddset(&mut set, libc::SIGBUS);
+            libc::sigprocmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+            Ok(())
+        });
+    }
+    let output = command.output().unwrap();
+    assert_eq!(output.status.signal(), Some(libc::SIGABRT));
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("has overflowed its stack"), "missing overflow message: {}", error);
+
+    0
+}
@@ -12,7 +12,7 @@
 //@ ignore-watchos stack overflow handlers aren't enabled
 //@ ignore-visionos stack overflow handlers aren't enabled
 
-#![feature(rustc_private)]
+#![feature(rustc_private, thread_force_stack_overflow)]
 
 #[cfg(unix)]
 extern crate libc;
@@ -34,6 +34,18 @@ fn loud_recurse() {
     black_box(()); // don't optimize this into a tail call. please.
 }
 
+struct RecurseOnDrop;
+
+impl Drop for RecurseOnDrop {
+    fn drop(&mut self) {
+        silent_recurse();
+    }
+}
+
+thread_local! {
+    static RECURSE_ON_DROP: RecurseOnDrop = RecurseOnDrop;
+}
+
 #[cfg(unix)]
 fn check_status(status: std::process::ExitStatus)
 {
@@ -60,10 +72,17 @@ fn main() {
         thread::spawn(silent_recurse).join();
     } else if args.len() > 1 && args[1] == "loud-thread" {
         thread::spawn(loud_recurse).join();
+    } else if args.len() > 1 && args[1] == "forced-thread" {
+        thread::spawn(|| thread::force_stack_overflow()).join();
+    } else if args.len() > 1 && args[1] == "tls-dtor-thread" {
+        // Overflow while the thread's TLS is being torn down.
+        thread::spawn(|| RECURSE_ON_DROP.with(|_| ())).join();
     } else {
         let mut modes = vec![
             "silent-thread",
             "loud-thread",
+            "forced-thread",
+            "tls-dtor-thread",
         ];
 
         // On linux it looks like the main thread can sometimes grow its stack
@@ -83,6 +102,24 @@ fn main() {
             let error = String::from_utf8_lossy(&silent.stderr);
             assert!(error.contains("has overflowed its stack"),
                     "missing overflow message: {}", error);
+            // Spawned threads also report their stack size and how to raise it.
+            if cfg!(target_os = "linux") && mode.ends_with("-thread") && mode != "tls-dtor-thread" {
+                assert!(error.contains("(stack size: ") && error.contains("RUST_MIN_STACK"),
+                        "missing stack size note: {}", error);
+            }
+        }
+
+        // `RUST_STACK_OVERFLOW=quiet` still aborts, but without the report.
+        if cfg!(unix) {
+            let quiet = Command::new(&args[0])
+                .arg("silent-thread")
+                .env("RUST_STACK_OVERFLOW", "quiet")
+                .output()
+                .unwrap();
+            check_status(quiet.status);
+            let error = String::from_utf8_lossy(&quiet.stderr);
+            assert!(!error.contains("has overflowed its stack"),
+                    "unexpected overflow message: {}", error);
         }
     }
 }
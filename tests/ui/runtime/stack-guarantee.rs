@@ -0,0 +1,36 @@
+//@ run-pass
+//@ only-unix
+//@ only-64bit
+//@ ignore-loongarch64 stacks are only switched on x86_64 and aarch64
+//@ ignore-mips64 stacks are only switched on x86_64 and aarch64
+//@ ignore-powerpc64 stacks are only switched on x86_64 and aarch64
+//@ ignore-riscv64 stacks are only switched on x86_64 and aarch64
+//@ ignore-s390x stacks are only switched on x86_64 and aarch64
+//@ ignore-sparc64 stacks are only switched on x86_64 and aarch64
+//@ ignore-fuchsia stacks are only switched with the handler in sys/pal/unix/stack_overflow.rs
+//@ ignore-ios stack overflow handlers aren't enabled
+//@ ignore-tvos stack overflow handlers aren't enabled
+//@ ignore-watchos stack overflow handlers aren't enabled
+//@ ignore-visionos stack overflow handlers aren't enabled
+
+// Recursion that needs far more than the thread's stack gets through when it
+// goes through `with_stack_guarantee`.
+
+#![feature(thread_stack_guarantee)]
+
+use std::hint::black_box;
+use std::thread;
+
+fn depth(n: u64) -> u64 {
+    let buf = black_box([n as u8; 1024]);
+    if n == 0 {
+        return u64::from(buf[0]);
+    }
+    thread::with_stack_guarantee(64 * 1024, 1024 * 1024, || depth(n - 1) + 1)
+}
+
+fn main() {
+    // Some 50 MiB of frames on a 256 KiB stack.
+    let depth = thread::Builder::new().stack_size(256 * 1024).spawn(|| depth(50_000)).unwrap();
+    assert_eq!(depth.join().unwrap(), 50_000);
+}